@@ -0,0 +1,265 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::str::FromStr;
+
+use pw_diff_lib::changes::{Change, Changes};
+use pw_diff_lib::range::Range;
+use pw_diff_lib::sequence::Seq;
+
+/// The ANSI colours (SGR parameters such as "1;31") used to show a diff
+/// in a terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    /// File headers (`hd`).
+    pub header: String,
+    /// The line numbers in clump headers (`ln`).
+    pub clump_header: String,
+    /// Deleted lines (`de`).
+    pub deleted: String,
+    /// Added lines (`ad`).
+    pub added: String,
+    /// The words that differ in a deleted line (`dh`).  Applied on top of
+    /// `deleted` (and not at all if empty).
+    pub deleted_highlight: String,
+    /// The words that differ in an added line (`ah`).  Applied on top of
+    /// `added` (and not at all if empty).
+    pub added_highlight: String,
+    /// What ends a colour (`rs`).
+    pub reset: String,
+}
+
+impl Default for Palette {
+    /// As GNU `diff --color` with changed words in reverse video.
+    fn default() -> Self {
+        Self {
+            header: "1".to_string(),
+            clump_header: "36".to_string(),
+            deleted: "31".to_string(),
+            added: "32".to_string(),
+            deleted_highlight: "7".to_string(),
+            added_highlight: "7".to_string(),
+            reset: "0".to_string(),
+        }
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// The default palette with the colours given in GNU `diff --palette`
+    /// format (e.g. "ad=1;32:de=1;31") changed.
+    ///
+    /// Example:
+    /// ```
+    /// use pw_cub_diff_lib::colour::Palette;
+    ///
+    /// let palette: Palette = "ad=1;32:dh=4".parse().unwrap();
+    /// assert_eq!(palette.added, "1;32");
+    /// assert_eq!(palette.deleted_highlight, "4");
+    /// assert_eq!(palette.deleted, Palette::default().deleted);
+    /// assert!("xx=1".parse::<Palette>().is_err());
+    /// assert!("ad=red".parse::<Palette>().is_err());
+    /// ```
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut palette = Self::default();
+        for entry in text.split(':').filter(|entry| !entry.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("{entry:?}: expected KEY=VALUE"))?;
+            if !value.chars().all(|c| c.is_ascii_digit() || c == ';') {
+                return Err(format!("{value:?}: not an SGR parameter list"));
+            }
+            let colour = match key {
+                "hd" => &mut palette.header,
+                "ln" => &mut palette.clump_header,
+                "de" => &mut palette.deleted,
+                "ad" => &mut palette.added,
+                "dh" => &mut palette.deleted_highlight,
+                "ah" => &mut palette.added_highlight,
+                "rs" => &mut palette.reset,
+                _ => return Err(format!("{key:?}: unknown palette key")),
+            };
+            *colour = value.to_string();
+        }
+        Ok(palette)
+    }
+}
+
+impl Palette {
+    /// `text` in the colour `sgr`.
+    pub fn paint(&self, sgr: &str, text: &str) -> String {
+        format!("\x1b[{sgr}m{text}\x1b[{}m", self.reset)
+    }
+
+    /// Paints a deleted (or added) line with its changed words highlighted.
+    pub fn highlighter<'a>(&'a self, deleted: bool, highlights: &'a [Range]) -> Highlighter<'a> {
+        Highlighter {
+            palette: self,
+            colour: if deleted { &self.deleted } else { &self.added },
+            highlight: if deleted {
+                &self.deleted_highlight
+            } else {
+                &self.added_highlight
+            },
+            highlights,
+            highlighting: false,
+        }
+    }
+}
+
+/// Gives the escape sequences to write while writing a line in a colour
+/// with some (byte) ranges of it highlighted.
+#[derive(Debug)]
+pub struct Highlighter<'a> {
+    palette: &'a Palette,
+    colour: &'a str,
+    highlight: &'a str,
+    highlights: &'a [Range],
+    highlighting: bool,
+}
+
+impl<'a> Highlighter<'a> {
+    /// To write before the line.
+    pub fn start(&mut self) -> String {
+        self.highlighting = false;
+        format!("\x1b[{}m", self.colour)
+    }
+
+    /// To write before the character at byte `index` in the line (if
+    /// anything).
+    pub fn at(&mut self, index: usize) -> Option<String> {
+        let highlighting = !self.highlight.is_empty()
+            && self
+                .highlights
+                .iter()
+                .any(|range| range.start() <= index && index < range.end());
+        if highlighting == self.highlighting {
+            None
+        } else if highlighting {
+            self.highlighting = true;
+            Some(format!("\x1b[{}m", self.highlight))
+        } else {
+            self.highlighting = false;
+            Some(format!("\x1b[{}m\x1b[{}m", self.palette.reset, self.colour))
+        }
+    }
+
+    /// To write after the line.
+    pub fn end(&mut self) -> String {
+        self.highlighting = false;
+        format!("\x1b[{}m", self.palette.reset)
+    }
+
+    /// `prefix` followed by `text` (from the start of the line) painted.
+    pub fn paint(&mut self, prefix: &str, text: &str) -> String {
+        let mut painted = self.start();
+        painted.push_str(prefix);
+        for (index, c) in text.char_indices() {
+            if let Some(escape) = self.at(index) {
+                painted.push_str(&escape);
+            }
+            painted.push(c);
+        }
+        painted.push_str(&self.end());
+        painted
+    }
+}
+
+/// The start of each word in `text` (and its end): words are runs of
+/// alphanumeric characters (or '_') or of white space or are single
+/// characters of any other kind.
+fn word_boundaries(text: &str) -> Vec<usize> {
+    let mut boundaries = vec![];
+    let mut previous_kind = None;
+    for (index, c) in text.char_indices() {
+        let kind = if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        };
+        if kind == 2 || previous_kind != Some(kind) {
+            boundaries.push(index);
+        }
+        previous_kind = Some(kind);
+    }
+    boundaries.push(text.len());
+    boundaries
+}
+
+/// The (byte) ranges of the words that differ between a line and the
+/// line that replaced it (both without their line terminators), or
+/// `None` if they have no alphanumeric words in common (when showing
+/// which words differ wouldn't help).
+///
+/// Example:
+/// ```
+/// use pw_diff_lib::range::Range;
+/// use pw_cub_diff_lib::colour::changed_words;
+///
+/// assert_eq!(
+///     changed_words("let x = foo(1);", "let x = bar(1, 2);"),
+///     Some((vec![Range(8, 11)], vec![Range(8, 11), Range(13, 16)]))
+/// );
+/// assert_eq!(changed_words("one two", "three four"), None);
+/// ```
+pub fn changed_words(before: &str, after: &str) -> Option<(Vec<Range>, Vec<Range>)> {
+    let boundaries = [word_boundaries(before), word_boundaries(after)];
+    let words = |text: &str, boundaries: &[usize]| {
+        Seq(boundaries
+            .windows(2)
+            .map(|pair| text[pair[0]..pair[1]].to_string())
+            .collect())
+    };
+    let changes =
+        Changes::<String>::new(words(before, &boundaries[0]), words(after, &boundaries[1]));
+    let byte_range = |side: usize, range: &Range| {
+        Range(
+            boundaries[side][range.start()],
+            boundaries[side][range.end()],
+        )
+    };
+    let mut in_common = false;
+    let (mut before_ranges, mut after_ranges) = (vec![], vec![]);
+    for change in changes.changes.iter() {
+        match change {
+            Change::NoChange(common_subsequence) => {
+                in_common |= changes
+                    .before
+                    .subsequence(common_subsequence.before_range())
+                    .any(|word| word.chars().any(char::is_alphanumeric));
+            }
+            Change::Delete(before_range, _) => before_ranges.push(byte_range(0, before_range)),
+            Change::Insert(_, after_range) => after_ranges.push(byte_range(1, after_range)),
+            Change::Replace(before_range, after_range) => {
+                before_ranges.push(byte_range(0, before_range));
+                after_ranges.push(byte_range(1, after_range));
+            }
+        }
+    }
+    in_common.then_some((before_ranges, after_ranges))
+}
+
+/// The changed words (see `changed_words()`) in the lines of a
+/// `Change::Replace`.  Lines are compared with the line in the same
+/// position on the other side (if any).
+pub fn replaced_lines_highlights(
+    before_lines: &[&String],
+    after_lines: &[&String],
+) -> (Vec<Vec<Range>>, Vec<Vec<Range>>) {
+    let mut highlights = (
+        vec![vec![]; before_lines.len()],
+        vec![vec![]; after_lines.len()],
+    );
+    for (i, (before, after)) in before_lines.iter().zip(after_lines.iter()).enumerate() {
+        let trimmed = |line: &'_ String| line.trim_end_matches(['\n', '\r']).len();
+        if let Some((before_ranges, after_ranges)) =
+            changed_words(&before[..trimmed(before)], &after[..trimmed(after)])
+        {
+            highlights.0[i] = before_ranges;
+            highlights.1[i] = after_ranges;
+        }
+    }
+    highlights
+}
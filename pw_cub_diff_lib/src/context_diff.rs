@@ -0,0 +1,717 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::io;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::{Captures, Regex};
+
+use pw_diff_lib::apply_text::{ApplyClumpFuzzy, ApplyClumpsFuzzy, TextClumpBasics};
+use pw_diff_lib::changes::{Change, ChangeBasics, ChangeClumpIter, Changes};
+use pw_diff_lib::range::Range;
+use pw_diff_lib::sequence::Seq;
+use pw_diff_lib::snippet::Snippet;
+use pw_diff_lib::text_diff::TextChangeClump;
+
+use crate::function_line::FunctionLineMatcher;
+use crate::gnu_changes::gnu_changes;
+use crate::text_diff::{
+    clump_lengths, clump_lines, context_time_stamp, has_changes, read_lines, DiffParseError,
+    DiffParseResult, GnuFileDiff, GnuPatch, GnuTextDiff, PathAndTimestamp, StartAndLength,
+};
+use crate::unified_diff::{
+    before_path_and_time_stamp, heading, path_and_time_stamp, StartsAndLengths, NO_NEWLINE_AT_END,
+};
+use crate::{ALT_TIMESTAMP_RE_STR, PATH_RE_STR, TIMESTAMP_RE_STR};
+
+lazy_static::lazy_static! {
+    pub static ref CONTEXT_BEFORE_PATH_REGEX: Regex =
+        Regex::new(&format!(r"^\*\*\* ({PATH_RE_STR})\s+({TIMESTAMP_RE_STR}|{ALT_TIMESTAMP_RE_STR})?(.*)(\n)?$")).unwrap();
+
+    pub static ref CONTEXT_CLUMP_START_REGEX: Regex = Regex::new(r"^\*{15}(\s.*)?(\n)?$").unwrap();
+
+    pub static ref CONTEXT_BEFORE_RANGE_REGEX: Regex =
+        Regex::new(r"^\*\*\* (\d+)(,(\d+))? \*\*\*\*\s*(\n)?$").unwrap();
+
+    pub static ref CONTEXT_AFTER_RANGE_REGEX: Regex =
+        Regex::new(r"^--- (\d+)(,(\d+))? ----\s*(\n)?$").unwrap();
+}
+
+/// The line separating the clumps of a context diff.
+pub const CONTEXT_CLUMP_START: &str = "***************";
+
+pub fn context_before_path_and_time_stamp(line: &str) -> Option<PathAndTimestamp> {
+    path_and_time_stamp(line, &CONTEXT_BEFORE_PATH_REGEX)
+}
+
+/// The first line number and number of lines in a context diff range:
+/// `first,last` or just `last` (if there is one line or, when there are
+/// none, the line before).  The number of lines in the latter case is
+/// `None` as it depends on the lines that follow.
+fn first_and_length(
+    captures: &Captures,
+    line_number: usize,
+) -> DiffParseResult<(usize, Option<usize>)> {
+    let number = |index: usize| {
+        captures
+            .get(index)
+            .map(|m| usize::from_str(m.as_str()))
+            .transpose()
+            .map_err(|e| DiffParseError::ParseNumberError(e, line_number))
+    };
+    let first = number(1)?.ok_or(DiffParseError::SyntaxError(line_number, "a line number"))?;
+    match number(3)? {
+        Some(last) if last >= first => Ok((first, Some(last - first + 1))),
+        Some(_) => Err(DiffParseError::SyntaxError(
+            line_number,
+            "a range whose last line isn't before its first",
+        )),
+        None => Ok((first, None)),
+    }
+}
+
+/// The lines of one section (before or after) of a context diff clump.
+/// Each line is tagged with its marker: ' ', '-', '+' or '!'.
+struct Section {
+    lines: Vec<(char, String)>,
+}
+
+impl Section {
+    fn get_from_at(
+        lines: &Seq<String>,
+        start_index: usize,
+        length: usize,
+    ) -> DiffParseResult<(Self, usize)> {
+        let mut section_lines: Vec<(char, String)> = vec![];
+        let mut index = start_index;
+        while section_lines.len() < length || lines.get(index).is_some_and(|l| l.starts_with('\\'))
+        {
+            let line = lines
+                .get(index)
+                .ok_or(DiffParseError::UnexpectedEndOfInput)?;
+            if line.starts_with('\\') && !section_lines.is_empty() {
+                if let Some((_, text)) = section_lines.last_mut() {
+                    if text.ends_with('\n') {
+                        text.pop();
+                    }
+                }
+            } else {
+                let marker = match line.get(..2) {
+                    Some("  ") => ' ',
+                    Some("- ") => '-',
+                    Some("+ ") => '+',
+                    Some("! ") => '!',
+                    _ => return Err(DiffParseError::UnexpectedEndClump(index)),
+                };
+                section_lines.push((marker, line[2..].to_string()));
+            }
+            index += 1;
+        }
+        Ok((
+            Self {
+                lines: section_lines,
+            },
+            index - start_index,
+        ))
+    }
+
+    fn texts(&self) -> Box<[String]> {
+        self.lines.iter().map(|(_, text)| text.clone()).collect()
+    }
+
+    fn context_texts(&self) -> Box<[String]> {
+        self.lines
+            .iter()
+            .filter(|(marker, _)| *marker == ' ')
+            .map(|(_, text)| text.clone())
+            .collect()
+    }
+
+    fn context_lengths(&self) -> (u8, u8) {
+        let is_context = |line: &&(char, String)| line.0 == ' ';
+        (
+            self.lines.iter().take_while(is_context).count() as u8,
+            self.lines.iter().rev().take_while(is_context).count() as u8,
+        )
+    }
+}
+
+pub struct ContextDiffClump {
+    pub starts_and_lengths: StartsAndLengths,
+    /// The text after the clump's separator line's stars (e.g. a
+    /// function's first line).
+    pub heading: Option<String>,
+    pub before_lines: Box<[String]>,
+    pub after_lines: Box<[String]>,
+    pub context_lengths: (u8, u8),
+    pub lines_consumed: usize,
+}
+
+impl ContextDiffClump {
+    pub fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        let Some(captures) = lines
+            .get(start_index)
+            .and_then(|line| CONTEXT_CLUMP_START_REGEX.captures(line))
+        else {
+            return Ok(None);
+        };
+        let heading = captures.get(1).and_then(|text| heading(text.as_str()));
+        let mut index = start_index + 1;
+        let captures = lines
+            .get(index)
+            .and_then(|line| CONTEXT_BEFORE_RANGE_REGEX.captures(line))
+            .ok_or(DiffParseError::SyntaxError(index, "a `*** N,M ****` line"))?;
+        let (before_first, before_length) = first_and_length(&captures, index)?;
+        index += 1;
+        // Sections without changes are omitted.
+        let is_after_range = |index: usize| {
+            lines
+                .get(index)
+                .is_some_and(|line| CONTEXT_AFTER_RANGE_REGEX.is_match(line))
+        };
+        let before_section = if is_after_range(index) {
+            None
+        } else {
+            let (section, consumed) =
+                Section::get_from_at(lines, index, before_length.unwrap_or(1))?;
+            index += consumed;
+            Some(section)
+        };
+        let captures = lines
+            .get(index)
+            .and_then(|line| CONTEXT_AFTER_RANGE_REGEX.captures(line))
+            .ok_or(DiffParseError::SyntaxError(index, "a `--- N,M ----` line"))?;
+        let (after_first, after_length) = first_and_length(&captures, index)?;
+        index += 1;
+        let after_expected = match &before_section {
+            None => true,
+            Some(section) if section.lines.iter().any(|(marker, _)| *marker == '!') => true,
+            Some(_) => lines.get(index).is_some_and(|line| {
+                ["  ", "+ ", "! "]
+                    .iter()
+                    .any(|marker| line.starts_with(marker))
+            }),
+        };
+        let after_section = if after_expected {
+            let (section, consumed) =
+                Section::get_from_at(lines, index, after_length.unwrap_or(1))?;
+            index += consumed;
+            Some(section)
+        } else {
+            None
+        };
+        let (before_lines, after_lines, context_lengths) = match (before_section, after_section) {
+            (Some(before), Some(after)) => {
+                (before.texts(), after.texts(), before.context_lengths())
+            }
+            (Some(before), None) => (
+                before.texts(),
+                before.context_texts(),
+                before.context_lengths(),
+            ),
+            (None, Some(after)) => (
+                after.context_texts(),
+                after.texts(),
+                after.context_lengths(),
+            ),
+            (None, None) => {
+                return Err(DiffParseError::SyntaxError(
+                    start_index,
+                    "a clump with changed lines",
+                ))
+            }
+        };
+        let starts_and_lengths = StartsAndLengths {
+            before: StartAndLength {
+                start: before_first,
+                length: before_lines.len(),
+            },
+            after: StartAndLength {
+                start: after_first,
+                length: after_lines.len(),
+            },
+        };
+        Ok(Some(Self {
+            starts_and_lengths,
+            heading,
+            before_lines,
+            after_lines,
+            context_lengths,
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    fn start_and_length(&self, reverse: bool) -> &StartAndLength {
+        if reverse {
+            &self.starts_and_lengths.after
+        } else {
+            &self.starts_and_lengths.before
+        }
+    }
+}
+
+impl ChangeBasics for ContextDiffClump {
+    fn before_start(&self, reverse: bool) -> usize {
+        self.start_and_length(reverse).start_index()
+    }
+
+    fn before_end(&self, reverse: bool) -> usize {
+        self.before_start(reverse) + self.before_length(reverse)
+    }
+
+    fn before_length(&self, reverse: bool) -> usize {
+        self.start_and_length(reverse).length
+    }
+}
+
+impl TextClumpBasics for ContextDiffClump {
+    fn context_lengths(&self) -> (u8, u8) {
+        self.context_lengths
+    }
+
+    fn heading(&self) -> Option<&str> {
+        self.heading.as_deref()
+    }
+
+    fn before_lines(&self, range: Option<Range>, reverse: bool) -> impl Iterator<Item = &String> {
+        let lines = if reverse {
+            &self.after_lines
+        } else {
+            &self.before_lines
+        };
+        match range {
+            Some(range) => lines[range.start()..range.end()].iter(),
+            None => lines.iter(),
+        }
+    }
+}
+
+impl ApplyClumpFuzzy for ContextDiffClump {}
+
+impl From<&ContextDiffClump> for TextChangeClump {
+    fn from(clump: &ContextDiffClump) -> Self {
+        let mut text_change_clump = TextChangeClump::new(
+            Snippet {
+                start: clump.before_start(false),
+                items: clump.before_lines.clone(),
+            },
+            Snippet {
+                start: clump.after_start(false),
+                items: clump.after_lines.clone(),
+            },
+            clump.context_lengths,
+        );
+        text_change_clump.set_heading(clump.heading.clone());
+        text_change_clump
+    }
+}
+
+/// The context diff of a single file: its `***`/`---` header lines and
+/// clumps together with any other lines (e.g. a `diff` command line) that
+/// preceded it in the patch.
+pub struct ContextFileDiff {
+    pub preamble: Vec<String>,
+    pub before: PathAndTimestamp,
+    pub after: PathAndTimestamp,
+    pub clumps: Vec<ContextDiffClump>,
+    pub lines_consumed: usize,
+}
+
+impl GnuFileDiff for ContextFileDiff {
+    fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        let Some(before) = lines
+            .get(start_index)
+            .and_then(|line| context_before_path_and_time_stamp(line))
+        else {
+            return Ok(None);
+        };
+        // The after file's header line is the same as a unified diff's before.
+        let Some(after) = lines
+            .get(start_index + 1)
+            .and_then(|line| before_path_and_time_stamp(line))
+        else {
+            return Ok(None);
+        };
+        let mut index = start_index + 2;
+        let mut clumps = vec![];
+        while let Some(clump) = ContextDiffClump::get_from_at(lines, index)? {
+            index += clump.lines_consumed;
+            clumps.push(clump);
+        }
+        if clumps.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            preamble: vec![],
+            before,
+            after,
+            clumps,
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    fn lines_consumed(&self) -> usize {
+        self.lines_consumed
+    }
+
+    fn preamble(&self) -> &[String] {
+        &self.preamble
+    }
+
+    fn set_preamble(&mut self, preamble: Vec<String>) {
+        self.preamble = preamble;
+    }
+
+    fn header_paths(&self) -> Option<(&PathAndTimestamp, &PathAndTimestamp)> {
+        Some((&self.before, &self.after))
+    }
+
+    fn clump_lengths(&self) -> impl Iterator<Item = (usize, usize)> {
+        clump_lengths(self)
+    }
+
+    fn lines(&self, reverse: bool) -> Vec<String> {
+        clump_lines(self, reverse)
+    }
+
+    fn text_change_clumps(
+        &self,
+        _path: &Path,
+        _strip: usize,
+        _named: bool,
+    ) -> io::Result<Vec<TextChangeClump>> {
+        Ok(self.clumps.iter().map(TextChangeClump::from).collect())
+    }
+}
+
+impl ApplyClumpsFuzzy<ContextDiffClump> for ContextFileDiff {
+    fn clumps<'s>(&'s self) -> impl Iterator<Item = &'s ContextDiffClump>
+    where
+        ContextDiffClump: 's,
+    {
+        self.clumps.iter()
+    }
+}
+
+/// A patch consisting of the context diffs of one or more files possibly
+/// with other text before, between and after them.
+pub struct ContextPatch {
+    pub file_diffs: Vec<ContextFileDiff>,
+}
+
+impl GnuPatch for ContextPatch {
+    type FileDiff = ContextFileDiff;
+
+    fn from_file_diffs(file_diffs: Vec<ContextFileDiff>) -> Self {
+        Self { file_diffs }
+    }
+
+    fn file_diffs(&self) -> &[ContextFileDiff] {
+        &self.file_diffs
+    }
+}
+
+/// A range as shown in context diff clump headers (see `first_and_length()`).
+fn context_range(range: Range) -> String {
+    let (first, last) = (range.start() + 1, range.end());
+    if last <= first {
+        format!("{last}")
+    } else {
+        format!("{first},{last}")
+    }
+}
+
+/// The text of a context diff clump.
+pub struct ContextClumpText {
+    pub heading: Option<String>,
+    pub before_header: String,
+    pub before_lines: Vec<String>,
+    pub after_header: String,
+    pub after_lines: Vec<String>,
+}
+
+/// Iterates over the clumps of `changes` rendered as GNU `diff -c` does
+/// (with clumps whose context would overlap or abut merged).
+pub struct ContextClumpIter<'a> {
+    pub before: &'a Seq<String>,
+    pub after: &'a Seq<String>,
+    pub iter: Peekable<ChangeClumpIter<'a, String>>,
+    /// Finds the function lines shown after the clumps' separators (if any).
+    pub function_line_matcher: Option<&'a FunctionLineMatcher>,
+}
+
+impl<'a> ContextClumpIter<'a> {
+    pub fn new(changes: &'a Changes<String>, context: u8) -> Self {
+        Self {
+            before: &changes.before,
+            after: &changes.after,
+            iter: changes.change_clumps(context).peekable(),
+            function_line_matcher: None,
+        }
+    }
+
+    fn push_lines<'b>(
+        lines: &mut Vec<String>,
+        marker: char,
+        content: impl Iterator<Item = &'b String>,
+    ) {
+        for line in content {
+            if line.ends_with('\n') {
+                lines.push(format!("{marker} {line}"));
+            } else {
+                lines.push(format!("{marker} {line}\n"));
+                lines.push(NO_NEWLINE_AT_END.to_string());
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for ContextClumpIter<'a> {
+    type Item = ContextClumpText;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut change_clump = self.iter.next()?;
+        while let Some(next_clump) = self
+            .iter
+            .next_if(|next| next.starts() == change_clump.ends())
+        {
+            change_clump.extend(next_clump.changes);
+        }
+        let (before_range, after_range) = change_clump.ranges();
+
+        let mut before_lines = vec![];
+        let mut after_lines = vec![];
+        let (mut deletions, mut insertions) = (false, false);
+        for change in change_clump.iter() {
+            use Change::*;
+            match change {
+                NoChange(common_subsequence) => {
+                    let lines = self.before.subsequence(common_subsequence.before_range());
+                    Self::push_lines(&mut before_lines, ' ', lines);
+                    let lines = self.after.subsequence(common_subsequence.after_range());
+                    Self::push_lines(&mut after_lines, ' ', lines);
+                }
+                Delete(before_range, _) => {
+                    deletions = true;
+                    let lines = self.before.subsequence(*before_range);
+                    Self::push_lines(&mut before_lines, '-', lines);
+                }
+                Insert(_, after_range) => {
+                    insertions = true;
+                    let lines = self.after.subsequence(*after_range);
+                    Self::push_lines(&mut after_lines, '+', lines);
+                }
+                Replace(before_range, after_range) => {
+                    (deletions, insertions) = (true, true);
+                    let lines = self.before.subsequence(*before_range);
+                    Self::push_lines(&mut before_lines, '!', lines);
+                    let lines = self.after.subsequence(*after_range);
+                    Self::push_lines(&mut after_lines, '!', lines);
+                }
+            }
+        }
+        if !deletions {
+            before_lines.clear();
+        }
+        if !insertions {
+            after_lines.clear();
+        }
+
+        Some(ContextClumpText {
+            heading: self
+                .function_line_matcher
+                .and_then(|matcher| matcher.heading(self.before, before_range.start())),
+            before_header: format!("*** {} ****", context_range(before_range)),
+            before_lines,
+            after_header: format!("--- {} ----", context_range(after_range)),
+            after_lines,
+        })
+    }
+}
+
+/// The differences between two files in GNU `diff -c` format.
+pub struct ContextDiff {
+    before: PathAndTimestamp,
+    after: PathAndTimestamp,
+    changes: Changes<String>,
+    context: u8,
+    function_line_matcher: Option<FunctionLineMatcher>,
+}
+
+impl ContextDiff {
+    pub fn from_lines(
+        before: PathAndTimestamp,
+        before_lines: Seq<String>,
+        after: PathAndTimestamp,
+        after_lines: Seq<String>,
+        context: u8,
+    ) -> Self {
+        Self {
+            before,
+            after,
+            changes: gnu_changes(before_lines, after_lines, context),
+            context,
+            function_line_matcher: None,
+        }
+    }
+}
+
+impl GnuTextDiff for ContextDiff {
+    const FORMAT_NAME: &'static str = "context";
+    const DIFF_OPTIONS: &'static str = "-Nrc";
+
+    fn new(before_file_path: &Path, after_file_path: &Path, context: u8) -> io::Result<Self> {
+        Ok(Self::from_lines(
+            PathAndTimestamp::of_file_as(before_file_path, context_time_stamp)?,
+            read_lines(before_file_path)?,
+            PathAndTimestamp::of_file_as(after_file_path, context_time_stamp)?,
+            read_lines(after_file_path)?,
+            context,
+        ))
+    }
+
+    fn has_changes(&self) -> bool {
+        has_changes(&self.changes)
+    }
+
+    fn set_function_line_matcher(&mut self, matcher: Option<FunctionLineMatcher>) {
+        self.function_line_matcher = matcher;
+    }
+
+    fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        if !self.has_changes() {
+            return Ok(());
+        }
+        write!(into, "*** {}\n--- {}\n", self.before, self.after)?;
+        let mut clumps = ContextClumpIter::new(&self.changes, self.context);
+        clumps.function_line_matcher = self.function_line_matcher.as_ref();
+        for clump in clumps {
+            match &clump.heading {
+                Some(heading) => writeln!(into, "{CONTEXT_CLUMP_START} {heading}")?,
+                None => writeln!(into, "{CONTEXT_CLUMP_START}")?,
+            }
+            writeln!(into, "{}", clump.before_header)?;
+            for line in clump.before_lines.iter() {
+                into.write_all(line.as_bytes())?;
+            }
+            writeln!(into, "{}", clump.after_header)?;
+            for line in clump.after_lines.iter() {
+                into.write_all(line.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod context_diff_tests {
+    use super::*;
+
+    use pw_diff_lib::diff::Diff;
+
+    static BEFORE: &str = "a\nb\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn\n";
+    static AFTER: &str = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\nk\nl\nm\nn\no\n";
+
+    // As made by GNU `diff -C1`.
+    static CONTEXT_DIFF: &str = "*** before\tThu Feb 21 23:30:39 2002
+--- after\tThu Feb 21 23:30:50 2002
+***************
+*** 2,7 ****
+  b
+- b
+  c
+  d
+! e
+  f
+--- 2,6 ----
+  b
+  c
+  d
+! E
+  f
+***************
+*** 15 ****
+--- 14,15 ----
+  n
++ o
+";
+
+    fn context_diff_text(before: &str, after: &str, context: u8) -> String {
+        let path_and_time_stamp = |file_path: &str, time_stamp: &str| PathAndTimestamp {
+            file_path: file_path.to_string(),
+            time_stamp: Some(time_stamp.to_string()),
+        };
+        let context_diff = ContextDiff::from_lines(
+            path_and_time_stamp("before", "Thu Feb 21 23:30:39 2002"),
+            Seq::<String>::from(before),
+            path_and_time_stamp("after", "Thu Feb 21 23:30:50 2002"),
+            Seq::<String>::from(after),
+            context,
+        );
+        let mut text = vec![];
+        context_diff.write_into(&mut text).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn context_diff_matches_gnu_diff() {
+        assert_eq!(context_diff_text(BEFORE, AFTER, 1), CONTEXT_DIFF);
+        assert_eq!(context_diff_text(BEFORE, BEFORE, 1), "");
+        let expected = "*** before\tThu Feb 21 23:30:39 2002
+--- after\tThu Feb 21 23:30:50 2002
+***************
+*** 1,2 ****
+  x
+! y
+\\ No newline at end of file
+--- 1,2 ----
+  x
+! z
+\\ No newline at end of file
+";
+        assert_eq!(context_diff_text("x\ny", "x\nz", 3), expected);
+    }
+
+    #[test]
+    fn context_patch_parse_and_apply() {
+        let patch_text = format!(
+            "Some explanatory text.
+diff -Nrc a/new b/new
+*** a/new\tThu Jan  1 00:00:00 1970
+--- b/new\tThu Feb 21 23:30:50 2002
+***************
+*** 0 ****
+--- 1,2 ----
++ new
++ file
+{CONTEXT_DIFF}"
+        );
+        let patch = ContextPatch::from_lines(&Seq::<String>::from(patch_text)).unwrap();
+        assert_eq!(patch.file_diffs.len(), 2);
+        assert_eq!(patch.file_diffs[0].preamble.len(), 2);
+        assert!(patch.file_diffs[0].is_creation());
+        assert!(!patch.file_diffs[1].is_creation() && !patch.file_diffs[1].is_removal());
+
+        let file_diff = &patch.file_diffs[1];
+        assert_eq!(file_diff.clumps.len(), 2);
+        assert_eq!(file_diff.clumps[1].before_start(false), 14);
+        assert_eq!(file_diff.clumps[1].before_length(false), 1);
+        let mut patched = vec![];
+        let report = file_diff
+            .apply_into_reporting(&Seq::<String>::from(BEFORE), &mut patched, false)
+            .unwrap();
+        assert!(report.succeeded());
+        assert_eq!(String::from_utf8(patched).unwrap(), AFTER);
+        let mut unpatched = vec![];
+        file_diff
+            .apply_into(&Seq::<String>::from(AFTER), &mut unpatched, true)
+            .unwrap();
+        assert_eq!(String::from_utf8(unpatched).unwrap(), BEFORE);
+
+        let diff_set = patch.to_diff_set(1, None).unwrap();
+        let diffs: Vec<&Diff> = diff_set.diffs().collect();
+        assert!(matches!(diffs[0], Diff::TextAdd(_)));
+        assert!(matches!(diffs[1], Diff::TextChange(_)));
+    }
+}
@@ -0,0 +1,420 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use pw_diff_lib::changes::{Change, Changes};
+use pw_diff_lib::range::{Len, Range};
+use pw_diff_lib::sequence::Seq;
+use pw_diff_lib::snippet::Snippet;
+use pw_diff_lib::text_diff::TextChangeClump;
+
+use crate::gnu_changes::gnu_changes;
+use crate::text_diff::{
+    has_changes, read_lines, target_file, DiffParseError, DiffParseResult, GnuFileDiff, GnuPatch,
+    GnuTextDiff,
+};
+
+lazy_static::lazy_static! {
+    pub static ref ED_COMMAND_REGEX: Regex = Regex::new(r"^(\d+)(,(\d+))?([acd])\s*(\n)?$").unwrap();
+}
+
+/// The `ed` commands written by GNU `diff -e`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EdAction {
+    Append,
+    Change,
+    Delete,
+}
+
+/// One command of an `ed` script and the lines it adds.
+pub struct EdCommand {
+    pub action: EdAction,
+    pub first: usize,
+    pub last: usize,
+    pub lines: Vec<String>,
+    pub lines_consumed: usize,
+}
+
+fn is_dot(line: &str) -> bool {
+    line == "." || line == ".\n"
+}
+
+impl EdCommand {
+    pub fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        let Some(captures) = lines
+            .get(start_index)
+            .and_then(|line| ED_COMMAND_REGEX.captures(line))
+        else {
+            return Ok(None);
+        };
+        let number = |index: usize| {
+            captures
+                .get(index)
+                .map(|m| usize::from_str(m.as_str()))
+                .transpose()
+                .map_err(|e| DiffParseError::ParseNumberError(e, start_index))
+        };
+        let first = number(1)?.ok_or(DiffParseError::SyntaxError(start_index, "a line number"))?;
+        let last = number(3)?.unwrap_or(first);
+        let action = match &captures[4] {
+            "a" => EdAction::Append,
+            "c" => EdAction::Change,
+            _ => EdAction::Delete,
+        };
+        let bad_range = match action {
+            EdAction::Append => last != first,
+            EdAction::Change | EdAction::Delete => first == 0 || last < first,
+        };
+        if bad_range {
+            return Err(DiffParseError::SyntaxError(
+                start_index,
+                "an ed command with a valid range",
+            ));
+        }
+        let mut index = start_index + 1;
+        let mut text_lines: Vec<String> = vec![];
+        if action != EdAction::Delete {
+            // A line consisting of a single period is written as two then
+            // corrected by a substitution after which appending resumes.
+            loop {
+                loop {
+                    let line = lines
+                        .get(index)
+                        .ok_or(DiffParseError::UnexpectedEndOfInput)?;
+                    index += 1;
+                    if is_dot(line) {
+                        break;
+                    }
+                    text_lines.push(line.clone());
+                }
+                match lines.get(index) {
+                    Some(line) if line.trim_end() == "s/.//" => {
+                        match text_lines.last_mut() {
+                            Some(text) if text.starts_with('.') => {
+                                text.remove(0);
+                            }
+                            _ => {
+                                return Err(DiffParseError::SyntaxError(
+                                    index,
+                                    "an added line starting with '.' before `s/.//`",
+                                ))
+                            }
+                        }
+                        index += 1;
+                    }
+                    _ => break,
+                }
+                match lines.get(index) {
+                    Some(line) if line.trim_end() == "a" => index += 1,
+                    _ => break,
+                }
+            }
+        }
+        Ok(Some(Self {
+            action,
+            first,
+            last,
+            lines: text_lines,
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    /// The (zero based) range of the lines of the original file replaced.
+    pub fn before_range(&self) -> Range {
+        match self.action {
+            EdAction::Append => Range(self.first, self.first),
+            EdAction::Change | EdAction::Delete => Range(self.first - 1, self.last),
+        }
+    }
+}
+
+/// The `ed` script for a single file together with any other lines (e.g. a
+/// `diff` command line naming the files) that preceded it in the patch.
+pub struct EdFileDiff {
+    pub preamble: Vec<String>,
+    pub commands: Vec<EdCommand>,
+    pub lines_consumed: usize,
+}
+
+impl GnuFileDiff for EdFileDiff {
+    fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        let mut index = start_index;
+        let mut commands = vec![];
+        while let Some(command) = EdCommand::get_from_at(lines, index)? {
+            index += command.lines_consumed;
+            commands.push(command);
+        }
+        if commands.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            preamble: vec![],
+            commands,
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    fn lines_consumed(&self) -> usize {
+        self.lines_consumed
+    }
+
+    fn preamble(&self) -> &[String] {
+        &self.preamble
+    }
+
+    fn set_preamble(&mut self, preamble: Vec<String>) {
+        self.preamble = preamble;
+    }
+
+    fn clump_lengths(&self) -> impl Iterator<Item = (usize, usize)> {
+        self.commands
+            .iter()
+            .map(|command| (command.before_range().len(), command.lines.len()))
+    }
+
+    fn lines(&self, _reverse: bool) -> Vec<String> {
+        // Commands are in reverse order so that line numbers stay valid.
+        self.commands
+            .iter()
+            .rev()
+            .flat_map(|command| command.lines.iter().cloned())
+            .collect()
+    }
+
+    /// The file's lines are read as `ed` scripts don't include the lines
+    /// they delete.
+    fn text_change_clumps(
+        &self,
+        path: &Path,
+        strip: usize,
+        named: bool,
+    ) -> io::Result<Vec<TextChangeClump>> {
+        let before = read_lines(&target_file(path, strip, named)?)?;
+        let mut commands: Vec<&EdCommand> = self.commands.iter().collect();
+        commands.sort_by_key(|command| (command.before_range().start(), command.first));
+        let mut clumps = vec![];
+        let mut end = 0;
+        let mut offset: isize = 0;
+        for command in commands {
+            let range = command.before_range();
+            if range.start() < end || range.end() > before.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{path:?}: ed script doesn't fit the file"),
+                ));
+            }
+            end = range.end();
+            let before_lines: Box<[String]> = before.subsequence(range).cloned().collect();
+            let after_start = range.start().saturating_add_signed(offset);
+            offset += command.lines.len() as isize - before_lines.len() as isize;
+            clumps.push(TextChangeClump::new(
+                Snippet {
+                    start: range.start(),
+                    items: before_lines,
+                },
+                Snippet {
+                    start: after_start,
+                    items: command.lines.clone().into_boxed_slice(),
+                },
+                (0, 0),
+            ));
+        }
+        Ok(clumps)
+    }
+}
+
+/// A patch consisting of the `ed` scripts for one or more files possibly
+/// with other text before, between and after them.
+pub struct EdPatch {
+    pub file_diffs: Vec<EdFileDiff>,
+}
+
+impl GnuPatch for EdPatch {
+    type FileDiff = EdFileDiff;
+
+    fn from_file_diffs(file_diffs: Vec<EdFileDiff>) -> Self {
+        Self { file_diffs }
+    }
+
+    fn file_diffs(&self) -> &[EdFileDiff] {
+        &self.file_diffs
+    }
+}
+
+/// A range in an `ed` command: `first,last` or just `first`.
+fn ed_range(range: Range) -> String {
+    let (first, last) = (range.start() + 1, range.end());
+    if last <= first {
+        format!("{first}")
+    } else {
+        format!("{first},{last}")
+    }
+}
+
+/// The differences between two files as an `ed` script (as written by
+/// GNU `diff -e`).
+pub struct EdDiff {
+    changes: Changes<String>,
+}
+
+impl EdDiff {
+    pub fn from_lines(before_lines: Seq<String>, after_lines: Seq<String>) -> Self {
+        // As with GNU `diff -e`, files that differ only in a missing newline
+        // at the end are the same (see `write_lines()`).
+        let complete = |mut lines: Seq<String>| {
+            if let Some(last) = lines.0.last_mut() {
+                if !last.ends_with('\n') {
+                    last.push('\n');
+                }
+            }
+            lines
+        };
+        Self {
+            changes: gnu_changes(complete(before_lines), complete(after_lines), 0),
+        }
+    }
+
+    /// `ed` can't express a missing newline at the end of the last line so
+    /// one is added.
+    fn write_lines<'a, W: io::Write>(
+        into: &mut W,
+        lines: impl ExactSizeIterator<Item = &'a String>,
+    ) -> io::Result<()> {
+        let mut last_is_dot = false;
+        let count = lines.len();
+        for (i, line) in lines.enumerate() {
+            last_is_dot = is_dot(line);
+            if last_is_dot {
+                into.write_all(b"..\n.\ns/.//\n")?;
+                if i + 1 < count {
+                    into.write_all(b"a\n")?;
+                }
+            } else if line.ends_with('\n') {
+                into.write_all(line.as_bytes())?;
+            } else {
+                writeln!(into, "{line}")?;
+            }
+        }
+        if !last_is_dot {
+            into.write_all(b".\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl GnuTextDiff for EdDiff {
+    const FORMAT_NAME: &'static str = "ed";
+    const DIFF_OPTIONS: &'static str = "-Nre";
+
+    /// `ed` scripts have no context so `context` is ignored.
+    fn new(before_file_path: &Path, after_file_path: &Path, _context: u8) -> io::Result<Self> {
+        Ok(Self::from_lines(
+            read_lines(before_file_path)?,
+            read_lines(after_file_path)?,
+        ))
+    }
+
+    fn has_changes(&self) -> bool {
+        has_changes(&self.changes)
+    }
+
+    /// The commands are written last change first so that the line
+    /// numbers of those yet to be made aren't affected.
+    fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        let after = &self.changes.after;
+        for change in self.changes.changes.iter().rev() {
+            use Change::*;
+            match change {
+                NoChange(_) => (),
+                Delete(before_range, _) => writeln!(into, "{}d", ed_range(*before_range))?,
+                Insert(before_start, after_range) => {
+                    writeln!(into, "{before_start}a")?;
+                    let lines: Vec<&String> = after.subsequence(*after_range).collect();
+                    Self::write_lines(into, lines.into_iter())?;
+                }
+                Replace(before_range, after_range) => {
+                    writeln!(into, "{}c", ed_range(*before_range))?;
+                    let lines: Vec<&String> = after.subsequence(*after_range).collect();
+                    Self::write_lines(into, lines.into_iter())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ed_diff_tests {
+    use super::*;
+
+    use pw_diff_lib::apply_text::ApplyClumpsFuzzy;
+    use pw_diff_lib::diff::Diff;
+
+    static BEFORE: &str = "a\nb\nc\n.\nd\ne\n";
+    static AFTER: &str = "a\nB\nc\n.\n.\nd\nX\n";
+
+    // As made by GNU `diff -e`.
+    static ED_DIFF: &str = "6c
+X
+.
+4a
+..
+.
+s/.//
+2c
+B
+.
+";
+
+    #[test]
+    fn ed_diff_matches_gnu_diff() {
+        let ed_diff = EdDiff::from_lines(Seq::from(BEFORE), Seq::from(AFTER));
+        let mut text = vec![];
+        ed_diff.write_into(&mut text).unwrap();
+        assert_eq!(String::from_utf8(text).unwrap(), ED_DIFF);
+        let ed_diff = EdDiff::from_lines(Seq::from("a\n"), Seq::from(".\n.\nb\n"));
+        let mut text = vec![];
+        ed_diff.write_into(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "1c\n..\n.\ns/.//\na\n..\n.\ns/.//\na\nb\n.\n"
+        );
+    }
+
+    #[test]
+    fn ed_patch_parse_and_apply() {
+        let patch = EdPatch::from_lines(&Seq::from(ED_DIFF)).unwrap();
+        assert_eq!(patch.file_diffs.len(), 1);
+        let commands = &patch.file_diffs[0].commands;
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[1].action, EdAction::Append);
+        assert_eq!(commands[1].lines, vec![".\n".to_string()]);
+        assert_eq!(commands[2].before_range(), Range(1, 2));
+
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("f");
+        std::fs::write(&file_path, BEFORE).unwrap();
+        let Diff::TextChange(text_diff) = patch.file_diffs[0].to_diff(0, Some(&file_path)).unwrap()
+        else {
+            panic!("expected a text change");
+        };
+        let mut patched = vec![];
+        text_diff
+            .apply_into(&Seq::from(BEFORE), &mut patched, false)
+            .unwrap();
+        assert_eq!(String::from_utf8(patched).unwrap(), AFTER);
+
+        std::fs::write(&file_path, "a\n").unwrap();
+        assert!(patch.file_diffs[0].to_diff(0, Some(&file_path)).is_err());
+        let new_path = temp_dir.path().join("new");
+        let patch = EdPatch::from_lines(&Seq::from("0a\nx\n.\n")).unwrap();
+        assert!(matches!(
+            patch.file_diffs[0].to_diff(0, Some(&new_path)).unwrap(),
+            Diff::TextAdd(_)
+        ));
+    }
+}
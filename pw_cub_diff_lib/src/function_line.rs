@@ -0,0 +1,146 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::path::Path;
+
+use regex::Regex;
+
+use pw_diff_lib::sequence::Seq;
+
+/// The most bytes of a function line shown in a clump header (as GNU `diff`).
+pub const MAX_FUNCTION_LINE_LEN: usize = 40;
+
+/// GNU `diff -p`'s pattern (and git's default): a line starting with a
+/// letter, `_` or `$`.
+pub const DEFAULT_FUNCTION_LINE_RE_STR: &str = r"^[[:alpha:]$_]";
+
+/// Patterns for the lines that start functions (or sections) in various
+/// languages keyed by file extension (after git's `xfuncname` patterns).
+const LANGUAGE_FUNCTION_LINE_RE_STRS: &[(&[&str], &str)] = &[
+    (
+        &["rs"],
+        r#"^[\t ]*((pub(\([^)]+\))?[\t ]+)?((async|const|unsafe|extern([\t ]+"[^"]+")?)[\t ]+)*(struct|enum|union|mod|trait|fn|impl|macro_rules!)[<\t ]+[^;]*)$"#,
+    ),
+    (
+        &["py", "pyw"],
+        r"^[\t ]*((class|(async[\t ]+)?def)[\t ].*)$",
+    ),
+    (&["go"], r"^(func|type)[\t ].*$"),
+    (
+        &["java", "kt", "cs"],
+        r"^[\t ]*(((public|protected|private|static|abstract|final|internal|override|sealed)[\t ]+)*(class|interface|enum|record|object|fun)[\t ]+.*|[\t ]*((public|protected|private|static|final|synchronized)[\t ]+)+[\w<>\[\], ]+[\t ]+\w+[\t ]*\(.*)$",
+    ),
+    (
+        &["sh", "bash", "zsh"],
+        r"^[\t ]*((function[\t ]+)?[A-Za-z_][A-Za-z_0-9]*[\t ]*\(\)[\t ]*(\{.*)?|function[\t ]+[A-Za-z_][A-Za-z_0-9]*.*)$",
+    ),
+    (&["md", "markdown"], r"^ {0,3}#{1,6}[\t ].*$"),
+    (&["ini", "toml", "cfg"], r"^[\t ]*\[.*\][\t ]*$"),
+    (
+        &["c", "h", "cc", "cpp", "cxx", "hh", "hpp", "hxx", "m"],
+        DEFAULT_FUNCTION_LINE_RE_STR,
+    ),
+];
+
+/// Finds the line shown at the end of a clump's header (after the line
+/// ranges): the nearest line before the clump that starts a function (or
+/// section) as GNU `diff -p`/`-F` and git's `xfuncname` do.
+#[derive(Debug, Clone)]
+pub struct FunctionLineMatcher {
+    regex: Regex,
+}
+
+impl FunctionLineMatcher {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+        })
+    }
+
+    /// The matcher for the language of the file at `path` (as decided by
+    /// its extension) or, if it isn't known, GNU `diff -p`'s.
+    pub fn for_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let pattern = LANGUAGE_FUNCTION_LINE_RE_STRS
+            .iter()
+            .find(|(extensions, _)| {
+                extension
+                    .as_deref()
+                    .is_some_and(|extension| extensions.contains(&extension))
+            })
+            .map_or(DEFAULT_FUNCTION_LINE_RE_STR, |(_, pattern)| pattern);
+        Self::new(pattern).expect("built in patterns are valid")
+    }
+
+    /// The function line (trimmed and truncated) for a clump starting at
+    /// `start` in `lines`, if there is one.
+    ///
+    /// Example:
+    /// ```
+    /// use std::path::Path;
+    /// use pw_diff_lib::sequence::Seq;
+    /// use pw_cub_diff_lib::function_line::FunctionLineMatcher;
+    ///
+    /// let lines = Seq::<String>::from("use x;\n\npub fn f() {\n    a();\n    b();\n}\n");
+    /// let matcher = FunctionLineMatcher::for_path(Path::new("lib.rs"));
+    /// assert_eq!(matcher.heading(&lines, 4).as_deref(), Some("pub fn f() {"));
+    /// assert_eq!(matcher.heading(&lines, 2), None);
+    /// ```
+    pub fn heading(&self, lines: &Seq<String>, start: usize) -> Option<String> {
+        lines[..start.min(lines.len())]
+            .iter()
+            .rev()
+            .find(|line| self.regex.is_match(line.trim_end_matches(['\n', '\r'])))
+            .map(|line| {
+                let line = line.trim_start();
+                let mut end = line.len().min(MAX_FUNCTION_LINE_LEN);
+                while !line.is_char_boundary(end) {
+                    end -= 1;
+                }
+                line[..end].trim_end().to_string()
+            })
+    }
+}
+
+/// Which lines (if any) are shown in clump headers as the function that
+/// contains the clump.
+#[derive(Debug, Clone, Default)]
+pub enum FunctionLines {
+    #[default]
+    None,
+    /// As chosen by the file's extension (see `FunctionLineMatcher::for_path()`).
+    ByLanguage,
+    Matching(FunctionLineMatcher),
+}
+
+impl FunctionLines {
+    pub fn matcher_for(&self, path: &Path) -> Option<FunctionLineMatcher> {
+        match self {
+            Self::None => None,
+            Self::ByLanguage => Some(FunctionLineMatcher::for_path(path)),
+            Self::Matching(matcher) => Some(matcher.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod function_line_tests {
+    use super::*;
+
+    #[test]
+    fn function_lines_by_language() {
+        let lines =
+            Seq::<String>::from("class A:\n    def f(self):\n        x = 1\n\n        return x\n");
+        let python = FunctionLineMatcher::for_path(Path::new("a.py"));
+        assert_eq!(python.heading(&lines, 4).as_deref(), Some("def f(self):"));
+        assert_eq!(python.heading(&lines, 1).as_deref(), Some("class A:"));
+        let default = FunctionLineMatcher::for_path(Path::new("README"));
+        assert_eq!(default.heading(&lines, 4).as_deref(), Some("class A:"));
+
+        let lines = Seq::<String>::from(format!("{}é\nx\n", "a".repeat(39)));
+        let heading = default.heading(&lines, 1).unwrap();
+        assert_eq!(heading, "a".repeat(39));
+        assert!(FunctionLineMatcher::new("(").is_err());
+    }
+}
@@ -0,0 +1,635 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use sha1::{Digest, Sha1};
+
+use pw_diff_lib::metadata::file_mode;
+use pw_diff_lib::sequence::Seq;
+
+use crate::text_diff::{quoted_path, DiffParseError, DiffParseResult};
+
+const BASE85_CHARS: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// The name git uses in place of a missing file's blob name.
+pub const NULL_BLOB_HASH: &str = "0000000000000000000000000000000000000000";
+
+/// The most bytes encoded on each line of a binary hunk.
+const BYTES_PER_LINE: usize = 52;
+
+fn base85_value(c: u8) -> Option<u32> {
+    BASE85_CHARS
+        .iter()
+        .position(|&b| b == c)
+        .map(|value| value as u32)
+}
+
+/// Encode `bytes` as git does (four bytes to five characters with the
+/// last group zero padded).
+///
+/// Example:
+///
+/// ```
+/// use pw_cub_diff_lib::git_binary::{decode_base85, encode_base85};
+///
+/// let encoded = encode_base85(b"hello");
+/// assert_eq!(encoded, "Xk~0{ZvX%Q");
+/// assert_eq!(decode_base85(&encoded, 5), Some(b"hello".to_vec()));
+/// ```
+pub fn encode_base85(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(4) * 5);
+    for chunk in bytes.chunks(4) {
+        let mut acc = chunk
+            .iter()
+            .chain([0u8; 4].iter())
+            .take(4)
+            .fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+        let mut group = [0u8; 5];
+        for c in group.iter_mut().rev() {
+            *c = BASE85_CHARS[(acc % 85) as usize];
+            acc /= 85;
+        }
+        encoded.extend(group.iter().map(|&c| c as char));
+    }
+    encoded
+}
+
+/// Decode the first `length` bytes encoded in `text` (or `None` if `text`
+/// isn't valid base 85 of at least that length).
+pub fn decode_base85(text: &str, length: usize) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(5) || text.len() / 5 < length.div_ceil(4) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 5 * 4);
+    for group in text.chunks(5) {
+        let mut acc = 0u64;
+        for &c in group {
+            acc = acc * 85 + base85_value(c)? as u64;
+        }
+        bytes.extend_from_slice(&u32::try_from(acc).ok()?.to_be_bytes());
+    }
+    bytes.truncate(length);
+    Some(bytes)
+}
+
+fn deflated(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    // Writing to a Vec can't fail.
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_size(delta: &[u8], index: &mut usize) -> io::Result<usize> {
+    let mut size = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *delta
+            .get(*index)
+            .ok_or_else(|| invalid_data("truncated binary delta"))?;
+        *index += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+fn push_size(delta: &mut Vec<u8>, mut size: usize) {
+    while size >= 0x80 {
+        delta.push((size as u8 & 0x7f) | 0x80);
+        size >>= 7;
+    }
+    delta.push(size as u8);
+}
+
+fn push_copy(delta: &mut Vec<u8>, offset: usize, size: usize) {
+    let cmd_index = delta.len();
+    let mut cmd = 0x80u8;
+    delta.push(cmd);
+    for (i, byte) in (offset as u32).to_le_bytes().into_iter().enumerate() {
+        if byte != 0 {
+            cmd |= 1 << i;
+            delta.push(byte);
+        }
+    }
+    for (i, byte) in (size as u32).to_le_bytes()[..3].iter().enumerate() {
+        if *byte != 0 {
+            cmd |= 0x10 << i;
+            delta.push(*byte);
+        }
+    }
+    delta[cmd_index] = cmd;
+}
+
+fn push_copies(delta: &mut Vec<u8>, offset: usize, size: usize) {
+    for start in (offset..offset + size).step_by(0xffffff) {
+        push_copy(delta, start, (offset + size - start).min(0xffffff));
+    }
+}
+
+fn push_inserts(delta: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(0x7f) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+/// The size of the blocks of `before` that are indexed when looking for
+/// bytes to copy (as in git's `diff-delta.c`).
+const DELTA_BLOCK_SIZE: usize = 16;
+
+/// The most places indexed for any one block's content.
+const MAX_BLOCK_PLACES: usize = 64;
+
+/// A git delta that turns `before` into `after`.  Like git, `before` is
+/// indexed by the content of its (non overlapping) blocks and, wherever a
+/// block of `after` is found in the index, the longest match is copied
+/// (rather than inserted).
+fn delta(before: &[u8], after: &[u8]) -> Vec<u8> {
+    let mut index: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for start in (0..before.len().saturating_sub(DELTA_BLOCK_SIZE - 1)).step_by(DELTA_BLOCK_SIZE) {
+        let places = index
+            .entry(&before[start..start + DELTA_BLOCK_SIZE])
+            .or_default();
+        if places.len() < MAX_BLOCK_PLACES {
+            places.push(start);
+        }
+    }
+    let common_length = |b: usize, a: usize| {
+        before[b..]
+            .iter()
+            .zip(after[a..].iter())
+            .take_while(|(b, a)| b == a)
+            .count()
+    };
+    let mut delta = vec![];
+    push_size(&mut delta, before.len());
+    push_size(&mut delta, after.len());
+    let mut inserts_start = 0;
+    let mut at = 0;
+    while at + DELTA_BLOCK_SIZE <= after.len() {
+        let best = index
+            .get(&after[at..at + DELTA_BLOCK_SIZE])
+            .and_then(|places| {
+                places
+                    .iter()
+                    .map(|&place| (place, common_length(place, at)))
+                    .max_by_key(|&(_, length)| length)
+            });
+        let Some((mut offset, mut size)) = best else {
+            at += 1;
+            continue;
+        };
+        // The match may start before the block that was found.
+        while at > inserts_start && offset > 0 && before[offset - 1] == after[at - 1] {
+            at -= 1;
+            offset -= 1;
+            size += 1;
+        }
+        push_inserts(&mut delta, &after[inserts_start..at]);
+        push_copies(&mut delta, offset, size);
+        at += size;
+        inserts_start = at;
+    }
+    push_inserts(&mut delta, &after[inserts_start..]);
+    delta
+}
+
+/// Apply a git delta to `before`.
+fn apply_delta(delta: &[u8], before: &[u8]) -> io::Result<Vec<u8>> {
+    let mut index = 0;
+    if read_size(delta, &mut index)? != before.len() {
+        return Err(invalid_data("binary delta doesn't match the file's size"));
+    }
+    let after_size = read_size(delta, &mut index)?;
+    let mut after = Vec::with_capacity(after_size);
+    while let Some(&cmd) = delta.get(index) {
+        index += 1;
+        if cmd & 0x80 != 0 {
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for bit in 0..7 {
+                if cmd & (1 << bit) != 0 {
+                    let byte = *delta
+                        .get(index)
+                        .ok_or_else(|| invalid_data("truncated binary delta"))?;
+                    index += 1;
+                    if bit < 4 {
+                        offset |= (byte as usize) << (8 * bit);
+                    } else {
+                        size |= (byte as usize) << (8 * (bit - 4));
+                    }
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            let copied = before
+                .get(offset..offset + size)
+                .ok_or_else(|| invalid_data("binary delta copies from outside the file"))?;
+            after.extend_from_slice(copied);
+        } else if cmd != 0 {
+            let inserted = delta
+                .get(index..index + cmd as usize)
+                .ok_or_else(|| invalid_data("truncated binary delta"))?;
+            after.extend_from_slice(inserted);
+            index += cmd as usize;
+        } else {
+            return Err(invalid_data("invalid binary delta instruction"));
+        }
+    }
+    if after.len() == after_size {
+        Ok(after)
+    } else {
+        Err(invalid_data("binary delta produced the wrong size"))
+    }
+}
+
+/// The full (40 hex digit) name git gives to a blob with these contents.
+pub fn git_blob_hash(bytes: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", bytes.len()).as_bytes());
+    hasher.update(bytes);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryHunkKind {
+    Literal,
+    Delta,
+}
+
+/// One (`literal` or `delta`) hunk of a git binary patch with its payload
+/// inflated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryHunk {
+    pub kind: BinaryHunkKind,
+    pub data: Vec<u8>,
+}
+
+impl BinaryHunk {
+    /// Whichever of a literal or a delta compresses smaller (as git does).
+    pub fn new(before: &[u8], after: &[u8]) -> Self {
+        let literal = Self {
+            kind: BinaryHunkKind::Literal,
+            data: after.to_vec(),
+        };
+        if before.is_empty() || after.is_empty() {
+            return literal;
+        }
+        let delta = Self {
+            kind: BinaryHunkKind::Delta,
+            data: delta(before, after),
+        };
+        if deflated(&delta.data).len() < deflated(&literal.data).len() {
+            delta
+        } else {
+            literal
+        }
+    }
+
+    fn get_from_at(
+        lines: &Seq<String>,
+        start_index: usize,
+    ) -> DiffParseResult<Option<(Self, usize)>> {
+        let Some(line) = lines.get(start_index) else {
+            return Ok(None);
+        };
+        let line = line.trim_end_matches(['\n', '\r']);
+        let (kind, size) = if let Some(size) = line.strip_prefix("literal ") {
+            (BinaryHunkKind::Literal, size)
+        } else if let Some(size) = line.strip_prefix("delta ") {
+            (BinaryHunkKind::Delta, size)
+        } else {
+            return Ok(None);
+        };
+        let size =
+            usize::from_str(size).map_err(|e| DiffParseError::ParseNumberError(e, start_index))?;
+        let mut deflated = vec![];
+        let mut index = start_index + 1;
+        loop {
+            let line = lines
+                .get(index)
+                .ok_or(DiffParseError::UnexpectedEndOfInput)?
+                .trim_end_matches(['\n', '\r']);
+            index += 1;
+            let Some(&length_char) = line.as_bytes().first() else {
+                break;
+            };
+            let length = match length_char {
+                b'A'..=b'Z' => length_char - b'A' + 1,
+                b'a'..=b'z' => length_char - b'a' + 27,
+                _ => return Err(DiffParseError::SyntaxError(index - 1, "a base85 data line")),
+            };
+            let bytes = decode_base85(&line[1..], length as usize)
+                .ok_or(DiffParseError::SyntaxError(index - 1, "a base85 data line"))?;
+            deflated.extend(bytes);
+        }
+        let mut data = Vec::with_capacity(size);
+        ZlibDecoder::new(deflated.as_slice())
+            .read_to_end(&mut data)
+            .map_err(|_| DiffParseError::SyntaxError(start_index, "zlib compressed data"))?;
+        if data.len() != size {
+            return Err(DiffParseError::SyntaxError(
+                start_index,
+                "data of the size given",
+            ));
+        }
+        Ok(Some((Self { kind, data }, index - start_index)))
+    }
+
+    /// The contents that result from applying this hunk to `before`.
+    pub fn apply(&self, before: &[u8]) -> io::Result<Vec<u8>> {
+        match self.kind {
+            BinaryHunkKind::Literal => Ok(self.data.clone()),
+            BinaryHunkKind::Delta => apply_delta(&self.data, before),
+        }
+    }
+
+    pub fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        match self.kind {
+            BinaryHunkKind::Literal => writeln!(into, "literal {}", self.data.len())?,
+            BinaryHunkKind::Delta => writeln!(into, "delta {}", self.data.len())?,
+        }
+        for chunk in deflated(&self.data).chunks(BYTES_PER_LINE) {
+            let length_char = if chunk.len() <= 26 {
+                b'A' + chunk.len() as u8 - 1
+            } else {
+                b'a' + chunk.len() as u8 - 27
+            };
+            writeln!(into, "{}{}", length_char as char, encode_base85(chunk))?;
+        }
+        writeln!(into)
+    }
+}
+
+/// The `GIT binary patch` section of a git diff: how to get the new
+/// contents from the old and (usually) vice versa.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitBinaryPatch {
+    pub forward: BinaryHunk,
+    pub reverse: Option<BinaryHunk>,
+    pub lines_consumed: usize,
+}
+
+impl GitBinaryPatch {
+    pub fn new(before: &[u8], after: &[u8]) -> Self {
+        Self {
+            forward: BinaryHunk::new(before, after),
+            reverse: Some(BinaryHunk::new(after, before)),
+            lines_consumed: 0,
+        }
+    }
+
+    pub fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        match lines.get(start_index) {
+            Some(line) if line.trim_end_matches(['\n', '\r']) == "GIT binary patch" => (),
+            _ => return Ok(None),
+        }
+        let mut index = start_index + 1;
+        let (forward, consumed) = BinaryHunk::get_from_at(lines, index)?.ok_or(
+            DiffParseError::SyntaxError(index, "a `literal` or `delta` line"),
+        )?;
+        index += consumed;
+        let reverse = match BinaryHunk::get_from_at(lines, index)? {
+            Some((reverse, consumed)) => {
+                index += consumed;
+                Some(reverse)
+            }
+            None => None,
+        };
+        Ok(Some(Self {
+            forward,
+            reverse,
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    /// The before and after contents.  Deltas need the file's current
+    /// contents (obtained via `current` when necessary) to be decoded and
+    /// these may be either the before or the after contents.
+    pub fn contents<F>(&self, current: F) -> io::Result<(Vec<u8>, Vec<u8>)>
+    where
+        F: FnOnce() -> io::Result<Vec<u8>>,
+    {
+        use BinaryHunkKind::*;
+        match (&self.forward, &self.reverse) {
+            (forward, Some(reverse)) if reverse.kind == Literal => {
+                let after = forward.apply(&reverse.data)?;
+                Ok((reverse.data.clone(), after))
+            }
+            (forward, Some(reverse)) if forward.kind == Literal => {
+                let before = reverse.apply(&forward.data)?;
+                Ok((before, forward.data.clone()))
+            }
+            (forward, reverse) => {
+                // Applying the forward delta to the after contents can
+                // succeed (if the sizes match) so check that it round trips.
+                let current = current()?;
+                if let Ok(after) = forward.apply(&current) {
+                    if reverse
+                        .as_ref()
+                        .is_none_or(|reverse| reverse.apply(&after).is_ok_and(|b| b == current))
+                    {
+                        return Ok((current, after));
+                    }
+                }
+                match reverse {
+                    Some(reverse) => Ok((reverse.apply(&current)?, current)),
+                    None => Err(invalid_data("binary patch doesn't match the file")),
+                }
+            }
+        }
+    }
+
+    pub fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        writeln!(into, "GIT binary patch")?;
+        self.forward.write_into(into)?;
+        if let Some(reverse) = &self.reverse {
+            reverse.write_into(into)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// The mode git records for the (regular) file at `path`.
+fn git_mode(path: &Path) -> io::Result<u32> {
+    match file_mode(path)? {
+        Some(mode) if mode & 0o111 != 0 => Ok(0o100755),
+        _ => Ok(0o100644),
+    }
+}
+
+/// Write the difference between the files at the given paths (either of
+/// which may be missing) as `git diff --binary` would.
+pub fn write_git_binary_diff<W: io::Write>(
+    before_file_path: &Path,
+    after_file_path: &Path,
+    into: &mut W,
+) -> io::Result<()> {
+    let before = read_if_exists(before_file_path)?;
+    let after = read_if_exists(after_file_path)?;
+    let hash = |bytes: &Option<Vec<u8>>| {
+        bytes
+            .as_deref()
+            .map_or(NULL_BLOB_HASH.to_string(), git_blob_hash)
+    };
+    let hashes = format!("{}..{}", hash(&before), hash(&after));
+    writeln!(
+        into,
+        "diff --git {} {}",
+        quoted_path(&before_file_path.to_string_lossy()),
+        quoted_path(&after_file_path.to_string_lossy())
+    )?;
+    match (&before, &after) {
+        (None, _) => {
+            writeln!(into, "new file mode {:o}", git_mode(after_file_path)?)?;
+            writeln!(into, "index {hashes}")?;
+        }
+        (_, None) => {
+            writeln!(into, "deleted file mode {:o}", git_mode(before_file_path)?)?;
+            writeln!(into, "index {hashes}")?;
+        }
+        _ => {
+            let before_mode = git_mode(before_file_path)?;
+            let after_mode = git_mode(after_file_path)?;
+            if before_mode == after_mode {
+                writeln!(into, "index {hashes} {before_mode:o}")?;
+            } else {
+                writeln!(into, "old mode {before_mode:o}")?;
+                writeln!(into, "new mode {after_mode:o}")?;
+                writeln!(into, "index {hashes}")?;
+            }
+        }
+    }
+    GitBinaryPatch::new(
+        before.as_deref().unwrap_or_default(),
+        after.as_deref().unwrap_or_default(),
+    )
+    .write_into(into)
+}
+
+#[cfg(test)]
+mod git_binary_tests {
+    use super::*;
+
+    #[test]
+    fn binary_deltas() {
+        let before: Vec<u8> = (0..200u8).chain(0..200u8).collect();
+        let mut after = before.clone();
+        after.splice(150..160, [0u8, 1, 2, 3, 4]);
+        let delta = delta(&before, &after);
+        assert!(delta.len() < 20);
+        assert_eq!(apply_delta(&delta, &before).unwrap(), after);
+        assert!(apply_delta(&delta, &after).is_err());
+
+        // An edit in the middle of the file and an addition at its end.
+        let before: Vec<u8> = (0..3000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let mut after = before.clone();
+        after.splice(1500..1510, *b"0123456789");
+        after.extend_from_slice(&[b'x'; 50]);
+        let delta = super::delta(&before, &after);
+        assert!(delta.len() < 100);
+        assert_eq!(apply_delta(&delta, &before).unwrap(), after);
+    }
+
+    #[test]
+    fn git_binary_patch_round_trip() {
+        let before: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let mut after = before.clone();
+        after[500] = 0;
+        let patch = GitBinaryPatch::new(&before, &after);
+        assert_eq!(patch.forward.kind, BinaryHunkKind::Delta);
+        let mut text = vec![];
+        patch.write_into(&mut text).unwrap();
+        let lines = Seq::<String>::from(String::from_utf8(text).unwrap());
+        let parsed = GitBinaryPatch::get_from_at(&lines, 0).unwrap().unwrap();
+        assert_eq!(parsed.lines_consumed, lines.len());
+        assert_eq!(parsed.forward, patch.forward);
+        let current = || Ok(after.clone());
+        assert_eq!(
+            parsed.contents(current).unwrap(),
+            (before.clone(), after.clone())
+        );
+
+        // As made by `git diff --binary` for a new file containing "hello\0".
+        let lines = Seq::<String>::from(
+            "GIT binary patch\nliteral 6\nNcmc~u&B@7U000O<0u=xN\n\nliteral 0\nHcmV?d00001\n\n",
+        );
+        let parsed = GitBinaryPatch::get_from_at(&lines, 0).unwrap().unwrap();
+        assert_eq!(parsed.lines_consumed, 7);
+        let current = || Err(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(
+            parsed.contents(current).unwrap(),
+            (vec![], b"hello\0".to_vec())
+        );
+        assert_eq!(
+            git_blob_hash(b""),
+            "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+        );
+    }
+
+    #[test]
+    fn identical_binary_files_are_not_mentioned() {
+        use pw_diff_lib::diff::Diff;
+
+        use crate::function_line::FunctionLines;
+        use crate::text_diff::write_diff;
+        use crate::unified_diff::UnifiedDiff;
+
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let before_path = temp_dir.path().join("before");
+        let after_path = temp_dir.path().join("after");
+        std::fs::write(&before_path, b"binary\0content").unwrap();
+        std::fs::write(&after_path, b"binary\0content").unwrap();
+        let write = |binary: bool| {
+            let diff = Diff::new(&before_path, &after_path, 3).unwrap();
+            let mut text = vec![];
+            write_diff::<UnifiedDiff, _>(
+                &diff,
+                &before_path,
+                &after_path,
+                3,
+                &FunctionLines::default(),
+                binary,
+                &mut text,
+            )
+            .unwrap();
+            String::from_utf8(text).unwrap()
+        };
+        assert_eq!(write(true), "");
+        assert_eq!(write(false), "");
+
+        std::fs::write(&after_path, b"binary\0changed").unwrap();
+        assert!(write(true).starts_with("diff --git "));
+        assert!(write(false).starts_with("Binary files "));
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::str::FromStr;
+
+use pw_diff_lib::metadata::Modes;
+use pw_diff_lib::sequence::Seq;
+
+use crate::text_diff::{unquoted_path, DiffParseError, DiffParseResult};
+
+/// The file type bits of a git mode.
+pub const GIT_TYPE_MASK: u32 = 0o170000;
+pub const GIT_REGULAR_FILE: u32 = 0o100000;
+pub const GIT_SYMLINK: u32 = 0o120000;
+
+/// The `index <hash>..<hash> [<mode>]` line of a git diff header.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct GitIndex {
+    pub before_hash: String,
+    pub after_hash: String,
+    pub mode: Option<u32>,
+}
+
+/// The `diff --git` line and extended header lines that precede (or, for
+/// changes that don't touch content, replace) the `---`/`+++` lines of a
+/// file's diff in patches generated by git.  Modes are as git records
+/// them: file type bits and permissions (e.g. 0o100644).
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct GitHeader {
+    pub before_path: String,
+    pub after_path: String,
+    pub old_mode: Option<u32>,
+    pub new_mode: Option<u32>,
+    pub deleted_file_mode: Option<u32>,
+    pub new_file_mode: Option<u32>,
+    pub copy_from: Option<String>,
+    pub copy_to: Option<String>,
+    pub rename_from: Option<String>,
+    pub rename_to: Option<String>,
+    pub similarity_index: Option<u8>,
+    pub dissimilarity_index: Option<u8>,
+    pub index: Option<GitIndex>,
+    /// "Binary files ... differ" (i.e. the content change is not included).
+    pub binary: bool,
+    pub lines_consumed: usize,
+}
+
+fn path_from(text: &str) -> String {
+    match text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    {
+        Some(quoted) => unquoted_path(quoted),
+        None => text.to_string(),
+    }
+}
+
+/// The length of the (possibly quoted) path at the start of `text` if it
+/// is quoted.
+fn quoted_length(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(i + 1),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Split the paths in a `diff --git` line.  Unquoted paths may contain
+/// spaces so, as git does, choose the split that gives the same name (or
+/// the names `from` and `to` of a rename or copy) after the prefixes.
+fn split_paths(text: &str, from_and_to: Option<(&str, &str)>) -> Option<(String, String)> {
+    if text.starts_with('"') {
+        let length = quoted_length(text)?;
+        let rest = text[length..].strip_prefix(' ')?;
+        return Some((path_from(&text[..length]), path_from(rest)));
+    }
+    if let Some((before, after)) = text.split_once(" \"") {
+        return Some((before.to_string(), path_from(&format!("\"{after}"))));
+    }
+    let name = |path: &str| {
+        path.split_once('/')
+            .map_or(path.to_string(), |s| s.1.to_string())
+    };
+    let splits: Vec<(&str, &str)> = text
+        .match_indices(' ')
+        .map(|(i, _)| (&text[..i], &text[i + 1..]))
+        .collect();
+    let split = match from_and_to {
+        Some((from, to)) => splits
+            .iter()
+            .find(|(before, after)| before.ends_with(from) && after.ends_with(to)),
+        None => splits
+            .iter()
+            .find(|(before, after)| name(before) == name(after)),
+    };
+    split
+        .or(splits.first())
+        .map(|(before, after)| (before.to_string(), after.to_string()))
+}
+
+fn parse_mode(text: &str, line_number: usize) -> DiffParseResult<u32> {
+    u32::from_str_radix(text.trim(), 8)
+        .map_err(|e| DiffParseError::ParseNumberError(e, line_number))
+}
+
+fn parse_percentage(text: &str, line_number: usize) -> DiffParseResult<u8> {
+    u8::from_str(text.trim().trim_end_matches('%'))
+        .map_err(|e| DiffParseError::ParseNumberError(e, line_number))
+}
+
+impl GitHeader {
+    pub fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        let Some(paths) = lines
+            .get(start_index)
+            .and_then(|line| line.strip_prefix("diff --git "))
+            .map(|text| text.trim_end_matches(['\n', '\r']))
+        else {
+            return Ok(None);
+        };
+        let mut header = Self::default();
+        let mut index = start_index + 1;
+        while let Some(line) = lines.get(index) {
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(mode) = line.strip_prefix("old mode ") {
+                header.old_mode = Some(parse_mode(mode, index)?);
+            } else if let Some(mode) = line.strip_prefix("new mode ") {
+                header.new_mode = Some(parse_mode(mode, index)?);
+            } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+                header.deleted_file_mode = Some(parse_mode(mode, index)?);
+            } else if let Some(mode) = line.strip_prefix("new file mode ") {
+                header.new_file_mode = Some(parse_mode(mode, index)?);
+            } else if let Some(path) = line.strip_prefix("copy from ") {
+                header.copy_from = Some(path_from(path));
+            } else if let Some(path) = line.strip_prefix("copy to ") {
+                header.copy_to = Some(path_from(path));
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                header.rename_from = Some(path_from(path));
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                header.rename_to = Some(path_from(path));
+            } else if let Some(percentage) = line.strip_prefix("similarity index ") {
+                header.similarity_index = Some(parse_percentage(percentage, index)?);
+            } else if let Some(percentage) = line.strip_prefix("dissimilarity index ") {
+                header.dissimilarity_index = Some(parse_percentage(percentage, index)?);
+            } else if let Some(text) = line.strip_prefix("index ") {
+                let (hashes, mode) = match text.split_once(' ') {
+                    Some((hashes, mode)) => (hashes, Some(parse_mode(mode, index)?)),
+                    None => (text, None),
+                };
+                let (before_hash, after_hash) = hashes.split_once("..").ok_or(
+                    DiffParseError::SyntaxError(index, "an `index <hash>..<hash>` line"),
+                )?;
+                header.index = Some(GitIndex {
+                    before_hash: before_hash.to_string(),
+                    after_hash: after_hash.to_string(),
+                    mode,
+                });
+            } else if line.starts_with("Binary files ") && line.ends_with(" differ") {
+                header.binary = true;
+                index += 1;
+                break;
+            } else {
+                break;
+            }
+            index += 1;
+        }
+        let from_and_to = match (&header.rename_from, &header.rename_to) {
+            (Some(from), Some(to)) => Some((from.as_str(), to.as_str())),
+            _ => match (&header.copy_from, &header.copy_to) {
+                (Some(from), Some(to)) => Some((from.as_str(), to.as_str())),
+                _ => None,
+            },
+        };
+        let (before_path, after_path) = split_paths(paths, from_and_to).ok_or(
+            DiffParseError::SyntaxError(start_index, "a `diff --git` line naming the files"),
+        )?;
+        header.before_path = before_path;
+        header.after_path = after_path;
+        header.lines_consumed = index - start_index;
+        Ok(Some(header))
+    }
+
+    pub fn is_creation(&self) -> bool {
+        self.new_file_mode.is_some()
+    }
+
+    pub fn is_removal(&self) -> bool {
+        self.deleted_file_mode.is_some()
+    }
+
+    pub fn is_rename(&self) -> bool {
+        self.rename_from.is_some() && self.rename_to.is_some()
+    }
+
+    pub fn is_copy(&self) -> bool {
+        self.copy_from.is_some() && self.copy_to.is_some()
+    }
+
+    pub fn before_mode(&self) -> Option<u32> {
+        self.old_mode
+            .or(self.deleted_file_mode)
+            .or(self.index.as_ref().and_then(|index| index.mode))
+    }
+
+    pub fn after_mode(&self) -> Option<u32> {
+        self.new_mode
+            .or(self.new_file_mode)
+            .or(self.index.as_ref().and_then(|index| index.mode))
+    }
+
+    /// Whether the file is a symbolic link (whose content is its target).
+    pub fn is_symlink(&self) -> bool {
+        [self.before_mode(), self.after_mode()]
+            .iter()
+            .flatten()
+            .any(|mode| mode & GIT_TYPE_MASK == GIT_SYMLINK)
+    }
+
+    /// The change to the file's permissions (if any).
+    pub fn modes(&self) -> Option<Modes> {
+        match (self.old_mode, self.new_mode) {
+            (Some(old_mode), Some(new_mode)) if old_mode != new_mode => {
+                Some(Modes::new(old_mode & 0o7777, new_mode & 0o7777))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod git_diff_tests {
+    use super::*;
+
+    #[test]
+    fn git_headers() {
+        let lines = Seq::<String>::from(
+            "diff --git a/old name b/new name
+similarity index 90%
+rename from old name
+rename to new name
+index 1234567..89abcde 100755
+--- a/old name
+diff --git a/script b/script
+old mode 100644
+new mode 100755
+diff --git a/link b/link
+new file mode 120000
+index 0000000..e69de29
+diff --git a/image.png b/image.png
+index 1234567..89abcde 100644
+Binary files a/image.png and b/image.png differ
+diff --git \"a/tab\\there\" \"b/tab\\there\"
+deleted file mode 100644
+",
+        );
+        let header = GitHeader::get_from_at(&lines, 0).unwrap().unwrap();
+        assert_eq!(header.before_path, "a/old name");
+        assert_eq!(header.after_path, "b/new name");
+        assert!(header.is_rename() && !header.is_copy());
+        assert_eq!(header.similarity_index, Some(90));
+        assert_eq!(header.before_mode(), Some(0o100755));
+        assert_eq!(header.lines_consumed, 5);
+        assert!(GitHeader::get_from_at(&lines, 5).unwrap().is_none());
+
+        let header = GitHeader::get_from_at(&lines, 6).unwrap().unwrap();
+        assert_eq!(header.modes(), Some(Modes::new(0o644, 0o755)));
+        assert_eq!(header.lines_consumed, 3);
+
+        let header = GitHeader::get_from_at(&lines, 9).unwrap().unwrap();
+        assert!(header.is_creation() && header.is_symlink());
+
+        let header = GitHeader::get_from_at(&lines, 12).unwrap().unwrap();
+        assert!(header.binary && !header.is_symlink());
+        assert_eq!(header.lines_consumed, 3);
+
+        let header = GitHeader::get_from_at(&lines, 15).unwrap().unwrap();
+        assert_eq!(header.before_path, "a/tab\there");
+        assert_eq!(header.after_path, "b/tab\there");
+        assert!(header.is_removal());
+    }
+}
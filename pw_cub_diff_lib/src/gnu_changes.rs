@@ -0,0 +1,627 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+//! The changes between two files as GNU `diff` finds them (following
+//! `analyze.c` in GNU diffutils and `diffseq.h` in gnulib) so that the GNU
+//! formats can be written exactly as GNU `diff` would write them.
+
+use std::collections::HashMap;
+
+use pw_diff_lib::changes::{Change, Changes};
+use pw_diff_lib::common_subsequence::CommonSubsequence;
+use pw_diff_lib::range::Range;
+use pw_diff_lib::sequence::Seq;
+
+/// The changes that turn `before` into `after` as GNU `diff` presents
+/// them when `horizon` lines (the context, for formats that have one) of
+/// the files' identical ends are taken into account.  The lines that differ
+/// are found with Myers' algorithm after lines that have no (or too many)
+/// matches in the other file are set aside.  Where the changed lines are
+/// ambiguous (e.g. one of a run of identical lines deleted) a run of
+/// changed lines is slid down as far as it will go and then back up to
+/// meet any run of changes in the other file.
+pub fn gnu_changes(before: Seq<String>, after: Seq<String>, horizon: u8) -> Changes<String> {
+    let horizon = horizon as usize;
+    let lens = [before.len(), after.len()];
+    let shorter = lens[0].min(lens[1]);
+    let prefix = (0..shorter).take_while(|&k| before[k] == after[k]).count();
+    let start = prefix.saturating_sub(horizon);
+    let suffix = (0..shorter - start)
+        .take_while(|&k| before[lens[0] - 1 - k] == after[lens[1] - 1 - k])
+        .count();
+    let trimmed = suffix - suffix.min(horizon);
+
+    // Only the lines between the files' identical ends are compared and
+    // identical lines share an equivalence class (numbered from 1).
+    let mut classes: HashMap<&String, usize> = HashMap::new();
+    let equivs = [&before, &after].map(|lines| {
+        (start..lines.len() - trimmed)
+            .map(|k| {
+                let next_class = classes.len() + 1;
+                *classes.entry(&lines[k]).or_insert(next_class)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // Changed flags have a false sentinel at each end (so index k + 1 is line k).
+    let mut changed = [
+        vec![false; equivs[0].len() + 2],
+        vec![false; equivs[1].len() + 2],
+    ];
+    let undiscarded = discard_confusing_lines(&equivs, classes.len() + 1, &mut changed);
+    Comparison::new(&undiscarded, &mut changed).compare_seq(
+        0,
+        undiscarded[0].0.len(),
+        0,
+        undiscarded[1].0.len(),
+        false,
+    );
+    shift_boundaries(&equivs, &mut changed);
+
+    let mut script = vec![];
+    let (mut i, mut j) = (0, 0);
+    let is_changed =
+        |f: usize, k: usize| (start..lens[f] - trimmed).contains(&k) && changed[f][k - start + 1];
+    while i < lens[0] || j < lens[1] {
+        let (i0, j0) = (i, j);
+        while i < lens[0] && j < lens[1] && !is_changed(0, i) && !is_changed(1, j) {
+            i += 1;
+            j += 1;
+        }
+        if i > i0 {
+            script.push(Change::NoChange(CommonSubsequence(i0, j0, i - i0)));
+        }
+        let (i0, j0) = (i, j);
+        while i < lens[0] && is_changed(0, i) {
+            i += 1;
+        }
+        while j < lens[1] && is_changed(1, j) {
+            j += 1;
+        }
+        match (i > i0, j > j0) {
+            (true, true) => script.push(Change::Replace(Range(i0, i), Range(j0, j))),
+            (true, false) => script.push(Change::Delete(Range(i0, i), j0)),
+            (false, true) => script.push(Change::Insert(i0, Range(j0, j))),
+            (false, false) => (),
+        }
+    }
+    Changes {
+        before,
+        after,
+        changes: script,
+    }
+}
+
+/// The lines (their equivalence classes and line numbers) that take part
+/// in the comparison.  Those that match no line in the other file are
+/// marked as changed and left out and so are those with many matches
+/// (when they're amongst such lines).
+fn discard_confusing_lines(
+    equivs: &[Vec<usize>; 2],
+    n_classes: usize,
+    changed: &mut [Vec<bool>; 2],
+) -> [(Vec<usize>, Vec<usize>); 2] {
+    let mut equiv_counts = [vec![0usize; n_classes], vec![0usize; n_classes]];
+    for (f, file_equivs) in equivs.iter().enumerate() {
+        for &class in file_equivs {
+            equiv_counts[f][class] += 1;
+        }
+    }
+
+    // 1 means discard and 2 discard provisionally.
+    let mut discards = [vec![0u8; equivs[0].len()], vec![0u8; equivs[1].len()]];
+    for f in 0..2 {
+        let counts = &equiv_counts[1 - f];
+        // Lines with more matches than (roughly) the square root of the
+        // number of lines are provisionally discardable.
+        let mut many = 5;
+        let mut tem = equivs[f].len() / 64;
+        loop {
+            tem >>= 2;
+            if tem == 0 {
+                break;
+            }
+            many *= 2;
+        }
+        for (k, &class) in equivs[f].iter().enumerate() {
+            match counts[class] {
+                0 => discards[f][k] = 1,
+                n if n > many => discards[f][k] = 2,
+                _ => (),
+            }
+        }
+    }
+
+    // Provisional discards only stand in the midst of a run of discards
+    // that starts and ends with definite ones.
+    for discards in discards.iter_mut() {
+        let end = discards.len();
+        let mut i = 0;
+        while i < end {
+            if discards[i] == 2 {
+                discards[i] = 0;
+            } else if discards[i] != 0 {
+                let mut provisional = 0;
+                let mut j = i;
+                while j < end && discards[j] != 0 {
+                    if discards[j] == 2 {
+                        provisional += 1;
+                    }
+                    j += 1;
+                }
+                while j > i && discards[j - 1] == 2 {
+                    j -= 1;
+                    discards[j] = 0;
+                    provisional -= 1;
+                }
+                let length = j - i;
+                if provisional * 4 > length {
+                    for discard in discards[i..j].iter_mut() {
+                        if *discard == 2 {
+                            *discard = 0;
+                        }
+                    }
+                } else {
+                    // Runs of provisionals of `minimum` (roughly the square
+                    // root of a quarter of the length) or more are cancelled.
+                    let mut minimum = 1;
+                    let mut tem = length >> 2;
+                    loop {
+                        tem >>= 2;
+                        if tem == 0 {
+                            break;
+                        }
+                        minimum <<= 1;
+                    }
+                    minimum += 1;
+                    let mut consec = 0;
+                    let mut k = 0;
+                    while k < length {
+                        if discards[i + k] != 2 {
+                            consec = 0;
+                        } else {
+                            consec += 1;
+                            if consec == minimum {
+                                k -= consec;
+                            } else if consec > minimum {
+                                discards[i + k] = 0;
+                            }
+                        }
+                        k += 1;
+                    }
+                    let last = i + length - 1;
+                    cancel_leading_provisionals(discards, i..=last);
+                    cancel_leading_provisionals(discards, (i..=last).rev());
+                    i = last;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    [0, 1].map(|f| {
+        let mut undiscarded = vec![];
+        let mut real_indices = vec![];
+        for (k, &class) in equivs[f].iter().enumerate() {
+            if discards[f][k] == 0 {
+                undiscarded.push(class);
+                real_indices.push(k);
+            } else {
+                changed[f][k + 1] = true;
+            }
+        }
+        (undiscarded, real_indices)
+    })
+}
+
+/// Cancel the provisional discards at the start of the run of discards
+/// at `indices` until three definite discards are found in a row or a
+/// definite one is found at least eight lines in.
+fn cancel_leading_provisionals(discards: &mut [u8], indices: impl Iterator<Item = usize>) {
+    let mut consec = 0;
+    for (k, index) in indices.enumerate() {
+        if k >= 8 && discards[index] == 1 {
+            break;
+        }
+        match discards[index] {
+            2 => {
+                consec = 0;
+                discards[index] = 0;
+            }
+            0 => consec = 0,
+            _ => consec += 1,
+        }
+        if consec == 3 {
+            break;
+        }
+    }
+}
+
+/// A midpoint of the shortest edit script for part of the comparison and
+/// whether the halves either side of it need to be minimal.
+struct Partition {
+    x_mid: isize,
+    y_mid: isize,
+    lo_minimal: bool,
+    hi_minimal: bool,
+}
+
+/// Myers' O(ND) comparison of the undiscarded lines (as in `diffseq.h`).
+struct Comparison<'a> {
+    xv: &'a [usize],
+    yv: &'a [usize],
+    x_indices: &'a [usize],
+    y_indices: &'a [usize],
+    changed: &'a mut [Vec<bool>; 2],
+    // Furthest reaching paths indexed by diagonal (offset by `diag_offset`).
+    fd: Vec<isize>,
+    bd: Vec<isize>,
+    diag_offset: isize,
+    too_expensive: isize,
+}
+
+impl<'a> Comparison<'a> {
+    fn new(
+        undiscarded: &'a [(Vec<usize>, Vec<usize>); 2],
+        changed: &'a mut [Vec<bool>; 2],
+    ) -> Self {
+        let (xv, x_indices) = (&undiscarded[0].0, &undiscarded[0].1);
+        let (yv, y_indices) = (&undiscarded[1].0, &undiscarded[1].1);
+        let diags = xv.len() + yv.len() + 3;
+        // Give up on finding a minimal midpoint after roughly the square
+        // root of the input size (but at least 4096) edits.
+        let mut too_expensive = 1;
+        let mut tem = diags;
+        while tem != 0 {
+            too_expensive <<= 1;
+            tem >>= 2;
+        }
+        Self {
+            xv,
+            yv,
+            x_indices,
+            y_indices,
+            changed,
+            fd: vec![0; diags],
+            bd: vec![0; diags],
+            diag_offset: yv.len() as isize + 1,
+            too_expensive: too_expensive.max(4096),
+        }
+    }
+
+    fn equal(&self, x: isize, y: isize) -> bool {
+        self.xv[x as usize] == self.yv[y as usize]
+    }
+
+    fn fd(&mut self, d: isize) -> &mut isize {
+        &mut self.fd[(d + self.diag_offset) as usize]
+    }
+
+    fn bd(&mut self, d: isize) -> &mut isize {
+        &mut self.bd[(d + self.diag_offset) as usize]
+    }
+
+    /// Find the midpoint of the shortest edit script for the given part of
+    /// the comparison (or a good enough one if that's too expensive and
+    /// `find_minimal` isn't set).
+    fn diag(
+        &mut self,
+        xoff: isize,
+        xlim: isize,
+        yoff: isize,
+        ylim: isize,
+        find_minimal: bool,
+    ) -> Partition {
+        let dmin = xoff - ylim;
+        let dmax = xlim - yoff;
+        let fmid = xoff - yoff;
+        let bmid = xlim - ylim;
+        let (mut fmin, mut fmax) = (fmid, fmid);
+        let (mut bmin, mut bmax) = (bmid, bmid);
+        let odd = (fmid - bmid) & 1 != 0;
+
+        *self.fd(fmid) = xoff;
+        *self.bd(bmid) = xlim;
+
+        let mut c = 1;
+        loop {
+            if fmin > dmin {
+                fmin -= 1;
+                *self.fd(fmin - 1) = -1;
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                *self.fd(fmax + 1) = -1;
+            } else {
+                fmax -= 1;
+            }
+            let mut d = fmax;
+            while d >= fmin {
+                let tlo = *self.fd(d - 1);
+                let thi = *self.fd(d + 1);
+                let x0 = if tlo < thi { thi } else { tlo + 1 };
+                let (mut x, mut y) = (x0, x0 - d);
+                while x < xlim && y < ylim && self.equal(x, y) {
+                    x += 1;
+                    y += 1;
+                }
+                *self.fd(d) = x;
+                if odd && bmin <= d && d <= bmax && *self.bd(d) <= x {
+                    return Partition {
+                        x_mid: x,
+                        y_mid: y,
+                        lo_minimal: true,
+                        hi_minimal: true,
+                    };
+                }
+                d -= 2;
+            }
+
+            if bmin > dmin {
+                bmin -= 1;
+                *self.bd(bmin - 1) = isize::MAX;
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                *self.bd(bmax + 1) = isize::MAX;
+            } else {
+                bmax -= 1;
+            }
+            let mut d = bmax;
+            while d >= bmin {
+                let tlo = *self.bd(d - 1);
+                let thi = *self.bd(d + 1);
+                let x0 = if tlo < thi { tlo } else { thi - 1 };
+                let (mut x, mut y) = (x0, x0 - d);
+                while xoff < x && yoff < y && self.equal(x - 1, y - 1) {
+                    x -= 1;
+                    y -= 1;
+                }
+                *self.bd(d) = x;
+                if !odd && fmin <= d && d <= fmax && x <= *self.fd(d) {
+                    return Partition {
+                        x_mid: x,
+                        y_mid: y,
+                        lo_minimal: true,
+                        hi_minimal: true,
+                    };
+                }
+                d -= 2;
+            }
+
+            if !find_minimal && c >= self.too_expensive {
+                // Settle for the better of the forward diagonal that's
+                // furthest along and the backward one that's furthest back.
+                let (mut fxybest, mut fxbest) = (-1, 0);
+                let mut d = fmax;
+                while d >= fmin {
+                    let mut x = (*self.fd(d)).min(xlim);
+                    let mut y = x - d;
+                    if ylim < y {
+                        x = ylim + d;
+                        y = ylim;
+                    }
+                    if fxybest < x + y {
+                        fxybest = x + y;
+                        fxbest = x;
+                    }
+                    d -= 2;
+                }
+                let (mut bxybest, mut bxbest) = (isize::MAX, 0);
+                let mut d = bmax;
+                while d >= bmin {
+                    let mut x = xoff.max(*self.bd(d));
+                    let mut y = x - d;
+                    if y < yoff {
+                        x = yoff + d;
+                        y = yoff;
+                    }
+                    if x + y < bxybest {
+                        bxybest = x + y;
+                        bxbest = x;
+                    }
+                    d -= 2;
+                }
+                return if (xlim + ylim) - bxybest < fxybest - (xoff + yoff) {
+                    Partition {
+                        x_mid: fxbest,
+                        y_mid: fxybest - fxbest,
+                        lo_minimal: true,
+                        hi_minimal: false,
+                    }
+                } else {
+                    Partition {
+                        x_mid: bxbest,
+                        y_mid: bxybest - bxbest,
+                        lo_minimal: false,
+                        hi_minimal: true,
+                    }
+                };
+            }
+            c += 1;
+        }
+    }
+
+    /// Mark the lines in the given part of the comparison that aren't in
+    /// its longest common subsequence as changed.
+    fn compare_seq(
+        &mut self,
+        xoff: usize,
+        xlim: usize,
+        yoff: usize,
+        ylim: usize,
+        find_minimal: bool,
+    ) {
+        let (mut xoff, mut xlim, mut yoff, mut ylim) =
+            (xoff as isize, xlim as isize, yoff as isize, ylim as isize);
+        while xoff < xlim && yoff < ylim && self.equal(xoff, yoff) {
+            xoff += 1;
+            yoff += 1;
+        }
+        while xoff < xlim && yoff < ylim && self.equal(xlim - 1, ylim - 1) {
+            xlim -= 1;
+            ylim -= 1;
+        }
+        if xoff == xlim {
+            for y in yoff..ylim {
+                self.changed[1][self.y_indices[y as usize] + 1] = true;
+            }
+        } else if yoff == ylim {
+            for x in xoff..xlim {
+                self.changed[0][self.x_indices[x as usize] + 1] = true;
+            }
+        } else {
+            let part = self.diag(xoff, xlim, yoff, ylim, find_minimal);
+            self.compare_seq(
+                xoff as usize,
+                part.x_mid as usize,
+                yoff as usize,
+                part.y_mid as usize,
+                part.lo_minimal,
+            );
+            self.compare_seq(
+                part.x_mid as usize,
+                xlim as usize,
+                part.y_mid as usize,
+                ylim as usize,
+                part.hi_minimal,
+            );
+        }
+    }
+}
+
+/// Slide each run of changed lines down as far as it will go (merging
+/// with later runs) and then back up to meet any corresponding run of
+/// changes in the other file.
+fn shift_boundaries(equivs: &[Vec<usize>; 2], changed: &mut [Vec<bool>; 2]) {
+    for (f, file_equivs) in equivs.iter().enumerate() {
+        let (this, other) = if f == 0 {
+            let (this, other) = changed.split_at_mut(1);
+            (&mut this[0], &other[0])
+        } else {
+            let (other, this) = changed.split_at_mut(1);
+            (&mut this[0], &other[0])
+        };
+        let equal = |a: usize, b: usize| file_equivs[a - 1] == file_equivs[b - 1];
+        let end = file_equivs.len() + 1;
+        let mut i = 1;
+        let mut j = 1;
+        loop {
+            while i < end && !this[i] {
+                while other[j] {
+                    j += 1;
+                }
+                j += 1;
+                i += 1;
+            }
+            if i == end {
+                break;
+            }
+            let mut start = i;
+            while this[i] {
+                i += 1;
+            }
+            while other[j] {
+                j += 1;
+            }
+            let mut corresponding;
+            loop {
+                let run_length = i - start;
+                while start > 1 && equal(start - 1, i - 1) {
+                    start -= 1;
+                    this[start] = true;
+                    i -= 1;
+                    this[i] = false;
+                    while this[start - 1] {
+                        start -= 1;
+                    }
+                    j -= 1;
+                    while other[j] {
+                        j -= 1;
+                    }
+                }
+                corresponding = if other[j - 1] { i } else { end };
+                while i != end && equal(start, i) {
+                    this[start] = false;
+                    start += 1;
+                    this[i] = true;
+                    i += 1;
+                    while this[i] {
+                        i += 1;
+                    }
+                    j += 1;
+                    while other[j] {
+                        j += 1;
+                        corresponding = i;
+                    }
+                }
+                if run_length == i - start {
+                    break;
+                }
+            }
+            while corresponding < i {
+                start -= 1;
+                this[start] = true;
+                i -= 1;
+                this[i] = false;
+                j -= 1;
+                while other[j] {
+                    j -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod gnu_changes_tests {
+    use super::*;
+
+    fn changed_lines(before: &str, after: &str, horizon: u8) -> Vec<Change> {
+        gnu_changes(Seq::from(before), Seq::from(after), horizon).changes
+    }
+
+    #[test]
+    fn changes_as_gnu_diff_finds_them() {
+        use Change::*;
+
+        // A minimal set of changes (as found by GNU `diff`).
+        assert_eq!(
+            changed_lines("e\nc\nb\nd\na\nc\n", "a\nc\nd\ne\nb\nc\n", 0),
+            vec![
+                Replace(Range(0, 1), Range(0, 1)),
+                NoChange(CommonSubsequence(1, 1, 1)),
+                Delete(Range(2, 3), 2),
+                NoChange(CommonSubsequence(3, 2, 1)),
+                Replace(Range(4, 5), Range(3, 5)),
+                NoChange(CommonSubsequence(5, 5, 1)),
+            ]
+        );
+        // Runs of changes slide down as far as they'll go.
+        assert_eq!(
+            changed_lines("a\nb\nb\nb\nc\n", "a\nb\nb\nc\n", 3),
+            vec![
+                NoChange(CommonSubsequence(0, 0, 3)),
+                Delete(Range(3, 4), 3),
+                NoChange(CommonSubsequence(4, 3, 1)),
+            ]
+        );
+        // Lines with no match in the other file are set aside.
+        let before = "x\ny\nz\n".repeat(3) + "p\nq\n";
+        let after = "x\ny\nz\n".repeat(3) + "q\n";
+        assert_eq!(
+            changed_lines(&before, &after, 0),
+            vec![
+                NoChange(CommonSubsequence(0, 0, 9)),
+                Delete(Range(9, 10), 9),
+                NoChange(CommonSubsequence(10, 9, 1)),
+            ]
+        );
+        assert!(changed_lines("a\nb\n", "a\nb\n", 3)
+            .iter()
+            .all(|change| matches!(change, NoChange(_))));
+    }
+}
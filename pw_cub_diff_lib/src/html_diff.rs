@@ -0,0 +1,500 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::borrow::Cow;
+use std::io;
+use std::path::Path;
+
+use pw_diff_lib::changes::{Change, Changes};
+use pw_diff_lib::diff::Diff;
+use pw_diff_lib::diff_set::DiffSet;
+use pw_diff_lib::range::{Len, Range};
+use pw_diff_lib::sequence::Seq;
+
+use crate::colour::replaced_lines_highlights;
+use crate::gnu_changes::gnu_changes;
+use crate::text_diff::{file_type_mismatch, has_changes, is_below_file, is_binary, read_lines};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 1em 2em; }
+h1 { font-size: 1.4em; }
+h2 { font-size: 1.1em; font-weight: normal; margin: 0; padding: 0.4em; background: #f0f0f0; }
+.path { font-family: monospace; font-weight: bold; }
+.added-count { color: #1a7f37; }
+.deleted-count { color: #cf222e; }
+.note { font-style: italic; color: #666; }
+section.file { border: 1px solid #ccc; margin: 1em 0; }
+section.file > p { padding: 0 0.4em; }
+table.diff { width: 100%; table-layout: fixed; border-collapse: collapse; font-family: monospace; font-size: 0.9em; }
+table.diff col.ln { width: 4em; }
+td { vertical-align: top; white-space: pre-wrap; overflow-wrap: anywhere; tab-size: 8; padding: 0 0.4em; }
+td.ln { text-align: right; color: #888; background: #fafafa; user-select: none; }
+td.deleted { background: #ffebe9; }
+td.added { background: #e6ffec; }
+td.empty { background: #f6f8fa; }
+td.deleted .hl { background: #ffb8b0; }
+td.added .hl { background: #9ee6aa; }
+details.unchanged > summary { cursor: pointer; color: #666; background: #f1f8ff; padding: 0.1em 0.4em; font-size: 0.9em; }
+";
+
+/// `text` with the characters that are special in HTML escaped.
+///
+/// Example:
+/// ```
+/// use pw_cub_diff_lib::html_diff::escaped;
+/// assert_eq!(escaped("a < b && c"), "a &lt; b &amp;&amp; c");
+/// assert_eq!(escaped("plain"), "plain");
+/// ```
+pub fn escaped(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// `line` (without its line terminator) as HTML with the (byte) ranges in
+/// `highlights` marked.
+fn html_line(line: &str, highlights: &[Range]) -> String {
+    let text = line.trim_end_matches(['\n', '\r']);
+    let mut html = String::new();
+    let mut start = 0;
+    for range in highlights {
+        html.push_str(&escaped(&text[start..range.start()]));
+        html.push_str("<span class=\"hl\">");
+        html.push_str(&escaped(&text[range.start()..range.end()]));
+        html.push_str("</span>");
+        start = range.end();
+    }
+    html.push_str(&escaped(&text[start..]));
+    if !line.ends_with('\n') {
+        html.push_str("<span class=\"note\"> (no newline at end of file)</span>");
+    }
+    html
+}
+
+/// One side of a row of a diff table: a line (and its number) or nothing.
+fn write_cells<W: io::Write>(
+    into: &mut W,
+    line: Option<(usize, &String)>,
+    class: &str,
+    highlights: &[Range],
+) -> io::Result<()> {
+    match line {
+        Some((index, line)) => write!(
+            into,
+            "<td class=\"ln\">{}</td><td class=\"{class}\">{}</td>",
+            index + 1,
+            html_line(line, highlights)
+        ),
+        None => write!(into, "<td class=\"ln\"></td><td class=\"empty\"></td>"),
+    }
+}
+
+/// The differences between two text files shown as a two column HTML
+/// table with line numbers.  Changes are shown in clumps with `context`
+/// lines of context and the unchanged lines between them are collapsed.
+#[derive(Debug)]
+pub struct HtmlFileDiff {
+    before: String,
+    after: String,
+    changes: Changes<String>,
+    context: u8,
+}
+
+impl HtmlFileDiff {
+    pub fn from_lines(
+        before: &str,
+        before_lines: Seq<String>,
+        after: &str,
+        after_lines: Seq<String>,
+        context: u8,
+    ) -> Self {
+        Self {
+            before: before.to_string(),
+            after: after.to_string(),
+            changes: gnu_changes(before_lines, after_lines, context),
+            context,
+        }
+    }
+
+    /// A file that does not exist is treated as being empty.
+    pub fn new(before_file_path: &Path, after_file_path: &Path, context: u8) -> io::Result<Self> {
+        Ok(Self::from_lines(
+            &before_file_path.to_string_lossy(),
+            read_lines(before_file_path)?,
+            &after_file_path.to_string_lossy(),
+            read_lines(after_file_path)?,
+            context,
+        ))
+    }
+
+    pub fn has_changes(&self) -> bool {
+        has_changes(&self.changes)
+    }
+
+    /// The number of lines deleted and added.
+    pub fn counts(&self) -> (usize, usize) {
+        self.changes
+            .changes
+            .iter()
+            .fold((0, 0), |(deleted, added), change| match change {
+                Change::NoChange(_) => (deleted, added),
+                Change::Delete(before_range, _) => (deleted + before_range.len(), added),
+                Change::Insert(_, after_range) => (deleted, added + after_range.len()),
+                Change::Replace(before_range, after_range) => {
+                    (deleted + before_range.len(), added + after_range.len())
+                }
+            })
+    }
+
+    fn write_table_start<W: io::Write>(into: &mut W) -> io::Result<()> {
+        writeln!(
+            into,
+            "<table class=\"diff\"><colgroup><col class=\"ln\"><col><col class=\"ln\"><col></colgroup>"
+        )
+    }
+
+    fn write_unchanged_rows<W: io::Write>(
+        &self,
+        into: &mut W,
+        before_range: Range,
+        after_start: usize,
+    ) -> io::Result<()> {
+        let (before, after) = (&self.changes.before, &self.changes.after);
+        for (i, line) in before.subsequence(before_range).enumerate() {
+            let (before_index, after_index) = (before_range.start() + i, after_start + i);
+            write!(into, "<tr>")?;
+            write_cells(into, Some((before_index, line)), "text", &[])?;
+            write_cells(into, Some((after_index, &after[after_index])), "text", &[])?;
+            writeln!(into, "</tr>")?;
+        }
+        Ok(())
+    }
+
+    /// The unchanged lines between clumps (if any) collapsed.
+    fn write_collapsed<W: io::Write>(
+        &self,
+        into: &mut W,
+        before_range: Range,
+        after_start: usize,
+    ) -> io::Result<()> {
+        let count = before_range.len();
+        writeln!(
+            into,
+            "<details class=\"unchanged\"><summary>{count} unchanged line{}</summary>",
+            if count == 1 { "" } else { "s" }
+        )?;
+        Self::write_table_start(into)?;
+        self.write_unchanged_rows(into, before_range, after_start)?;
+        writeln!(into, "</table></details>")
+    }
+
+    fn write_change_rows<W: io::Write>(&self, into: &mut W, change: &Change) -> io::Result<()> {
+        let (before, after) = (&self.changes.before, &self.changes.after);
+        let (before_range, after_range) = match change {
+            Change::NoChange(common_subsequence) => {
+                return self.write_unchanged_rows(
+                    into,
+                    common_subsequence.before_range(),
+                    common_subsequence.after_start(),
+                )
+            }
+            Change::Delete(before_range, after_start) => {
+                (*before_range, Range(*after_start, *after_start))
+            }
+            Change::Insert(before_start, after_range) => {
+                (Range(*before_start, *before_start), *after_range)
+            }
+            Change::Replace(before_range, after_range) => (*before_range, *after_range),
+        };
+        let before_lines: Vec<&String> = before.subsequence(before_range).collect();
+        let after_lines: Vec<&String> = after.subsequence(after_range).collect();
+        let highlights = replaced_lines_highlights(&before_lines, &after_lines);
+        for i in 0..before_lines.len().max(after_lines.len()) {
+            write!(into, "<tr>")?;
+            write_cells(
+                into,
+                before_lines
+                    .get(i)
+                    .map(|line| (before_range.start() + i, *line)),
+                "deleted",
+                highlights.0.get(i).map_or(&[][..], Vec::as_slice),
+            )?;
+            write_cells(
+                into,
+                after_lines
+                    .get(i)
+                    .map(|line| (after_range.start() + i, *line)),
+                "added",
+                highlights.1.get(i).map_or(&[][..], Vec::as_slice),
+            )?;
+            writeln!(into, "</tr>")?;
+        }
+        Ok(())
+    }
+
+    /// Write the table (or tables) showing the changes.
+    pub fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        // Where the lines not yet shown start.
+        let (mut before_next, mut after_next) = (0, 0);
+        let mut table_open = false;
+        for clump in self.changes.change_clumps(self.context) {
+            let (before_start, _) = clump.starts();
+            if before_start > before_next {
+                if table_open {
+                    writeln!(into, "</table>")?;
+                    table_open = false;
+                }
+                self.write_collapsed(into, Range(before_next, before_start), after_next)?;
+            }
+            if !table_open {
+                Self::write_table_start(into)?;
+                table_open = true;
+            }
+            for change in clump.iter() {
+                self.write_change_rows(into, change)?;
+            }
+            (before_next, after_next) = clump.ends();
+        }
+        if table_open {
+            writeln!(into, "</table>")?;
+        }
+        if self.changes.before.len() > before_next {
+            self.write_collapsed(
+                into,
+                Range(before_next, self.changes.before.len()),
+                after_next,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What is shown for one of the files in a report.
+#[derive(Debug)]
+enum Section {
+    Text(HtmlFileDiff),
+    Note {
+        before: String,
+        after: String,
+        note: String,
+    },
+}
+
+impl Section {
+    fn paths(&self) -> (&str, &str) {
+        match self {
+            Self::Text(file_diff) => (&file_diff.before, &file_diff.after),
+            Self::Note { before, after, .. } => (before, after),
+        }
+    }
+
+    fn write_title<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        let (before, after) = self.paths();
+        write!(
+            into,
+            "<span class=\"path\">{}</span> &rarr; <span class=\"path\">{}</span>",
+            escaped(before),
+            escaped(after)
+        )?;
+        if let Self::Text(file_diff) = self {
+            let (deleted, added) = file_diff.counts();
+            write!(
+                into,
+                " <span class=\"deleted-count\">&minus;{deleted}</span> <span class=\"added-count\">+{added}</span>"
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The section for `diff` (made from the files at the given paths)
+    /// if it has anything to show.
+    fn for_diff(
+        diff: &Diff,
+        before_file_path: &Path,
+        after_file_path: &Path,
+        context: u8,
+    ) -> io::Result<Option<Self>> {
+        let note = |note: String| Self::Note {
+            before: before_file_path.to_string_lossy().to_string(),
+            after: after_file_path.to_string_lossy().to_string(),
+            note,
+        };
+        let section = match diff {
+            Diff::TextChange(_) | Diff::TextAdd(_) | Diff::TextRemove(_)
+                if !is_binary(before_file_path)? && !is_binary(after_file_path)? =>
+            {
+                let file_diff = HtmlFileDiff::new(before_file_path, after_file_path, context)?;
+                if !file_diff.has_changes() {
+                    return Ok(None);
+                }
+                Self::Text(file_diff)
+            }
+            Diff::TextChange(_)
+            | Diff::TextAdd(_)
+            | Diff::TextRemove(_)
+            | Diff::ByteChange(_)
+            | Diff::ByteAdd(_)
+            | Diff::ByteRemove(_) => note("Binary files differ".to_string()),
+            Diff::SymlinkAdd(_) | Diff::SymlinkRemove(_) | Diff::SymlinkChange(_) => {
+                note("Symbolic links differ".to_string())
+            }
+            Diff::ModeChange(mode_change) => note(format!(
+                "Mode changed from {:o} to {:o}",
+                mode_change.modes().before(false),
+                mode_change.modes().after(false)
+            )),
+            Diff::TextRename(_) | Diff::TextCopy(_) | Diff::ByteRename(_) | Diff::ByteCopy(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "{:?}: renames and copies can't be shown in HTML reports",
+                        diff.paths()
+                    ),
+                ))
+            }
+        };
+        Ok(Some(section))
+    }
+}
+
+/// Write a self contained HTML page titled `title` showing `sections`.
+fn write_page<W: io::Write>(title: &str, sections: &[Section], into: &mut W) -> io::Result<()> {
+    let title = escaped(title);
+    writeln!(
+        into,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>"
+    )?;
+    if sections.is_empty() {
+        writeln!(into, "<p class=\"note\">No differences.</p>")?;
+    } else {
+        writeln!(into, "<ul class=\"files\">")?;
+        for (i, section) in sections.iter().enumerate() {
+            write!(into, "<li><a href=\"#file-{}\">", i + 1)?;
+            section.write_title(into)?;
+            writeln!(into, "</a></li>")?;
+        }
+        writeln!(into, "</ul>")?;
+    }
+    for (i, section) in sections.iter().enumerate() {
+        write!(into, "<section class=\"file\" id=\"file-{}\">\n<h2>", i + 1)?;
+        section.write_title(into)?;
+        writeln!(into, "</h2>")?;
+        match section {
+            Section::Text(file_diff) => file_diff.write_into(into)?,
+            Section::Note { note, .. } => {
+                writeln!(into, "<p class=\"note\">{}</p>", escaped(note))?
+            }
+        }
+        writeln!(into, "</section>")?;
+    }
+    writeln!(into, "</body>\n</html>")
+}
+
+/// Write `diff` (made from the files at the given paths) as a self
+/// contained HTML page.
+pub fn write_html_diff<W: io::Write>(
+    diff: &Diff,
+    before_file_path: &Path,
+    after_file_path: &Path,
+    context: u8,
+    into: &mut W,
+) -> io::Result<()> {
+    let sections: Vec<Section> =
+        Section::for_diff(diff, before_file_path, after_file_path, context)?
+            .into_iter()
+            .collect();
+    let title = format!(
+        "{} vs {}",
+        before_file_path.display(),
+        after_file_path.display()
+    );
+    write_page(&title, &sections, into)
+}
+
+/// Write `diff_set` (made by comparing `before_dir` with `after_dir`) as
+/// a self contained HTML page with a section for each file that differs.
+pub fn write_html_diff_set<W: io::Write>(
+    diff_set: &DiffSet,
+    before_dir: &Path,
+    after_dir: &Path,
+    context: u8,
+    into: &mut W,
+) -> io::Result<()> {
+    let mut sections = vec![];
+    for diff in diff_set.diffs() {
+        let rel_path = diff.paths()[0].strip_prefix(before_dir).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?}: not in {before_dir:?}", diff.paths()[0]),
+            )
+        })?;
+        let before_file_path = before_dir.join(rel_path);
+        let after_file_path = after_dir.join(rel_path);
+        // What's in a directory that replaced a file (or vice versa) isn't
+        // shown.
+        if let Some((before_kind, _)) = file_type_mismatch(&before_file_path, &after_file_path) {
+            let note = if before_kind == "a directory" {
+                "Directory replaced by a regular file"
+            } else {
+                "Regular file replaced by a directory"
+            };
+            sections.push(Section::Note {
+                before: before_file_path.to_string_lossy().to_string(),
+                after: after_file_path.to_string_lossy().to_string(),
+                note: note.to_string(),
+            });
+        } else if !is_below_file(before_dir, rel_path) && !is_below_file(after_dir, rel_path) {
+            sections.extend(Section::for_diff(
+                diff,
+                &before_file_path,
+                &after_file_path,
+                context,
+            )?);
+        }
+    }
+    let title = format!("{} vs {}", before_dir.display(), after_dir.display());
+    write_page(&title, &sections, into)
+}
+
+#[cfg(test)]
+mod html_diff_tests {
+    use super::*;
+
+    #[test]
+    fn html_file_diff_collapses_and_highlights() {
+        let before = "1\n2\n3\n4\n5\nlet x = 1;\n7\n8\n9\n10\n";
+        let after = "1\n2\n3\n4\n5\nlet y = 1;\n<new>\n7\n8\n9\n10";
+        let file_diff = HtmlFileDiff::from_lines("a", Seq::from(before), "b", Seq::from(after), 1);
+        assert!(file_diff.has_changes());
+        assert_eq!(file_diff.counts(), (2, 3));
+        let mut html = vec![];
+        file_diff.write_into(&mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<summary>4 unchanged lines</summary>"));
+        assert!(html.contains("<summary>1 unchanged line</summary>"));
+        assert!(html.contains(
+            "<td class=\"ln\">6</td><td class=\"deleted\">let <span class=\"hl\">x</span> = 1;</td>"
+        ));
+        assert!(html.contains(
+            "<td class=\"ln\"></td><td class=\"empty\"></td><td class=\"ln\">7</td><td class=\"added\">&lt;new&gt;</td>"
+        ));
+        assert!(html.contains("10<span class=\"note\"> (no newline at end of file)</span>"));
+
+        let mut page = vec![];
+        write_page("a vs b", &[Section::Text(file_diff)], &mut page).unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<a href=\"#file-1\">"));
+        assert!(!page.contains("http"));
+    }
+}
@@ -1,9 +1,20 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
+pub mod colour;
+pub mod context_diff;
+pub mod ed_diff;
+pub mod function_line;
+pub mod git_binary;
+pub mod git_diff;
+pub mod gnu_changes;
+pub mod html_diff;
+pub mod normal_diff;
+pub mod side_by_side;
 pub mod text_diff;
 pub mod unified_diff;
 
 pub const TIMESTAMP_RE_STR: &str = r"\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(\.\d{9})? [-+]{1}\d{4}";
 pub const ALT_TIMESTAMP_RE_STR: &str =
-    r"[A-Z][a-z]{2} [A-Z][a-z]{2} \d{2} \d{2}:\d{2}:\d{2} \d{4} [-+]{1}\d{4}";
+    r"[A-Z][a-z]{2} [A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2} \d{4}(?: [-+]{1}\d{4})?";
+pub const DEV_NULL: &str = "/dev/null";
 pub const PATH_RE_STR: &str = r###""([^"]+)"|(\S+)"###;
@@ -0,0 +1,503 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::{Captures, Regex};
+
+use pw_diff_lib::apply_text::{ApplyClumpFuzzy, ApplyClumpsFuzzy, TextClumpBasics};
+use pw_diff_lib::changes::{Change, ChangeBasics, Changes};
+use pw_diff_lib::range::Range;
+use pw_diff_lib::sequence::Seq;
+use pw_diff_lib::snippet::Snippet;
+use pw_diff_lib::text_diff::TextChangeClump;
+
+use crate::gnu_changes::gnu_changes;
+use crate::text_diff::{
+    clump_lengths, clump_lines, has_changes, read_lines, unquoted_path, DiffParseError,
+    DiffParseResult, GnuFileDiff, GnuPatch, GnuTextDiff, PathAndTimestamp, StartAndLength,
+};
+use crate::unified_diff::{StartsAndLengths, NO_NEWLINE_AT_END};
+use crate::PATH_RE_STR;
+
+lazy_static::lazy_static! {
+    pub static ref DIFF_COMMAND_REGEX: Regex =
+        Regex::new(&format!(r"^diff(?:\s+-\S+)*\s+({PATH_RE_STR})\s+({PATH_RE_STR})\s*(\n)?$")).unwrap();
+
+    pub static ref NORMAL_COMMAND_REGEX: Regex =
+        Regex::new(r"^(\d+)(,(\d+))?([acd])(\d+)(,(\d+))?\s*(\n)?$").unwrap();
+}
+
+/// The paths of the files compared by a `diff` command line (as written
+/// by GNU `diff -r` before the diff of each pair of files).
+pub fn diff_command_paths(line: &str) -> Option<(PathAndTimestamp, PathAndTimestamp)> {
+    let captures = DIFF_COMMAND_REGEX.captures(line)?;
+    let path = |quoted: usize| PathAndTimestamp {
+        file_path: match captures.get(quoted) {
+            Some(path) => unquoted_path(path.as_str()),
+            None => captures.get(quoted + 1).unwrap().as_str().to_string(),
+        },
+        time_stamp: None,
+    };
+    Some((path(2), path(5)))
+}
+
+/// A range in a normal diff command: `first,last` or just `last` (if
+/// there is one line or, when there are none, the line before).
+fn normal_range(range: Range) -> String {
+    let (first, last) = (range.start() + 1, range.end());
+    if last <= first {
+        format!("{last}")
+    } else {
+        format!("{first},{last}")
+    }
+}
+
+fn start_and_length(
+    captures: &Captures,
+    index: usize,
+    empty: bool,
+    line_number: usize,
+) -> DiffParseResult<StartAndLength> {
+    let number = |index: usize| {
+        captures
+            .get(index)
+            .map(|m| usize::from_str(m.as_str()))
+            .transpose()
+            .map_err(|e| DiffParseError::ParseNumberError(e, line_number))
+    };
+    let first = number(index)?.ok_or(DiffParseError::SyntaxError(line_number, "a line number"))?;
+    match (number(index + 2)?, empty) {
+        (None, true) => Ok(StartAndLength {
+            start: first,
+            length: 0,
+        }),
+        (None, false) => Ok(StartAndLength {
+            start: first,
+            length: 1,
+        }),
+        (Some(last), false) if last >= first => Ok(StartAndLength {
+            start: first,
+            length: last - first + 1,
+        }),
+        _ => Err(DiffParseError::SyntaxError(
+            line_number,
+            "a normal diff command with a valid range",
+        )),
+    }
+}
+
+/// Read `length` lines starting with `marker` (and any following
+/// `\ No newline at end of file` line) from `lines` at `start_index`.
+fn marked_lines_from_at(
+    lines: &Seq<String>,
+    start_index: usize,
+    length: usize,
+    marker: &str,
+) -> DiffParseResult<(Vec<String>, usize)> {
+    let mut marked_lines: Vec<String> = vec![];
+    let mut index = start_index;
+    while marked_lines.len() < length
+        || (!marked_lines.is_empty() && lines.get(index).is_some_and(|l| l.starts_with('\\')))
+    {
+        let line = lines
+            .get(index)
+            .ok_or(DiffParseError::UnexpectedEndOfInput)?;
+        if line.starts_with('\\') {
+            if let Some(text) = marked_lines.last_mut() {
+                if text.ends_with('\n') {
+                    text.pop();
+                }
+            }
+        } else if let Some(text) = line.strip_prefix(marker) {
+            marked_lines.push(text.to_string());
+        } else {
+            return Err(DiffParseError::UnexpectedEndClump(index));
+        }
+        index += 1;
+    }
+    Ok((marked_lines, index - start_index))
+}
+
+/// A change in a normal diff: a `3c3` style command and its lines.
+pub struct NormalDiffClump {
+    pub starts_and_lengths: StartsAndLengths,
+    pub before_lines: Box<[String]>,
+    pub after_lines: Box<[String]>,
+    pub lines_consumed: usize,
+}
+
+impl NormalDiffClump {
+    pub fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        let Some(captures) = lines
+            .get(start_index)
+            .and_then(|line| NORMAL_COMMAND_REGEX.captures(line))
+        else {
+            return Ok(None);
+        };
+        let command = &captures[4];
+        let before = start_and_length(&captures, 1, command == "a", start_index)?;
+        let after = start_and_length(&captures, 5, command == "d", start_index)?;
+        let mut index = start_index + 1;
+        let (before_lines, consumed) = marked_lines_from_at(lines, index, before.length, "< ")?;
+        index += consumed;
+        if command == "c" {
+            match lines.get(index) {
+                Some(line) if line.trim_end() == "---" => index += 1,
+                _ => return Err(DiffParseError::SyntaxError(index, "a `---` line")),
+            }
+        }
+        let (after_lines, consumed) = marked_lines_from_at(lines, index, after.length, "> ")?;
+        index += consumed;
+        Ok(Some(Self {
+            starts_and_lengths: StartsAndLengths { before, after },
+            before_lines: before_lines.into_boxed_slice(),
+            after_lines: after_lines.into_boxed_slice(),
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    fn start_and_length(&self, reverse: bool) -> &StartAndLength {
+        if reverse {
+            &self.starts_and_lengths.after
+        } else {
+            &self.starts_and_lengths.before
+        }
+    }
+}
+
+impl ChangeBasics for NormalDiffClump {
+    fn before_start(&self, reverse: bool) -> usize {
+        self.start_and_length(reverse).start_index()
+    }
+
+    fn before_end(&self, reverse: bool) -> usize {
+        self.before_start(reverse) + self.before_length(reverse)
+    }
+
+    fn before_length(&self, reverse: bool) -> usize {
+        self.start_and_length(reverse).length
+    }
+}
+
+impl TextClumpBasics for NormalDiffClump {
+    fn context_lengths(&self) -> (u8, u8) {
+        (0, 0)
+    }
+
+    fn before_lines(&self, range: Option<Range>, reverse: bool) -> impl Iterator<Item = &String> {
+        let lines = if reverse {
+            &self.after_lines
+        } else {
+            &self.before_lines
+        };
+        match range {
+            Some(range) => lines[range.start()..range.end()].iter(),
+            None => lines.iter(),
+        }
+    }
+}
+
+impl ApplyClumpFuzzy for NormalDiffClump {}
+
+impl From<&NormalDiffClump> for TextChangeClump {
+    fn from(clump: &NormalDiffClump) -> Self {
+        TextChangeClump::new(
+            Snippet {
+                start: clump.before_start(false),
+                items: clump.before_lines.clone(),
+            },
+            Snippet {
+                start: clump.after_start(false),
+                items: clump.after_lines.clone(),
+            },
+            (0, 0),
+        )
+    }
+}
+
+/// The normal diff of a single file together with any other lines (e.g. a
+/// `diff` command line naming the files) that preceded it in the patch.
+pub struct NormalFileDiff {
+    pub preamble: Vec<String>,
+    pub clumps: Vec<NormalDiffClump>,
+    pub lines_consumed: usize,
+}
+
+impl GnuFileDiff for NormalFileDiff {
+    fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        let mut index = start_index;
+        let mut clumps = vec![];
+        while let Some(clump) = NormalDiffClump::get_from_at(lines, index)? {
+            index += clump.lines_consumed;
+            clumps.push(clump);
+        }
+        if clumps.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            preamble: vec![],
+            clumps,
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    fn lines_consumed(&self) -> usize {
+        self.lines_consumed
+    }
+
+    fn preamble(&self) -> &[String] {
+        &self.preamble
+    }
+
+    fn set_preamble(&mut self, preamble: Vec<String>) {
+        self.preamble = preamble;
+    }
+
+    fn clump_lengths(&self) -> impl Iterator<Item = (usize, usize)> {
+        clump_lengths(self)
+    }
+
+    fn lines(&self, reverse: bool) -> Vec<String> {
+        clump_lines(self, reverse)
+    }
+
+    fn text_change_clumps(
+        &self,
+        _path: &Path,
+        _strip: usize,
+        _named: bool,
+    ) -> io::Result<Vec<TextChangeClump>> {
+        Ok(self.clumps.iter().map(TextChangeClump::from).collect())
+    }
+}
+
+impl ApplyClumpsFuzzy<NormalDiffClump> for NormalFileDiff {
+    fn clumps<'s>(&'s self) -> impl Iterator<Item = &'s NormalDiffClump>
+    where
+        NormalDiffClump: 's,
+    {
+        self.clumps.iter()
+    }
+}
+
+/// A patch consisting of the normal diffs of one or more files possibly
+/// with other text before, between and after them.
+pub struct NormalPatch {
+    pub file_diffs: Vec<NormalFileDiff>,
+}
+
+impl GnuPatch for NormalPatch {
+    type FileDiff = NormalFileDiff;
+
+    fn from_file_diffs(file_diffs: Vec<NormalFileDiff>) -> Self {
+        Self { file_diffs }
+    }
+
+    fn file_diffs(&self) -> &[NormalFileDiff] {
+        &self.file_diffs
+    }
+}
+
+fn write_marked_lines<'a, W: io::Write>(
+    into: &mut W,
+    marker: &str,
+    lines: impl Iterator<Item = &'a String>,
+) -> io::Result<()> {
+    for line in lines {
+        write!(into, "{marker}{line}")?;
+        if !line.ends_with('\n') {
+            write!(into, "\n{NO_NEWLINE_AT_END}")?;
+        }
+    }
+    Ok(())
+}
+
+/// The differences between two files in GNU `diff`'s normal format.
+pub struct NormalDiff {
+    changes: Changes<String>,
+}
+
+impl NormalDiff {
+    pub fn from_lines(before_lines: Seq<String>, after_lines: Seq<String>) -> Self {
+        Self {
+            changes: gnu_changes(before_lines, after_lines, 0),
+        }
+    }
+}
+
+impl GnuTextDiff for NormalDiff {
+    const FORMAT_NAME: &'static str = "normal";
+    const DIFF_OPTIONS: &'static str = "-Nr";
+
+    /// Normal diffs have no context so `context` is ignored.
+    fn new(before_file_path: &Path, after_file_path: &Path, _context: u8) -> io::Result<Self> {
+        Ok(Self::from_lines(
+            read_lines(before_file_path)?,
+            read_lines(after_file_path)?,
+        ))
+    }
+
+    fn has_changes(&self) -> bool {
+        has_changes(&self.changes)
+    }
+
+    fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        let (before, after) = (&self.changes.before, &self.changes.after);
+        for change in self.changes.changes.iter() {
+            use Change::*;
+            match change {
+                NoChange(_) => (),
+                Delete(before_range, after_start) => {
+                    writeln!(into, "{}d{after_start}", normal_range(*before_range))?;
+                    write_marked_lines(into, "< ", before.subsequence(*before_range))?;
+                }
+                Insert(before_start, after_range) => {
+                    writeln!(into, "{before_start}a{}", normal_range(*after_range))?;
+                    write_marked_lines(into, "> ", after.subsequence(*after_range))?;
+                }
+                Replace(before_range, after_range) => {
+                    writeln!(
+                        into,
+                        "{}c{}",
+                        normal_range(*before_range),
+                        normal_range(*after_range)
+                    )?;
+                    write_marked_lines(into, "< ", before.subsequence(*before_range))?;
+                    writeln!(into, "---")?;
+                    write_marked_lines(into, "> ", after.subsequence(*after_range))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod normal_diff_tests {
+    use super::*;
+
+    use pw_diff_lib::diff_set::DiffSet;
+
+    static BEFORE: &str = "a\nb\nc\n.\nd\ne\n";
+    static AFTER: &str = "a\nB\nc\n.\n.\nd\nX";
+
+    // As made by GNU `diff`.
+    static NORMAL_DIFF: &str = "2c2
+< b
+---
+> B
+4a5
+> .
+6c7
+< e
+---
+> X
+\\ No newline at end of file
+";
+
+    #[test]
+    fn normal_diff_matches_gnu_diff() {
+        let normal_diff = NormalDiff::from_lines(Seq::from(BEFORE), Seq::from(AFTER));
+        let mut text = vec![];
+        normal_diff.write_into(&mut text).unwrap();
+        assert_eq!(String::from_utf8(text).unwrap(), NORMAL_DIFF);
+        assert!(!NormalDiff::from_lines(Seq::from(BEFORE), Seq::from(BEFORE)).has_changes());
+    }
+
+    #[test]
+    fn normal_patch_parse_and_apply() {
+        let patch_text =
+            format!("diff -Nr a/f \"b/f g\"\n{NORMAL_DIFF}diff -Nr a/h b/h\n1,2d0\n< x\n< y\n");
+        let patch = NormalPatch::from_lines(&Seq::from(patch_text)).unwrap();
+        assert_eq!(patch.file_diffs.len(), 2);
+        let (before, after) = diff_command_paths(&patch.file_diffs[0].preamble[0]).unwrap();
+        assert_eq!(before.file_path, "a/f");
+        assert_eq!(after.file_path, "b/f g");
+        let clumps = &patch.file_diffs[1].clumps;
+        assert_eq!(clumps.len(), 1);
+        assert_eq!(
+            (
+                clumps[0].before_start(false),
+                clumps[0].before_length(false)
+            ),
+            (0, 2)
+        );
+        assert_eq!(
+            (clumps[0].after_start(false), clumps[0].after_length(false)),
+            (0, 0)
+        );
+
+        let file_diff = &patch.file_diffs[0];
+        assert_eq!(file_diff.clumps.len(), 3);
+        let mut patched = vec![];
+        let stats = file_diff
+            .apply_into(&Seq::from(BEFORE), &mut patched, false)
+            .unwrap();
+        assert_eq!(stats.failed, 0);
+        assert_eq!(String::from_utf8(patched).unwrap(), AFTER);
+        let mut unpatched = vec![];
+        file_diff
+            .apply_into(&Seq::from(AFTER), &mut unpatched, true)
+            .unwrap();
+        assert_eq!(String::from_utf8(unpatched).unwrap(), BEFORE);
+
+        assert!(file_diff.to_diff(0, None).is_ok());
+        assert!(NormalPatch::from_lines(&Seq::from(NORMAL_DIFF))
+            .unwrap()
+            .file_diffs[0]
+            .to_diff(0, None)
+            .is_err());
+    }
+
+    #[test]
+    fn file_replaced_by_directory_as_gnu_diff() {
+        use pw_diff_lib::dir_diff::PathFilter;
+
+        use crate::function_line::FunctionLines;
+        use crate::text_diff::write_diff_set;
+
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let before_dir = temp_dir.path().join("a");
+        let after_dir = temp_dir.path().join("b");
+        for (path, content) in [
+            (before_dir.join("x"), "file\n"),
+            (before_dir.join("y/z"), "nested\n"),
+            (before_dir.join("z"), "z\n"),
+            (after_dir.join("x/w"), "nested\n"),
+            (after_dir.join("y"), "file\n"),
+            (after_dir.join("z"), "Z\n"),
+        ] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let diff_set =
+            DiffSet::from_dirs(&before_dir, &after_dir, 0, &PathFilter::default(), None).unwrap();
+        let mut text = vec![];
+        write_diff_set::<NormalDiff, _>(
+            &diff_set,
+            &before_dir,
+            &after_dir,
+            0,
+            &FunctionLines::default(),
+            false,
+            &mut text,
+        )
+        .unwrap();
+        let (a, b) = (before_dir.display(), after_dir.display());
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            format!(
+                "File {a}/x is a regular file while file {b}/x is a directory
+File {a}/y is a directory while file {b}/y is a regular file
+diff -Nr {a}/z {b}/z
+1c1
+< z
+---
+> Z
+"
+            )
+        );
+    }
+}
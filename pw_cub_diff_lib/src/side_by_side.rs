@@ -0,0 +1,456 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::io;
+use std::path::Path;
+
+use pw_diff_lib::changes::{Change, Changes};
+use pw_diff_lib::range::Range;
+use pw_diff_lib::sequence::Seq;
+
+use crate::colour::{replaced_lines_highlights, Highlighter, Palette};
+use crate::gnu_changes::gnu_changes;
+use crate::text_diff::{has_changes, read_lines, GnuTextDiff};
+
+/// The narrowest gap between the two columns (as GNU `diff -y`).
+const GUTTER_WIDTH_MINIMUM: usize = 3;
+
+/// How a side by side diff is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SideBySideOptions {
+    /// The number of columns in the output (at most).
+    pub width: usize,
+    /// Use spaces rather than tabs for tabs in the lines and for padding.
+    pub expand_tabs: bool,
+    pub tab_size: usize,
+    /// Show only the lines that differ.
+    pub suppress_common_lines: bool,
+}
+
+impl Default for SideBySideOptions {
+    /// As GNU `diff -y`.
+    fn default() -> Self {
+        Self {
+            width: 130,
+            expand_tabs: false,
+            tab_size: 8,
+            suppress_common_lines: false,
+        }
+    }
+}
+
+impl SideBySideOptions {
+    fn tab_size(&self) -> usize {
+        self.tab_size.max(1)
+    }
+
+    /// The width of each column and the offset of the second column (as
+    /// chosen by GNU `diff -y`).
+    fn columns(&self) -> (usize, usize) {
+        let unit = if self.expand_tabs { 1 } else { self.tab_size() };
+        let offset = (self.width + unit + GUTTER_WIDTH_MINIMUM) / (2 * unit) * unit;
+        let half_width = offset
+            .saturating_sub(GUTTER_WIDTH_MINIMUM)
+            .min(self.width.saturating_sub(offset));
+        let column2_offset = if half_width < offset {
+            offset
+        } else {
+            self.width
+        };
+        (half_width, column2_offset)
+    }
+
+    /// Pad from column `from` to column `to` (with tabs where possible
+    /// unless they're being expanded) and return `to`.
+    fn pad<W: io::Write>(&self, into: &mut W, mut from: usize, to: usize) -> io::Result<usize> {
+        if !self.expand_tabs {
+            let tab_size = self.tab_size();
+            let mut tab = from + tab_size - from % tab_size;
+            while tab <= to {
+                into.write_all(b"\t")?;
+                from = tab;
+                tab += tab_size;
+            }
+        }
+        while from < to {
+            into.write_all(b" ")?;
+            from += 1;
+        }
+        Ok(to)
+    }
+
+    /// Write as much of `line` as fits in `out_bound` columns (in a column
+    /// starting at `indent`), painted by `highlighter` (if any), and
+    /// return the column reached.
+    fn write_half_line<W: io::Write>(
+        &self,
+        into: &mut W,
+        line: &str,
+        indent: usize,
+        out_bound: usize,
+        mut highlighter: Option<Highlighter>,
+    ) -> io::Result<usize> {
+        let tab_size = self.tab_size();
+        let (mut in_position, mut out_position) = (0, 0);
+        let mut buffer = [0; 4];
+        if let Some(highlighter) = highlighter.as_mut() {
+            into.write_all(highlighter.start().as_bytes())?;
+        }
+        for (index, c) in line.char_indices() {
+            if let Some(escape) = highlighter.as_mut().and_then(|h| h.at(index)) {
+                into.write_all(escape.as_bytes())?;
+            }
+            match c {
+                '\n' => break,
+                '\t' => {
+                    let spaces = tab_size - in_position % tab_size;
+                    if in_position == out_position {
+                        let tab_stop = out_position + spaces;
+                        if self.expand_tabs {
+                            while out_position < tab_stop.min(out_bound) {
+                                into.write_all(b" ")?;
+                                out_position += 1;
+                            }
+                        } else if tab_stop < out_bound {
+                            into.write_all(b"\t")?;
+                            out_position = tab_stop;
+                        }
+                    }
+                    in_position += spaces;
+                }
+                '\r' => {
+                    into.write_all(b"\r")?;
+                    self.pad(into, 0, indent)?;
+                    in_position = 0;
+                    out_position = 0;
+                }
+                '\u{8}' => {
+                    if in_position != 0 {
+                        in_position -= 1;
+                        if in_position < out_bound {
+                            if out_position <= in_position {
+                                // Make up for a tab suppressed beyond the bound.
+                                while out_position < in_position {
+                                    into.write_all(b" ")?;
+                                    out_position += 1;
+                                }
+                            } else {
+                                into.write_all(b"\x08")?;
+                                out_position = in_position;
+                            }
+                        }
+                    }
+                }
+                c => {
+                    if in_position < out_bound {
+                        into.write_all(c.encode_utf8(&mut buffer).as_bytes())?;
+                        out_position = in_position + 1;
+                    }
+                    in_position += 1;
+                }
+            }
+        }
+        if let Some(highlighter) = highlighter.as_mut() {
+            into.write_all(highlighter.end().as_bytes())?;
+        }
+        Ok(out_position)
+    }
+
+    /// Write a line of output with `left` and `right` (either of which
+    /// may be missing) separated by `gutter` (' ', '|', '<' or '>').  A
+    /// '|' becomes '/' or '\' if only the left or right line (respectively)
+    /// ends with a newline.  Changed lines are painted with `palette` (if
+    /// any) with the given (byte) ranges of the left and right lines
+    /// highlighted.
+    fn write_line<W: io::Write>(
+        &self,
+        into: &mut W,
+        left: Option<&str>,
+        gutter: char,
+        right: Option<&str>,
+        painting: Option<(&Palette, &[Range], &[Range])>,
+    ) -> io::Result<()> {
+        let highlighter = |deleted: bool| {
+            painting.filter(|_| gutter != ' ').map(
+                |(palette, left_highlights, right_highlights)| {
+                    let highlights = if deleted {
+                        left_highlights
+                    } else {
+                        right_highlights
+                    };
+                    palette.highlighter(deleted, highlights)
+                },
+            )
+        };
+        let (half_width, column2_offset) = self.columns();
+        let mut column = 0;
+        let mut put_newline = false;
+        if let Some(left) = left {
+            put_newline = left.ends_with('\n');
+            column = self.write_half_line(into, left, 0, half_width, highlighter(true))?;
+        }
+        if gutter != ' ' {
+            let gutter_column = (half_width + column2_offset).saturating_sub(1) / 2;
+            column = self.pad(into, column, gutter_column)? + 1;
+            let gutter = match gutter {
+                '|' if put_newline != right.is_some_and(|right| right.ends_with('\n')) => {
+                    if put_newline {
+                        '/'
+                    } else {
+                        '\\'
+                    }
+                }
+                gutter => gutter,
+            };
+            write!(into, "{gutter}")?;
+        }
+        if let Some(right) = right {
+            put_newline |= right.ends_with('\n');
+            // Empty lines don't leave trailing white space.
+            if !right.starts_with('\n') {
+                column = self.pad(into, column, column2_offset)?;
+                self.write_half_line(into, right, column, half_width, highlighter(false))?;
+            }
+        }
+        if put_newline {
+            writeln!(into)?;
+        }
+        Ok(())
+    }
+}
+
+/// The differences between two files shown in two columns (as GNU
+/// `diff -y`): lines that are the same are separated by spaces and lines
+/// that differ by '|', '<' (only in the first file) or '>' (only in the
+/// second file).
+#[derive(Debug)]
+pub struct SideBySideDiff {
+    changes: Changes<String>,
+    options: SideBySideOptions,
+    palette: Option<Palette>,
+}
+
+impl SideBySideDiff {
+    pub fn from_lines(
+        before_lines: Seq<String>,
+        after_lines: Seq<String>,
+        options: SideBySideOptions,
+    ) -> Self {
+        Self {
+            changes: gnu_changes(before_lines, after_lines, 0),
+            options,
+            palette: None,
+        }
+    }
+
+    pub fn set_options(&mut self, options: SideBySideOptions) {
+        self.options = options;
+    }
+
+    fn write_change<W: io::Write>(&self, into: &mut W, change: &Change) -> io::Result<()> {
+        let (before, after) = (&self.changes.before, &self.changes.after);
+        match change {
+            Change::NoChange(common_subsequence) => {
+                let before_lines = before.subsequence(common_subsequence.before_range());
+                let after_lines = after.subsequence(common_subsequence.after_range());
+                for (left, right) in before_lines.zip(after_lines) {
+                    self.options
+                        .write_line(into, Some(left), ' ', Some(right), None)?;
+                }
+            }
+            Change::Delete(before_range, _) => {
+                for left in before.subsequence(*before_range) {
+                    let painting = self
+                        .palette
+                        .as_ref()
+                        .map(|palette| (palette, &[][..], &[][..]));
+                    self.options
+                        .write_line(into, Some(left), '<', None, painting)?;
+                }
+            }
+            Change::Insert(_, after_range) => {
+                for right in after.subsequence(*after_range) {
+                    let painting = self
+                        .palette
+                        .as_ref()
+                        .map(|palette| (palette, &[][..], &[][..]));
+                    self.options
+                        .write_line(into, None, '>', Some(right), painting)?;
+                }
+            }
+            Change::Replace(before_range, after_range) => {
+                let before_lines: Vec<&String> = before.subsequence(*before_range).collect();
+                let after_lines: Vec<&String> = after.subsequence(*after_range).collect();
+                let highlights = if self.palette.is_some() {
+                    replaced_lines_highlights(&before_lines, &after_lines)
+                } else {
+                    (vec![], vec![])
+                };
+                for i in 0..before_lines.len().max(after_lines.len()) {
+                    let (left, right) = (before_lines.get(i), after_lines.get(i));
+                    let gutter = match (left, right) {
+                        (Some(_), Some(_)) => '|',
+                        (Some(_), None) => '<',
+                        _ => '>',
+                    };
+                    let painting = self.palette.as_ref().map(|palette| {
+                        (
+                            palette,
+                            highlights.0.get(i).map_or(&[][..], Vec::as_slice),
+                            highlights.1.get(i).map_or(&[][..], Vec::as_slice),
+                        )
+                    });
+                    self.options.write_line(
+                        into,
+                        left.map(|line| line.as_str()),
+                        gutter,
+                        right.map(|line| line.as_str()),
+                        painting,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GnuTextDiff for SideBySideDiff {
+    const FORMAT_NAME: &'static str = "side by side";
+    const DIFF_OPTIONS: &'static str = "-Nry";
+
+    /// Side by side diffs show all lines (or only those that differ) so
+    /// `context` is ignored.  The default layout is used (see
+    /// `set_options()`).
+    fn new(before_file_path: &Path, after_file_path: &Path, _context: u8) -> io::Result<Self> {
+        Ok(Self::from_lines(
+            read_lines(before_file_path)?,
+            read_lines(after_file_path)?,
+            SideBySideOptions::default(),
+        ))
+    }
+
+    fn has_changes(&self) -> bool {
+        has_changes(&self.changes)
+    }
+
+    fn set_palette(&mut self, palette: Option<Palette>) {
+        self.palette = palette;
+    }
+
+    /// Unlike the other formats, the lines are written even if the files
+    /// are the same (unless common lines are suppressed).
+    fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        if self.options.suppress_common_lines {
+            // Clumps without context contain only the changes.
+            for clump in self.changes.change_clumps(0) {
+                for change in clump.iter() {
+                    self.write_change(into, change)?;
+                }
+            }
+        } else {
+            for change in self.changes.changes.iter() {
+                self.write_change(into, change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod side_by_side_tests {
+    use super::*;
+
+    static BEFORE: &str = "one\n\ttwo\nthree\nfour\nfive\nsix\n";
+    static AFTER: &str = "one\n\tTWO\nthree\nfive\nsix\nseven\neight";
+
+    fn written(options: SideBySideOptions) -> String {
+        let diff = SideBySideDiff::from_lines(Seq::from(BEFORE), Seq::from(AFTER), options);
+        let mut text = vec![];
+        diff.write_into(&mut text).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn side_by_side_matches_gnu_diff() {
+        // As made by GNU `diff -y`.
+        let expected = "one\t\t\t\t\t\t\t\tone
+\ttwo\t\t\t\t\t\t      |\t\tTWO
+three\t\t\t\t\t\t\t\tthree
+four\t\t\t\t\t\t\t      <
+five\t\t\t\t\t\t\t\tfive
+six\t\t\t\t\t\t\t\tsix
+\t\t\t\t\t\t\t      >\tseven
+\t\t\t\t\t\t\t      >\teight";
+        assert_eq!(written(SideBySideOptions::default()), expected);
+
+        // As made by GNU `diff -y -t -W 40 --suppress-common-lines`.
+        let expected = "        two        |          TWO
+four               <
+                   >  seven
+                   >  eight";
+        let options = SideBySideOptions {
+            width: 40,
+            expand_tabs: true,
+            suppress_common_lines: true,
+            ..SideBySideOptions::default()
+        };
+        assert_eq!(written(options), expected);
+    }
+
+    #[test]
+    fn coloured_side_by_side() {
+        let mut diff = SideBySideDiff::from_lines(
+            Seq::from("same\nlet x = 1;\ngone\n"),
+            Seq::from("same\nlet y = 1;\n"),
+            SideBySideOptions {
+                width: 40,
+                expand_tabs: true,
+                ..SideBySideOptions::default()
+            },
+        );
+        diff.set_palette(Some(Palette::default()));
+        let mut text = vec![];
+        diff.write_into(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "same                  same
+\x1b[31mlet \x1b[7mx\x1b[0m\x1b[31m = 1;\x1b[0m         |  \x1b[32mlet \x1b[7my\x1b[0m\x1b[32m = 1;\x1b[0m
+\x1b[31mgone\x1b[0m               <
+"
+        );
+    }
+
+    #[test]
+    fn side_by_side_truncation_and_missing_newlines() {
+        // As made by GNU `diff -y -W 30`.
+        let diff = SideBySideDiff::from_lines(
+            Seq::from("abcdefghijklmnopqrstuvwxyz\n"),
+            Seq::from("a\n\nb"),
+            SideBySideOptions {
+                width: 30,
+                ..SideBySideOptions::default()
+            },
+        );
+        let mut text = vec![];
+        diff.write_into(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "abcdefghijklm |\ta\n\t      >\n\t      >\tb"
+        );
+        assert!(diff.has_changes());
+
+        let diff = SideBySideDiff::from_lines(
+            Seq::from("a\nc\nB\n"),
+            Seq::from("a\n\nb"),
+            SideBySideOptions {
+                width: 30,
+                ..SideBySideOptions::default()
+            },
+        );
+        let mut text = vec![];
+        diff.write_into(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "a\t\ta\nc\t      |\nB\t      /\tb\n"
+        );
+    }
+}
@@ -1,26 +1,163 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
+use std::borrow::Cow;
 use std::error::Error;
 use std::fmt;
+use std::fs::{self, File};
+use std::io;
 use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use chrono::{DateTime, Local, NaiveDateTime};
+
+use pw_diff_lib::apply_text::{ApplyClumpFuzzy, ApplyClumpsFuzzy};
+use pw_diff_lib::changes::{Change, Changes};
+use pw_diff_lib::diff::Diff;
+use pw_diff_lib::diff_set::DiffSet;
+use pw_diff_lib::metadata::{is_symlink, path_exists};
+use pw_diff_lib::paths::strip_path;
+use pw_diff_lib::range::Range;
+use pw_diff_lib::sequence::Seq;
+use pw_diff_lib::text_diff::{PathAndLines, TextChangeClump, TextChangeDiff};
+
+use crate::colour::Palette;
+use crate::function_line::{FunctionLineMatcher, FunctionLines};
+use crate::git_binary::write_git_binary_diff;
+use crate::normal_diff::diff_command_paths;
+use crate::DEV_NULL;
+
+/// An error found while parsing a diff.  Line indices are zero based.
 #[derive(Debug)]
 pub enum DiffParseError {
     ParseNumberError(ParseIntError, usize),
     UnexpectedEndOfInput,
     UnexpectedEndClump(usize),
-    SyntaxError(usize),
+    /// The line at the index isn't what was expected (as described).
+    SyntaxError(usize, &'static str),
+}
+
+impl DiffParseError {
+    /// The index of the line at which the error was found (if known).
+    pub fn line_index(&self) -> Option<usize> {
+        match self {
+            Self::ParseNumberError(_, index)
+            | Self::UnexpectedEndClump(index)
+            | Self::SyntaxError(index, _) => Some(*index),
+            Self::UnexpectedEndOfInput => None,
+        }
+    }
+
+    /// What was expected (and, for numbers, why what was found isn't one).
+    fn expected(&self) -> String {
+        match self {
+            Self::ParseNumberError(err, _) => format!("expected a number ({err})"),
+            Self::UnexpectedEndOfInput => "expected more lines".to_string(),
+            Self::UnexpectedEndClump(_) => "expected another line of the clump".to_string(),
+            Self::SyntaxError(_, expected) => format!("expected {expected}"),
+        }
+    }
 }
 
 impl fmt::Display for DiffParseError {
-    // TODO: flesh out fmt::Display implementation for DiffParseError
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "bug the developer to fix this!")
+        match self.line_index() {
+            Some(index) => write!(f, "line {}: {}", index + 1, self.expected()),
+            None => write!(f, "unexpected end of input: {}", self.expected()),
+        }
     }
 }
 
 impl Error for DiffParseError {}
 
+/// A `DiffParseError` located in the text (and file) being parsed.
+#[derive(Debug)]
+pub struct PatchParseError {
+    pub file_path: Option<PathBuf>,
+    /// One based (one past the last line for the end of input).
+    pub line_number: usize,
+    /// Of the start of the line.
+    pub byte_offset: usize,
+    /// The offending line (without its line terminator).
+    pub found: Option<String>,
+    pub error: DiffParseError,
+}
+
+impl PatchParseError {
+    /// Locate `error` found while parsing `lines` (read from `file_path`).
+    ///
+    /// Example:
+    /// ```
+    /// use std::path::Path;
+    /// use pw_diff_lib::sequence::Seq;
+    /// use pw_cub_diff_lib::context_diff::ContextPatch;
+    /// use pw_cub_diff_lib::text_diff::{GnuPatch, PatchParseError};
+    ///
+    /// let lines = Seq::<String>::from("*** a\n--- b\n***************\n*** 1 ****\n- x\nbad\n");
+    /// let error = ContextPatch::from_lines(&lines).err().unwrap();
+    /// let error = PatchParseError::new(error, &lines, Some(Path::new("x.diff")));
+    /// assert_eq!((error.line_number, error.byte_offset), (6, 43));
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "x.diff:6: expected a `--- N,M ----` line but found \"bad\""
+    /// );
+    /// ```
+    pub fn new(error: DiffParseError, lines: &Seq<String>, file_path: Option<&Path>) -> Self {
+        let index = error.line_index().unwrap_or(lines.len()).min(lines.len());
+        Self {
+            file_path: file_path.map(Path::to_path_buf),
+            line_number: index + 1,
+            byte_offset: lines.subsequence(Range(0, index)).map(String::len).sum(),
+            found: lines
+                .get(index)
+                .map(|line| line.trim_end_matches(['\n', '\r']).to_string()),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for PatchParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file_path {
+            Some(file_path) => write!(f, "{}:{}: ", file_path.display(), self.line_number)?,
+            None => write!(f, "line {}: ", self.line_number)?,
+        }
+        match &self.found {
+            Some(found) => write!(f, "{} but found {found:?}", self.error.expected()),
+            None => write!(f, "unexpected end of input: {}", self.error.expected()),
+        }
+    }
+}
+
+impl Error for PatchParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Something dubious (but tolerated) found while parsing a diff leniently.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseWarning {
+    /// Zero based.
+    pub line_index: usize,
+    pub message: String,
+}
+
+impl ParseWarning {
+    pub fn new(line_index: usize, message: impl Into<String>) -> Self {
+        Self {
+            line_index,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line_index + 1, self.message)
+    }
+}
+
 pub type DiffParseResult<T> = Result<T, DiffParseError>;
 
 pub trait CheckEndOfInput<T> {
@@ -42,8 +179,701 @@ pub struct PathAndTimestamp {
     pub time_stamp: Option<String>,
 }
 
+impl PathAndTimestamp {
+    /// The path and (local) modification time of the file at `path` in the
+    /// format used by GNU `diff`.  A file that does not exist is given the
+    /// epoch as its time stamp (as GNU `diff -N` does).
+    pub fn of_file(path: &Path) -> io::Result<Self> {
+        Self::of_file_as(path, time_stamp)
+    }
+
+    /// As `of_file()` with the time stamp made by `format`.
+    pub fn of_file_as(path: &Path, format: fn(SystemTime) -> String) -> io::Result<Self> {
+        let modified = match fs::metadata(path) {
+            Ok(metadata) => metadata.modified()?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => UNIX_EPOCH,
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            file_path: path.to_string_lossy().to_string(),
+            time_stamp: Some(format(modified)),
+        })
+    }
+
+    /// Whether this names a file that doesn't exist: either "/dev/null" or
+    /// (if `shown_empty`, i.e. the diff shows none of its lines) a file
+    /// time stamped with the epoch, as GNU `diff -N` marks them.
+    pub fn is_absent(&self, shown_empty: bool) -> bool {
+        if self.file_path == DEV_NULL {
+            true
+        } else if let (true, Some(time_stamp)) = (shown_empty, &self.time_stamp) {
+            // Time stamps without a time zone are in local time.
+            DateTime::parse_from_str(time_stamp, "%Y-%m-%d %H:%M:%S%.f %z")
+                .or_else(|_| DateTime::parse_from_str(time_stamp, "%a %b %e %H:%M:%S %Y %z"))
+                .map(|time| time.timestamp() == 0)
+                .or_else(|_| {
+                    NaiveDateTime::parse_from_str(time_stamp, "%a %b %e %H:%M:%S %Y")
+                        .map(|time| time == DateTime::<Local>::from(UNIX_EPOCH).naive_local())
+                })
+                .unwrap_or(false)
+        } else {
+            false
+        }
+    }
+}
+
+impl fmt::Display for PathAndTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.time_stamp {
+            Some(time_stamp) => write!(f, "{}\t{time_stamp}", quoted_path(&self.file_path)),
+            None => write!(f, "{}", quoted_path(&self.file_path)),
+        }
+    }
+}
+
+/// The path of the file a diff between `before` and `after` should be
+/// applied to.  Of the named files that exist (once `strip` components are
+/// stripped), or all of them if none exist, the one with the fewest
+/// components then the shortest base name then the shortest path is chosen
+/// (as GNU `patch` does).
+pub fn target_path(before: &PathAndTimestamp, after: &PathAndTimestamp, strip: usize) -> PathBuf {
+    let candidates: Vec<&Path> = [before, after]
+        .into_iter()
+        .filter(|path_and_time_stamp| path_and_time_stamp.file_path != DEV_NULL)
+        .map(|path_and_time_stamp| Path::new(&path_and_time_stamp.file_path))
+        .collect();
+    let existing: Vec<&Path> = candidates
+        .iter()
+        .copied()
+        .filter(|path| strip_path(path, strip).is_ok_and(|path| path_exists(&path)))
+        .collect();
+    let candidates = if existing.is_empty() {
+        candidates
+    } else {
+        existing
+    };
+    candidates
+        .into_iter()
+        .min_by_key(|path| {
+            (
+                path.components().count(),
+                path.file_name().map_or(0, |name| name.len()),
+                path.as_os_str().len(),
+            )
+        })
+        .unwrap_or(Path::new(DEV_NULL))
+        .to_path_buf()
+}
+
+/// `time` formatted as in GNU `diff` file headers.
+///
+/// Example:
+/// ```
+/// use std::time::UNIX_EPOCH;
+/// use regex::Regex;
+/// use pw_cub_diff_lib::text_diff::time_stamp;
+/// use pw_cub_diff_lib::TIMESTAMP_RE_STR;
+/// let regex = Regex::new(&format!("^{TIMESTAMP_RE_STR}$")).unwrap();
+/// assert!(regex.is_match(&time_stamp(UNIX_EPOCH)));
+/// ```
+pub fn time_stamp(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M:%S%.9f %z")
+        .to_string()
+}
+
+/// `time` formatted as in GNU `diff -c` file headers.
+///
+/// Example:
+/// ```
+/// use std::time::UNIX_EPOCH;
+/// use regex::Regex;
+/// use pw_cub_diff_lib::text_diff::context_time_stamp;
+/// use pw_cub_diff_lib::ALT_TIMESTAMP_RE_STR;
+/// let regex = Regex::new(&format!("^{ALT_TIMESTAMP_RE_STR}$")).unwrap();
+/// assert!(regex.is_match(&context_time_stamp(UNIX_EPOCH)));
+/// ```
+pub fn context_time_stamp(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%a %b %e %H:%M:%S %Y")
+        .to_string()
+}
+
+/// `path` as GNU `diff` shows it: in double quotes with C style escapes if
+/// it contains white space, quotes, backslashes or control characters.
+///
+/// Example:
+/// ```
+/// use pw_cub_diff_lib::text_diff::quoted_path;
+/// assert_eq!(quoted_path("a/b.rs"), "a/b.rs");
+/// assert_eq!(quoted_path("a/b c"), "\"a/b c\"");
+/// assert_eq!(quoted_path("a\tb"), "\"a\\tb\"");
+/// ```
+pub fn quoted_path(path: &str) -> Cow<'_, str> {
+    if !path
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '\\')
+    {
+        return Cow::Borrowed(path);
+    }
+    let mut quoted = String::from('"');
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// The inverse of `quoted_path()` applied to the text between the quotes.
+///
+/// Example:
+/// ```
+/// use pw_cub_diff_lib::text_diff::unquoted_path;
+/// assert_eq!(unquoted_path("a/b c"), "a/b c");
+/// assert_eq!(unquoted_path("a\\tb\\\"\\101"), "a\tb\"A");
+/// ```
+pub fn unquoted_path(quoted: &str) -> String {
+    let mut path = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => path.push('\t'),
+            Some('n') => path.push('\n'),
+            Some('r') => path.push('\r'),
+            Some(digit @ '0'..='7') => {
+                let mut code = digit.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(value) => {
+                            code = code * 8 + value;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                path.extend(char::from_u32(code));
+            }
+            Some(c) => path.push(c),
+            None => path.push('\\'),
+        }
+    }
+    path
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct StartAndLength {
     pub start: usize,
     pub length: usize,
 }
+
+impl StartAndLength {
+    /// The (zero based) index of the first line.  Line numbers in clump
+    /// headers and commands start at 1 (or are that of the line before if
+    /// the length is zero).
+    pub fn start_index(&self) -> usize {
+        if self.length == 0 {
+            self.start
+        } else {
+            self.start.saturating_sub(1)
+        }
+    }
+}
+
+/// The lines of the file at `path` (none if it doesn't exist).
+pub(crate) fn read_lines(path: &Path) -> io::Result<Seq<String>> {
+    match File::open(path) {
+        Ok(file) => Seq::<String>::read(file),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Seq::default()),
+        Err(err) => Err(err),
+    }
+}
+
+pub(crate) fn has_changes(changes: &Changes<String>) -> bool {
+    changes
+        .changes
+        .iter()
+        .any(|change| !matches!(change, Change::NoChange(_)))
+}
+
+/// Where the file at `path` is once `strip` components are stripped (it
+/// is used as is if it was `named` on the command line).
+pub(crate) fn target_file(path: &Path, strip: usize, named: bool) -> io::Result<PathBuf> {
+    if named {
+        Ok(path.to_path_buf())
+    } else {
+        strip_path(path, strip)
+    }
+}
+
+/// The diff of a single file in a patch in one of GNU `diff`'s text
+/// formats together with any other lines (e.g. a `diff` command line
+/// naming the files) that preceded it in the patch.
+pub trait GnuFileDiff: Sized {
+    /// The file diff (if any) starting at `start_index`.
+    fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>>;
+
+    fn lines_consumed(&self) -> usize;
+
+    fn preamble(&self) -> &[String];
+
+    fn set_preamble(&mut self, preamble: Vec<String>);
+
+    /// The files named by the diff's header lines (if the format has them).
+    fn header_paths(&self) -> Option<(&PathAndTimestamp, &PathAndTimestamp)> {
+        None
+    }
+
+    /// The numbers of lines each clump (or command) removes and inserts.
+    fn clump_lengths(&self) -> impl Iterator<Item = (usize, usize)>;
+
+    /// The lines of the file after (or, if `reverse`, before) the changes
+    /// when the diff creates (or removes) it.
+    fn lines(&self, reverse: bool) -> Vec<String>;
+
+    /// The clumps of changes to the file at `path` (see `target_file()`).
+    fn text_change_clumps(
+        &self,
+        path: &Path,
+        strip: usize,
+        named: bool,
+    ) -> io::Result<Vec<TextChangeClump>>;
+
+    /// Whether the diff creates its file (as GNU `patch` decides).  Diffs
+    /// without header lines can't tell (see `to_diff()`).
+    fn is_creation(&self) -> bool {
+        self.header_paths().is_some_and(|(before, _)| {
+            before.is_absent(self.clump_lengths().all(|(removed, _)| removed == 0))
+        })
+    }
+
+    /// Whether the diff removes its file (as GNU `patch` decides).
+    fn is_removal(&self) -> bool {
+        self.header_paths().is_some_and(|(_, after)| {
+            after.is_absent(self.clump_lengths().all(|(_, inserted)| inserted == 0))
+        })
+    }
+
+    /// The path of the file to be patched: `file_path` if given, else one
+    /// of those named by the header lines or, failing those, by a `diff`
+    /// command line at the end of the preamble (see `target_path()`).
+    fn target_path(&self, strip: usize, file_path: Option<&Path>) -> io::Result<PathBuf> {
+        if let Some(file_path) = file_path {
+            return Ok(file_path.to_path_buf());
+        }
+        if let Some((before, after)) = self.header_paths() {
+            return Ok(target_path(before, after, strip));
+        }
+        match self
+            .preamble()
+            .last()
+            .and_then(|line| diff_command_paths(line))
+        {
+            Some((before, after)) => Ok(target_path(&before, &after, strip)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the file to patch is not named",
+            )),
+        }
+    }
+
+    /// The equivalent `Diff` (for applying to files `strip` components
+    /// down).  The file patched is `file_path` if given (in which case it
+    /// isn't subject to `strip`) or else `target_path()`.  A diff without
+    /// header lines that only inserts lines into a file that doesn't exist
+    /// creates it.
+    fn to_diff(&self, strip: usize, file_path: Option<&Path>) -> io::Result<Diff> {
+        gnu_file_diff_to_diff(self, strip, file_path)
+    }
+}
+
+/// The body of `GnuFileDiff::to_diff()` (for formats that extend it).
+pub(crate) fn gnu_file_diff_to_diff<D: GnuFileDiff>(
+    file_diff: &D,
+    strip: usize,
+    file_path: Option<&Path>,
+) -> io::Result<Diff> {
+    let path = file_diff.target_path(strip, file_path)?;
+    let header_path = |reverse: bool| match (file_path, file_diff.header_paths()) {
+        (None, Some((before, after))) => {
+            PathBuf::from(if reverse { before } else { after }.file_path.clone())
+        }
+        _ => path.clone(),
+    };
+    let is_creation = match file_diff.header_paths() {
+        Some(_) => file_diff.is_creation(),
+        None => {
+            file_diff.clump_lengths().all(|(removed, _)| removed == 0)
+                && !target_file(&path, strip, file_path.is_some())?.exists()
+        }
+    };
+    if is_creation {
+        let lines = file_diff.lines(false);
+        Ok(Diff::TextAdd(PathAndLines::from_lines(
+            &header_path(false),
+            None,
+            lines,
+        )))
+    } else if file_diff.is_removal() {
+        let lines = file_diff.lines(true);
+        Ok(Diff::TextRemove(PathAndLines::from_lines(
+            &header_path(true),
+            None,
+            lines,
+        )))
+    } else {
+        let clumps = file_diff.text_change_clumps(&path, strip, file_path.is_some())?;
+        Ok(Diff::TextChange(TextChangeDiff::from_clumps(
+            &path, &path, clumps,
+        )))
+    }
+}
+
+/// The numbers of lines removed and inserted by each of `file_diff`'s clumps.
+pub(crate) fn clump_lengths<'a, C, D>(file_diff: &'a D) -> impl Iterator<Item = (usize, usize)> + 'a
+where
+    C: ApplyClumpFuzzy + 'a,
+    D: ApplyClumpsFuzzy<C>,
+{
+    file_diff
+        .clumps()
+        .map(|clump| (clump.before_length(false), clump.after_length(false)))
+}
+
+/// The lines inserted (or, if `reverse`, removed) by `file_diff`'s clumps.
+pub(crate) fn clump_lines<C, D>(file_diff: &D, reverse: bool) -> Vec<String>
+where
+    C: ApplyClumpFuzzy,
+    D: ApplyClumpsFuzzy<C>,
+{
+    file_diff
+        .clumps()
+        .flat_map(|clump| clump.after_lines(None, reverse).cloned())
+        .collect()
+}
+
+/// A patch in one of GNU `diff`'s text formats: the diffs of one or more
+/// files possibly with other text before, between and after them.
+pub trait GnuPatch: Sized {
+    type FileDiff: GnuFileDiff;
+
+    fn from_file_diffs(file_diffs: Vec<Self::FileDiff>) -> Self;
+
+    fn file_diffs(&self) -> &[Self::FileDiff];
+
+    fn from_lines(lines: &Seq<String>) -> DiffParseResult<Self> {
+        file_diffs_from_lines(lines, Self::FileDiff::get_from_at).map(Self::from_file_diffs)
+    }
+
+    /// The equivalent `DiffSet` (see `GnuFileDiff::to_diff()`).
+    fn to_diff_set(&self, strip: usize, file_path: Option<&Path>) -> io::Result<DiffSet> {
+        let diffs = self
+            .file_diffs()
+            .iter()
+            .map(|file_diff| file_diff.to_diff(strip, file_path))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(DiffSet::from(diffs))
+    }
+}
+
+/// The file diffs found in `lines` by `get_from_at` each with the lines
+/// preceding it (that aren't part of another file diff) as its preamble.
+pub(crate) fn file_diffs_from_lines<D: GnuFileDiff>(
+    lines: &Seq<String>,
+    mut get_from_at: impl FnMut(&Seq<String>, usize) -> DiffParseResult<Option<D>>,
+) -> DiffParseResult<Vec<D>> {
+    let mut file_diffs = vec![];
+    let mut preamble = vec![];
+    let mut index = 0;
+    while index < lines.len() {
+        if let Some(mut file_diff) = get_from_at(lines, index)? {
+            index += file_diff.lines_consumed();
+            file_diff.set_preamble(std::mem::take(&mut preamble));
+            file_diffs.push(file_diff);
+        } else {
+            preamble.push(lines[index].clone());
+            index += 1;
+        }
+    }
+    Ok(file_diffs)
+}
+
+/// The differences between two files in one of GNU `diff`'s text formats.
+pub trait GnuTextDiff: Sized {
+    /// The format's name (for messages).
+    const FORMAT_NAME: &'static str;
+    /// The options given to GNU `diff` to compare directories in this format.
+    const DIFF_OPTIONS: &'static str;
+
+    /// A file that does not exist is treated as being empty (as with
+    /// GNU `diff -N`).
+    fn new(before_file_path: &Path, after_file_path: &Path, context: u8) -> io::Result<Self>;
+
+    fn has_changes(&self) -> bool;
+
+    /// Show the function lines `matcher` finds in clump headers (if the
+    /// format has them).
+    fn set_function_line_matcher(&mut self, _matcher: Option<FunctionLineMatcher>) {}
+
+    /// Colour the diff with `palette` (if the format can be coloured).
+    fn set_palette(&mut self, _palette: Option<Palette>) {}
+
+    /// Write the diff (nothing at all if the files are the same).
+    fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()>;
+}
+
+/// Whether GNU `diff` would consider the file at `path` to be binary
+/// (i.e. it contains a NUL byte).
+pub(crate) fn is_binary(path: &Path) -> io::Result<bool> {
+    match fs::read(path) {
+        Ok(content) => Ok(content.contains(&0)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+// Whether there's a directory (rather than a file or symbolic link) at
+// `path`.
+fn is_real_dir(path: &Path) -> bool {
+    path.is_dir() && !is_symlink(path)
+}
+
+// The kinds of the things at the given paths (as GNU `diff -r` describes
+// them) if one is a directory and the other a file.
+pub(crate) fn file_type_mismatch(
+    before_file_path: &Path,
+    after_file_path: &Path,
+) -> Option<(&'static str, &'static str)> {
+    let kind = |path: &Path| {
+        if is_real_dir(path) {
+            "a directory"
+        } else {
+            "a regular file"
+        }
+    };
+    if path_exists(before_file_path)
+        && path_exists(after_file_path)
+        && is_real_dir(before_file_path) != is_real_dir(after_file_path)
+    {
+        Some((kind(before_file_path), kind(after_file_path)))
+    } else {
+        None
+    }
+}
+
+// Whether `rel_path` is below something in `dir` that isn't a directory.
+pub(crate) fn is_below_file(dir: &Path, rel_path: &Path) -> bool {
+    rel_path
+        .ancestors()
+        .skip(1)
+        .any(|ancestor| path_exists(&dir.join(ancestor)) && !is_real_dir(&dir.join(ancestor)))
+}
+
+fn unsupported<D: GnuTextDiff>(diff: &Diff) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{:?}: renames and copies can't be shown as {} diffs",
+            diff.paths(),
+            D::FORMAT_NAME
+        ),
+    )
+}
+
+/// Write `diff` (made from the files at the given paths) in the format
+/// `D` as GNU `diff` would.  Changes to file modes are not shown.  If
+/// `binary` is set, changes to binary files are written as git binary
+/// patches.
+pub fn write_diff<D: GnuTextDiff, W: io::Write>(
+    diff: &Diff,
+    before_file_path: &Path,
+    after_file_path: &Path,
+    context: u8,
+    function_lines: &FunctionLines,
+    binary: bool,
+    into: &mut W,
+) -> io::Result<()> {
+    write_diff_with(
+        diff,
+        before_file_path,
+        after_file_path,
+        |before_file_path, after_file_path| {
+            new_text_diff::<D>(before_file_path, after_file_path, context, function_lines)
+        },
+        binary,
+        into,
+    )
+}
+
+fn new_text_diff<D: GnuTextDiff>(
+    before_file_path: &Path,
+    after_file_path: &Path,
+    context: u8,
+    function_lines: &FunctionLines,
+) -> io::Result<D> {
+    let mut text_diff = D::new(before_file_path, after_file_path, context)?;
+    text_diff.set_function_line_matcher(function_lines.matcher_for(before_file_path));
+    Ok(text_diff)
+}
+
+/// As `write_diff()` with changes to text files made into text diffs by
+/// `new_text_diff` (for formats with options of their own).
+pub fn write_diff_with<D, F, W>(
+    diff: &Diff,
+    before_file_path: &Path,
+    after_file_path: &Path,
+    new_text_diff: F,
+    binary: bool,
+    into: &mut W,
+) -> io::Result<()>
+where
+    D: GnuTextDiff,
+    F: Fn(&Path, &Path) -> io::Result<D>,
+    W: io::Write,
+{
+    let before = quoted_path(&before_file_path.to_string_lossy()).to_string();
+    let after = quoted_path(&after_file_path.to_string_lossy()).to_string();
+    match diff {
+        Diff::TextChange(_) | Diff::TextAdd(_) | Diff::TextRemove(_)
+            if !is_binary(before_file_path)? && !is_binary(after_file_path)? =>
+        {
+            new_text_diff(before_file_path, after_file_path)?.write_into(into)
+        }
+        // As GNU `diff`, identical binary files aren't mentioned.
+        Diff::TextChange(_) | Diff::ByteChange(_)
+            if fs::read(before_file_path)? == fs::read(after_file_path)? =>
+        {
+            Ok(())
+        }
+        Diff::TextChange(_)
+        | Diff::TextAdd(_)
+        | Diff::TextRemove(_)
+        | Diff::ByteChange(_)
+        | Diff::ByteAdd(_)
+        | Diff::ByteRemove(_) => {
+            if binary {
+                write_git_binary_diff(before_file_path, after_file_path, into)
+            } else {
+                writeln!(into, "Binary files {before} and {after} differ")
+            }
+        }
+        Diff::SymlinkAdd(_) | Diff::SymlinkRemove(_) | Diff::SymlinkChange(_) => {
+            writeln!(into, "Symbolic links {before} and {after} differ")
+        }
+        Diff::ModeChange(_) => Ok(()),
+        Diff::TextRename(_) | Diff::TextCopy(_) | Diff::ByteRename(_) | Diff::ByteCopy(_) => {
+            Err(unsupported::<D>(diff))
+        }
+    }
+}
+
+/// Write `diff_set` (made by comparing `before_dir` with `after_dir`) in
+/// the format `D` as GNU `diff -Nr before_dir after_dir` would (see
+/// `write_diff()` for `binary`).
+pub fn write_diff_set<D: GnuTextDiff, W: io::Write>(
+    diff_set: &DiffSet,
+    before_dir: &Path,
+    after_dir: &Path,
+    context: u8,
+    function_lines: &FunctionLines,
+    binary: bool,
+    into: &mut W,
+) -> io::Result<()> {
+    write_diff_set_with(
+        diff_set,
+        before_dir,
+        after_dir,
+        |before_file_path, after_file_path| {
+            new_text_diff::<D>(before_file_path, after_file_path, context, function_lines)
+        },
+        binary,
+        into,
+    )
+}
+
+/// As `write_diff_set()` with text diffs made by `new_text_diff` (see
+/// `write_diff_with()`).
+pub fn write_diff_set_with<D, F, W>(
+    diff_set: &DiffSet,
+    before_dir: &Path,
+    after_dir: &Path,
+    new_text_diff: F,
+    binary: bool,
+    into: &mut W,
+) -> io::Result<()>
+where
+    D: GnuTextDiff,
+    F: Fn(&Path, &Path) -> io::Result<D>,
+    W: io::Write,
+{
+    for diff in diff_set.diffs() {
+        let rel_path = match diff {
+            Diff::TextRename(_) | Diff::TextCopy(_) | Diff::ByteRename(_) | Diff::ByteCopy(_) => {
+                return Err(unsupported::<D>(diff))
+            }
+            _ => diff.paths()[0].strip_prefix(before_dir).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{:?}: not in {before_dir:?}", diff.paths()[0]),
+                )
+            })?,
+        };
+        let before_file_path = before_dir.join(rel_path);
+        let after_file_path = after_dir.join(rel_path);
+        // As GNU `diff`, a file replaced by a directory (or vice versa) is
+        // mentioned but what's in the directory isn't.
+        if let Some((before_kind, after_kind)) =
+            file_type_mismatch(&before_file_path, &after_file_path)
+        {
+            writeln!(
+                into,
+                "File {} is {before_kind} while file {} is {after_kind}",
+                before_file_path.display(),
+                after_file_path.display()
+            )?;
+            continue;
+        } else if is_below_file(before_dir, rel_path) || is_below_file(after_dir, rel_path) {
+            continue;
+        }
+        if matches!(
+            diff,
+            Diff::TextChange(_) | Diff::TextAdd(_) | Diff::TextRemove(_)
+        ) && !is_binary(&before_file_path)?
+            && !is_binary(&after_file_path)?
+        {
+            // Empty files that were added or removed aren't mentioned.
+            let text_diff = new_text_diff(&before_file_path, &after_file_path)?;
+            if text_diff.has_changes() {
+                writeln!(
+                    into,
+                    "diff {} {} {}",
+                    D::DIFF_OPTIONS,
+                    quoted_path(&before_file_path.to_string_lossy()),
+                    quoted_path(&after_file_path.to_string_lossy())
+                )?;
+                text_diff.write_into(into)?;
+            }
+        } else {
+            write_diff_with(
+                diff,
+                &before_file_path,
+                &after_file_path,
+                &new_text_diff,
+                binary,
+                into,
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -1,22 +1,39 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
 use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use regex::{Captures, Regex};
 
-use pw_diff_lib::apply_text::TextClumpBasics;
-use pw_diff_lib::changes::{Change, ChangeBasics, ChangeClumpIter};
+use pw_diff_lib::apply_text::{ApplyClumpFuzzy, ApplyClumpsFuzzy, TextClumpBasics};
+use pw_diff_lib::byte_diff::{ByteChangeDiff, PathAndBytes};
+use pw_diff_lib::changes::{Change, ChangeBasics, ChangeClump, ChangeClumpIter, Changes};
+use pw_diff_lib::diff::{Diff, Relocation};
+use pw_diff_lib::metadata::{ModeChange, Modes, Symlink, SymlinkChange};
+use pw_diff_lib::paths::strip_path;
 use pw_diff_lib::range::{Len, Range};
 use pw_diff_lib::sequence::Seq;
+use pw_diff_lib::snippet::Snippet;
+use pw_diff_lib::text_diff::{PathAndLines, TextChangeClump, TextChangeDiff};
 
+use crate::colour::{replaced_lines_highlights, Palette};
+use crate::function_line::FunctionLineMatcher;
+use crate::git_binary::GitBinaryPatch;
+use crate::git_diff::GitHeader;
+use crate::gnu_changes::gnu_changes;
 use crate::text_diff::{
-    CheckEndOfInput, DiffParseError, DiffParseResult, PathAndTimestamp, StartAndLength,
+    clump_lengths, clump_lines, file_diffs_from_lines, gnu_file_diff_to_diff, has_changes,
+    read_lines, unquoted_path, DiffParseError, DiffParseResult, GnuFileDiff, GnuPatch, GnuTextDiff,
+    ParseWarning, PathAndTimestamp, StartAndLength,
 };
-use crate::{ALT_TIMESTAMP_RE_STR, PATH_RE_STR, TIMESTAMP_RE_STR};
+use crate::{ALT_TIMESTAMP_RE_STR, DEV_NULL, PATH_RE_STR, TIMESTAMP_RE_STR};
 
 lazy_static::lazy_static! {
     pub static ref EITHER_TIME_STAMP_RE_STR: String = format!("({TIMESTAMP_RE_STR}|{ALT_TIMESTAMP_RE_STR})");
+    static ref EITHER_TIME_STAMP_REGEX: Regex = Regex::new(&format!("^{}", *EITHER_TIME_STAMP_RE_STR)).unwrap();
     pub static ref BEFORE_PATH_REGEX: Regex =
         Regex::new(&format!(r"^--- ({PATH_RE_STR})\s+({TIMESTAMP_RE_STR}|{ALT_TIMESTAMP_RE_STR})?(.*)(\n)?$")).unwrap();
 
@@ -29,25 +46,42 @@ lazy_static::lazy_static! {
 
 fn path_and_time_stamp_from_captures(captures: &Captures) -> PathAndTimestamp {
     let file_path = if let Some(path) = captures.get(2) {
-        path.as_str()
+        unquoted_path(path.as_str())
     } else {
-        captures.get(3).unwrap().as_str() // TODO: confirm unwrap is OK here
+        captures.get(3).unwrap().as_str().to_string() // TODO: confirm unwrap is OK here
     };
     let time_stamp = captures.get(4).map(|ts| ts.as_str().to_string());
     PathAndTimestamp {
-        file_path: file_path.to_string(),
+        file_path,
         time_stamp,
     }
 }
 
+pub(crate) fn path_and_time_stamp(line: &str, regex: &Regex) -> Option<PathAndTimestamp> {
+    let captures = regex.captures(line)?;
+    let mut path_and_time_stamp = path_and_time_stamp_from_captures(&captures);
+    // An unquoted path containing spaces is terminated by a tab (as git does).
+    if captures.get(2).is_none() {
+        if let Some((path, rest)) = line[4..].split_once('\t') {
+            if path.contains(' ') {
+                path_and_time_stamp = PathAndTimestamp {
+                    file_path: path.to_string(),
+                    time_stamp: EITHER_TIME_STAMP_REGEX
+                        .find(rest)
+                        .map(|ts| ts.as_str().to_string()),
+                };
+            }
+        }
+    }
+    Some(path_and_time_stamp)
+}
+
 pub fn before_path_and_time_stamp(line: &str) -> Option<PathAndTimestamp> {
-    let captures = BEFORE_PATH_REGEX.captures(line)?;
-    Some(path_and_time_stamp_from_captures(&captures))
+    path_and_time_stamp(line, &BEFORE_PATH_REGEX)
 }
 
 pub fn after_path_and_time_stamp(line: &str) -> Option<PathAndTimestamp> {
-    let captures = AFTER_PATH_REGEX.captures(line)?;
-    Some(path_and_time_stamp_from_captures(&captures))
+    path_and_time_stamp(line, &AFTER_PATH_REGEX)
 }
 
 fn start_and_length_from_captures(
@@ -59,7 +93,10 @@ fn start_and_length_from_captures(
     let start: usize = if let Some(m) = captures.get(line_num) {
         usize::from_str(m.as_str()).map_err(|e| DiffParseError::ParseNumberError(e, line_number))?
     } else {
-        return Err(DiffParseError::SyntaxError(line_number));
+        return Err(DiffParseError::SyntaxError(
+            line_number,
+            "a clump header line (`@@ -N,M +N,M @@`)",
+        ));
     };
     let length: usize = if let Some(m) = captures.get(length) {
         usize::from_str(m.as_str()).map_err(|e| DiffParseError::ParseNumberError(e, line_number))?
@@ -75,6 +112,25 @@ pub struct StartsAndLengths {
     pub after: StartAndLength,
 }
 
+impl StartsAndLengths {
+    /// Make the lengths `lengths` (adjusting the starts of those that
+    /// become or cease to be empty) and return whether anything changed.
+    pub fn correct_lengths(&mut self, lengths: (usize, usize)) -> bool {
+        let correct = |start_and_length: &mut StartAndLength, length: usize| {
+            match (start_and_length.length, length) {
+                (0, 1..) => start_and_length.start += 1,
+                (1.., 0) => start_and_length.start = start_and_length.start.saturating_sub(1),
+                _ => (),
+            }
+            let changed = start_and_length.length != length;
+            start_and_length.length = length;
+            changed
+        };
+        let before_changed = correct(&mut self.before, lengths.0);
+        correct(&mut self.after, lengths.1) || before_changed
+    }
+}
+
 impl Display for StartsAndLengths {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if self.before.length == 1 {
@@ -117,8 +173,69 @@ pub fn starts_and_lengths(
     }
 }
 
+/// Apply a "\ No newline at end of file" line to the line before it
+/// (which was of `line_type` '-', '+' or ' ').
+fn no_newline_at_end(line_type: char, before_lines: &mut [String], after_lines: &mut [String]) {
+    let drop_final_newline = |lines: &mut [String]| {
+        if let Some(line) = lines.last_mut() {
+            if line.ends_with('\n') {
+                line.pop();
+            }
+        }
+    };
+    if line_type != '+' {
+        drop_final_newline(before_lines);
+    }
+    if line_type != '-' {
+        drop_final_newline(after_lines);
+    }
+}
+
+/// The text after a clump header's line ranges (if there is any).
+pub(crate) fn heading(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim_end_matches(['\n', '\r']).is_empty()
+}
+
+fn is_clump_line(line: &str) -> bool {
+    line.starts_with(['-', '+', ' '])
+}
+
+/// Whether `line` separates an email's signature from its body.
+fn is_signature_separator(line: &str) -> bool {
+    line.trim_end_matches(['\n', '\r']) == "-- "
+}
+
+/// Whether a file diff's `+++` header line is at `index`.
+fn is_after_header(lines: &Seq<String>, index: usize) -> bool {
+    lines[index].starts_with("+++ ") && index > 0 && lines[index - 1].starts_with("--- ")
+}
+
+/// Whether a file's diff (rather than a clump's line) starts at `index`.
+fn starts_file_diff(lines: &Seq<String>, index: usize) -> bool {
+    lines.get(index).is_some_and(|line| {
+        line.starts_with("diff ")
+            || line.starts_with("Index: ")
+            || (line.starts_with("--- ")
+                && lines
+                    .get(index + 1)
+                    .is_some_and(|next| next.starts_with("+++ ")))
+    })
+}
+
 pub struct UnifiedDiffClump {
     pub starts_and_lengths: StartsAndLengths,
+    /// The text after the line ranges in the header (e.g. a function's
+    /// first line).
+    pub heading: Option<String>,
     pub before_lines: Box<[String]>,
     pub after_lines: Box<[String]>,
     pub context_lengths: (u8, u8),
@@ -127,97 +244,149 @@ pub struct UnifiedDiffClump {
 
 impl UnifiedDiffClump {
     pub fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
-        let mut iter = lines.subsequence(lines.range_from(start_index));
-        let line = match iter.next() {
-            Some(line) => line,
-            None => return Ok(None),
+        Self::get_from_at_with(lines, start_index, None)
+    }
+
+    /// As `get_from_at()` but if `warnings` is given the clump's lines run
+    /// up to the next clump or file header (or anything else that isn't a
+    /// clump line), the header's line counts being corrected to match them
+    /// (rather than being an error), and blank lines are taken to be
+    /// context lines whose leading space has been stripped.
+    pub fn get_from_at_with(
+        lines: &Seq<String>,
+        start_index: usize,
+        mut warnings: Option<&mut Vec<ParseWarning>>,
+    ) -> DiffParseResult<Option<Self>> {
+        let Some(line) = lines.get(start_index) else {
+            return Ok(None);
         };
-        let starts_and_lengths = match starts_and_lengths(line, start_index)? {
+        let mut starts_and_lengths = match starts_and_lengths(line, start_index)? {
             Some(sal) => sal,
             None => return Ok(None),
         };
+        let heading = CLUMP_HEADER_REGEX
+            .captures(line)
+            .and_then(|captures| heading(captures.get(7)?.as_str()));
         let mut start_context_length = 0u8;
         let mut end_context_length = 0u8;
         let mut at_the_front = true;
-        let mut before_lines = vec![];
-        let mut after_lines = vec![];
-        let mut index = 0usize;
-        let mut last_line_type = "X";
-        while before_lines.len() < starts_and_lengths.before.length
-            || after_lines.len() < starts_and_lengths.after.length
-        {
-            let line = *iter.next().check_end_of_input()?;
-            if line.starts_with('-') {
-                before_lines.push(line.as_str().to_string());
+        let mut before_lines: Vec<String> = vec![];
+        let mut after_lines: Vec<String> = vec![];
+        let mut index = start_index + 1;
+        let mut last_line_type = ' ';
+        let mut counts_exceeded = false;
+        loop {
+            let counts_met = before_lines.len() >= starts_and_lengths.before.length
+                && after_lines.len() >= starts_and_lengths.after.length;
+            let Some(line) = lines.get(index) else {
+                if counts_met || warnings.is_some() {
+                    break;
+                }
+                return Err(DiffParseError::UnexpectedEndOfInput);
+            };
+            if line.starts_with('\\') && index > start_index + 1 {
+                no_newline_at_end(last_line_type, &mut before_lines, &mut after_lines);
+                index += 1;
+                continue;
+            }
+            let line = match warnings.as_deref_mut() {
+                None if counts_met => break,
+                None => line.as_str(),
+                Some(warnings) => {
+                    // Once the counts are met, a blank line or an email
+                    // signature's separator ends the clump.
+                    if starts_file_diff(lines, index) || line.starts_with("@@") {
+                        break;
+                    } else if is_blank(line) && !counts_met {
+                        warnings.push(ParseWarning::new(
+                            index,
+                            "blank line taken to be an empty context line",
+                        ));
+                        if line.ends_with("\r\n") {
+                            " \r\n"
+                        } else {
+                            " \n"
+                        }
+                    } else if is_clump_line(line) && !(counts_met && is_signature_separator(line)) {
+                        if counts_met && !counts_exceeded {
+                            counts_exceeded = true;
+                            warnings.push(ParseWarning::new(
+                                index,
+                                "line beyond the clump header's counts taken to be part of the clump",
+                            ));
+                        }
+                        line.as_str()
+                    } else {
+                        break;
+                    }
+                }
+            };
+            if let Some(text) = line.strip_prefix('-') {
+                before_lines.push(text.to_string());
                 end_context_length = 0;
                 at_the_front = false;
-                last_line_type = "-";
-            } else if line.starts_with('+') {
-                after_lines.push(line.as_str().to_string());
+                last_line_type = '-';
+            } else if let Some(text) = line.strip_prefix('+') {
+                after_lines.push(text.to_string());
                 end_context_length = 0;
                 at_the_front = false;
-                last_line_type = "+";
-            } else if line.starts_with(' ') {
-                before_lines.push(line.as_str().to_string());
-                after_lines.push(line.as_str().to_string());
+                last_line_type = '+';
+            } else if let Some(text) = line.strip_prefix(' ') {
+                before_lines.push(text.to_string());
+                after_lines.push(text.to_string());
                 if at_the_front {
                     start_context_length += 1
                 } else {
                     end_context_length += 1
                 }
-                last_line_type = "-";
+                last_line_type = ' ';
             } else {
-                return Err(DiffParseError::UnexpectedEndClump(start_index + index + 1));
+                return Err(DiffParseError::UnexpectedEndClump(index));
             }
             index += 1;
         }
-        let mut lines_consumed = index + 1;
-        if let Some(line) = iter.next() {
-            if line.starts_with("\\") {
-                lines_consumed += 1;
-                if last_line_type == "-" {
-                    let line = before_lines.pop().unwrap();
-                    before_lines.push(line.trim_end().to_string())
-                } else if last_line_type == "+" {
-                    let line = after_lines.pop().unwrap();
-                    after_lines.push(line.trim_end().to_string())
-                } else {
-                    let line = before_lines.pop().unwrap();
-                    before_lines.push(line.trim_end().to_string());
-                    let line = after_lines.pop().unwrap();
-                    after_lines.push(line.trim_end().to_string())
-                }
+        let lengths = (before_lines.len(), after_lines.len());
+        if let Some(warnings) = warnings {
+            let header = starts_and_lengths.to_string();
+            if starts_and_lengths.correct_lengths(lengths) {
+                warnings.push(ParseWarning::new(
+                    start_index,
+                    format!(
+                        "clump header {header} doesn't match the clump's lines: using {starts_and_lengths}"
+                    ),
+                ));
             }
         }
         Ok(Some(Self {
-            lines_consumed,
+            lines_consumed: index - start_index,
             before_lines: before_lines.into_boxed_slice(),
             after_lines: after_lines.into_boxed_slice(),
             starts_and_lengths,
+            heading,
             context_lengths: (start_context_length, end_context_length),
         }))
     }
-}
 
-impl ChangeBasics for UnifiedDiffClump {
-    fn before_start(&self, reverse: bool) -> usize {
+    fn start_and_length(&self, reverse: bool) -> &StartAndLength {
         if reverse {
-            self.starts_and_lengths.after.start
+            &self.starts_and_lengths.after
         } else {
-            self.starts_and_lengths.before.start
+            &self.starts_and_lengths.before
         }
     }
+}
+
+impl ChangeBasics for UnifiedDiffClump {
+    fn before_start(&self, reverse: bool) -> usize {
+        self.start_and_length(reverse).start_index()
+    }
 
     fn before_end(&self, reverse: bool) -> usize {
         self.before_start(reverse) + self.before_length(reverse)
     }
 
     fn before_length(&self, reverse: bool) -> usize {
-        if reverse {
-            self.starts_and_lengths.after.length
-        } else {
-            self.starts_and_lengths.before.length
-        }
+        self.start_and_length(reverse).length
     }
 }
 
@@ -226,6 +395,10 @@ impl TextClumpBasics for UnifiedDiffClump {
         self.context_lengths
     }
 
+    fn heading(&self) -> Option<&str> {
+        self.heading.as_deref()
+    }
+
     fn before_lines(&self, range: Option<Range>, reverse: bool) -> impl Iterator<Item = &String> {
         if let Some(range) = range {
             if reverse {
@@ -243,86 +416,715 @@ impl TextClumpBasics for UnifiedDiffClump {
     }
 }
 
+impl ApplyClumpFuzzy for UnifiedDiffClump {}
+
+impl From<&UnifiedDiffClump> for TextChangeClump {
+    fn from(clump: &UnifiedDiffClump) -> Self {
+        let mut text_change_clump = TextChangeClump::new(
+            Snippet {
+                start: clump.before_start(false),
+                items: clump.before_lines.clone(),
+            },
+            Snippet {
+                start: clump.after_start(false),
+                items: clump.after_lines.clone(),
+            },
+            clump.context_lengths,
+        );
+        text_change_clump.set_heading(clump.heading.clone());
+        text_change_clump
+    }
+}
+
+/// The context of the byte diffs made from git binary patches.
+const BYTE_CONTEXT: u8 = 2;
+
+/// The unified diff of a single file: its `---`/`+++` header lines and
+/// clumps together with any git header and other lines (e.g. a `diff`
+/// command line) that preceded it in the patch.  Git diffs of changes that
+/// don't touch content (e.g. renames and mode changes) have no clumps.
+pub struct UnifiedFileDiff {
+    pub preamble: Vec<String>,
+    pub git_header: Option<GitHeader>,
+    pub binary_patch: Option<GitBinaryPatch>,
+    pub before: PathAndTimestamp,
+    pub after: PathAndTimestamp,
+    pub clumps: Vec<UnifiedDiffClump>,
+    pub lines_consumed: usize,
+}
+
+impl UnifiedFileDiff {
+    /// The file diff without a git header (if any) starting at
+    /// `start_index`, parsed leniently if `warnings` is given (see
+    /// `UnifiedDiffClump::get_from_at_with()`).
+    pub fn get_from_at_with(
+        lines: &Seq<String>,
+        start_index: usize,
+        mut warnings: Option<&mut Vec<ParseWarning>>,
+    ) -> DiffParseResult<Option<Self>> {
+        let Some(before) = lines
+            .get(start_index)
+            .and_then(|line| before_path_and_time_stamp(line))
+        else {
+            return Ok(None);
+        };
+        let Some(after) = lines
+            .get(start_index + 1)
+            .and_then(|line| after_path_and_time_stamp(line))
+        else {
+            return Ok(None);
+        };
+        let mut index = start_index + 2;
+        let mut clumps = vec![];
+        while let Some(clump) =
+            UnifiedDiffClump::get_from_at_with(lines, index, warnings.as_deref_mut())?
+        {
+            index += clump.lines_consumed;
+            clumps.push(clump);
+        }
+        if clumps.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            preamble: vec![],
+            git_header: None,
+            binary_patch: None,
+            before,
+            after,
+            clumps,
+            lines_consumed: index - start_index,
+        }))
+    }
+
+    /// A file diff starting with a git header (and, if the content changed,
+    /// followed by an ordinary unified diff or a git binary patch).
+    pub fn get_git_diff_from_at(
+        lines: &Seq<String>,
+        start_index: usize,
+    ) -> DiffParseResult<Option<Self>> {
+        Self::get_git_diff_from_at_with(lines, start_index, None)
+    }
+
+    /// As `get_git_diff_from_at()` but leniently if `warnings` is given.
+    pub fn get_git_diff_from_at_with(
+        lines: &Seq<String>,
+        start_index: usize,
+        warnings: Option<&mut Vec<ParseWarning>>,
+    ) -> DiffParseResult<Option<Self>> {
+        let Some(git_header) = GitHeader::get_from_at(lines, start_index)? else {
+            return Ok(None);
+        };
+        let index = start_index + git_header.lines_consumed;
+        let mut file_diff = match Self::get_from_at_with(lines, index, warnings)? {
+            Some(file_diff) => file_diff,
+            None => {
+                let path_and_time_stamp = |file_path: &str, absent: bool| PathAndTimestamp {
+                    file_path: if absent { DEV_NULL } else { file_path }.to_string(),
+                    time_stamp: None,
+                };
+                let binary_patch = GitBinaryPatch::get_from_at(lines, index)?;
+                Self {
+                    preamble: vec![],
+                    git_header: None,
+                    before: path_and_time_stamp(&git_header.before_path, git_header.is_creation()),
+                    after: path_and_time_stamp(&git_header.after_path, git_header.is_removal()),
+                    clumps: vec![],
+                    lines_consumed: binary_patch
+                        .as_ref()
+                        .map_or(0, |patch| patch.lines_consumed),
+                    binary_patch,
+                }
+            }
+        };
+        file_diff.lines_consumed += git_header.lines_consumed;
+        file_diff.git_header = Some(git_header);
+        Ok(Some(file_diff))
+    }
+
+    /// A file diff with a git header or else one without (see
+    /// `get_from_at_with()`).
+    fn get_any_from_at_with(
+        lines: &Seq<String>,
+        start_index: usize,
+        mut warnings: Option<&mut Vec<ParseWarning>>,
+    ) -> DiffParseResult<Option<Self>> {
+        match Self::get_git_diff_from_at_with(lines, start_index, warnings.as_deref_mut())? {
+            Some(file_diff) => Ok(Some(file_diff)),
+            None => Self::get_from_at_with(lines, start_index, warnings),
+        }
+    }
+
+    fn text_change_diff(&self, path: &Path, modes: Option<Modes>) -> TextChangeDiff {
+        let mut diff = TextChangeDiff::from_clumps(
+            path,
+            path,
+            self.clumps.iter().map(TextChangeClump::from).collect(),
+        );
+        diff.set_modes(modes);
+        diff
+    }
+
+    /// Whether any of the clumps' lines end with CRLF (or a CR).
+    fn has_crs(&self) -> bool {
+        self.clumps.iter().any(|clump| {
+            clump
+                .before_lines
+                .iter()
+                .chain(clump.after_lines.iter())
+                .any(|line| line.trim_end_matches('\n').ends_with('\r'))
+        })
+    }
+
+    /// Drop the CRs from the ends of the clumps' lines (including those
+    /// whose LF was dropped by a "\ No newline at end of file" line).
+    fn drop_crs(&mut self) {
+        for clump in self.clumps.iter_mut() {
+            for line in clump
+                .before_lines
+                .iter_mut()
+                .chain(clump.after_lines.iter_mut())
+            {
+                if line.ends_with("\r\n") {
+                    line.remove(line.len() - 2);
+                } else if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+        }
+    }
+
+    /// As `GnuFileDiff::to_diff()` for diffs with a git header.
+    fn git_diff_to_diff(&self, git_header: &GitHeader, strip: usize) -> io::Result<Diff> {
+        let before_path = Path::new(&self.before.file_path);
+        let after_path = Path::new(&self.after.file_path);
+        let permissions = |mode: Option<u32>| mode.map(|mode| mode & 0o7777);
+        if let Some(binary_patch) = &self.binary_patch {
+            let (before, after) = binary_patch
+                .contents(|| fs::read(strip_path(&self.target_path(strip, None)?, strip)?))?;
+            if self.is_creation() {
+                let mode = permissions(git_header.after_mode());
+                Ok(Diff::ByteAdd(PathAndBytes::from_bytes(
+                    after_path, mode, after,
+                )))
+            } else if self.is_removal() {
+                let mode = permissions(git_header.before_mode());
+                Ok(Diff::ByteRemove(PathAndBytes::from_bytes(
+                    before_path,
+                    mode,
+                    before,
+                )))
+            } else {
+                let relocated = git_header.is_rename() || git_header.is_copy();
+                let path = if relocated {
+                    before_path.to_path_buf()
+                } else {
+                    self.target_path(strip, None)?
+                };
+                let (before, after) = (Seq::from(before), Seq::from(after));
+                let mut diff =
+                    ByteChangeDiff::from_bytes(&path, &path, before, after, BYTE_CONTEXT);
+                diff.set_modes(git_header.modes());
+                if relocated {
+                    let similarity = git_header.similarity_index.unwrap_or(100);
+                    let relocation = Relocation::new(after_path, similarity, diff);
+                    if git_header.is_rename() {
+                        Ok(Diff::ByteRename(relocation))
+                    } else {
+                        Ok(Diff::ByteCopy(relocation))
+                    }
+                } else {
+                    Ok(Diff::ByteChange(diff))
+                }
+            }
+        } else if git_header.binary {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{before_path:?}: the binary diff's content is missing"),
+            ))
+        } else if git_header.is_symlink() {
+            let target = |reverse: bool| PathBuf::from(self.lines(reverse).concat());
+            Ok(if self.is_creation() {
+                Diff::SymlinkAdd(Symlink::from_target(after_path, &target(false)))
+            } else if self.is_removal() {
+                Diff::SymlinkRemove(Symlink::from_target(before_path, &target(true)))
+            } else {
+                Diff::SymlinkChange(SymlinkChange::from_targets(
+                    before_path,
+                    &target(true),
+                    &target(false),
+                ))
+            })
+        } else if self.is_creation() {
+            let mode = permissions(git_header.after_mode());
+            let lines = self.lines(false);
+            Ok(Diff::TextAdd(PathAndLines::from_lines(
+                after_path, mode, lines,
+            )))
+        } else if self.is_removal() {
+            let mode = permissions(git_header.before_mode());
+            let lines = self.lines(true);
+            Ok(Diff::TextRemove(PathAndLines::from_lines(
+                before_path,
+                mode,
+                lines,
+            )))
+        } else if git_header.is_rename() || git_header.is_copy() {
+            let diff = self.text_change_diff(before_path, git_header.modes());
+            let relocation =
+                Relocation::new(after_path, git_header.similarity_index.unwrap_or(100), diff);
+            if git_header.is_rename() {
+                Ok(Diff::TextRename(relocation))
+            } else {
+                Ok(Diff::TextCopy(relocation))
+            }
+        } else if let (true, Some(modes)) = (self.clumps.is_empty(), git_header.modes()) {
+            Ok(Diff::ModeChange(ModeChange::new(before_path, modes)))
+        } else {
+            let path = self.target_path(strip, None)?;
+            Ok(Diff::TextChange(
+                self.text_change_diff(&path, git_header.modes()),
+            ))
+        }
+    }
+}
+
+impl GnuFileDiff for UnifiedFileDiff {
+    fn get_from_at(lines: &Seq<String>, start_index: usize) -> DiffParseResult<Option<Self>> {
+        Self::get_any_from_at_with(lines, start_index, None)
+    }
+
+    fn lines_consumed(&self) -> usize {
+        self.lines_consumed
+    }
+
+    fn preamble(&self) -> &[String] {
+        &self.preamble
+    }
+
+    fn set_preamble(&mut self, preamble: Vec<String>) {
+        self.preamble = preamble;
+    }
+
+    fn header_paths(&self) -> Option<(&PathAndTimestamp, &PathAndTimestamp)> {
+        Some((&self.before, &self.after))
+    }
+
+    fn clump_lengths(&self) -> impl Iterator<Item = (usize, usize)> {
+        clump_lengths(self)
+    }
+
+    fn lines(&self, reverse: bool) -> Vec<String> {
+        clump_lines(self, reverse)
+    }
+
+    fn text_change_clumps(
+        &self,
+        _path: &Path,
+        _strip: usize,
+        _named: bool,
+    ) -> io::Result<Vec<TextChangeClump>> {
+        Ok(self.clumps.iter().map(TextChangeClump::from).collect())
+    }
+
+    fn is_creation(&self) -> bool {
+        match &self.git_header {
+            Some(git_header) => git_header.is_creation(),
+            None => self
+                .before
+                .is_absent(self.clump_lengths().all(|(removed, _)| removed == 0)),
+        }
+    }
+
+    fn is_removal(&self) -> bool {
+        match &self.git_header {
+            Some(git_header) => git_header.is_removal(),
+            None => self
+                .after
+                .is_absent(self.clump_lengths().all(|(_, inserted)| inserted == 0)),
+        }
+    }
+
+    /// Diffs with a git header may also be of binary files, symbolic links,
+    /// renames, copies and mode changes.
+    fn to_diff(&self, strip: usize, file_path: Option<&Path>) -> io::Result<Diff> {
+        match &self.git_header {
+            Some(git_header) => self.git_diff_to_diff(git_header, strip),
+            None => gnu_file_diff_to_diff(self, strip, file_path),
+        }
+    }
+}
+
+impl ApplyClumpsFuzzy<UnifiedDiffClump> for UnifiedFileDiff {
+    fn clumps<'s>(&'s self) -> impl Iterator<Item = &'s UnifiedDiffClump>
+    where
+        UnifiedDiffClump: 's,
+    {
+        self.clumps.iter()
+    }
+}
+
+/// A patch consisting of the unified diffs (git style or otherwise) of one
+/// or more files possibly with other text before, between and after them.
+pub struct UnifiedPatch {
+    pub file_diffs: Vec<UnifiedFileDiff>,
+}
+
+impl UnifiedPatch {
+    /// As `from_lines()` but tolerating damage (e.g. from hand editing or
+    /// email) as GNU `patch` does: clump headers' line counts are corrected
+    /// to match the clumps' lines, blank lines within clumps are taken to
+    /// be empty context lines and CRLF line endings are taken to be LF on
+    /// lines other than the clumps' lines (see `drop_stray_crs()` for
+    /// those).  What was tolerated is reported.
+    pub fn from_lines_leniently(lines: &Seq<String>) -> DiffParseResult<(Self, Vec<ParseWarning>)> {
+        let mut warnings = vec![];
+        let mut first_crlf = None;
+        let text: String = (0..lines.len())
+            .map(|index| {
+                let line = &lines[index];
+                match line.strip_suffix("\r\n") {
+                    Some(text)
+                        if !(is_clump_line(line) || is_blank(line))
+                            || starts_file_diff(lines, index)
+                            || is_after_header(lines, index) =>
+                    {
+                        first_crlf.get_or_insert(index);
+                        format!("{text}\n")
+                    }
+                    _ => line.clone(),
+                }
+            })
+            .collect();
+        if let Some(index) = first_crlf {
+            warnings.push(ParseWarning::new(
+                index,
+                "CRLF line endings taken to be LF outside clumps",
+            ));
+        }
+        let lines = Seq::<String>::from(text);
+        let file_diffs = file_diffs_from_lines(&lines, |lines, index| {
+            UnifiedFileDiff::get_any_from_at_with(lines, index, Some(&mut warnings))
+        })?;
+        Ok((Self { file_diffs }, warnings))
+    }
+
+    /// Drop the CRs from the CRLF line endings of the clumps' lines of the
+    /// diffs of files (once `strip` components are stripped from their
+    /// paths) that don't use them, as when a patch has been through email.
+    /// A file uses CRLF line endings if its first line ends with one.  What
+    /// was dropped is reported.
+    pub fn drop_stray_crs(&mut self, strip: usize) -> io::Result<Vec<ParseWarning>> {
+        let mut warnings = vec![];
+        let mut index = 0;
+        for file_diff in self.file_diffs.iter_mut() {
+            index += file_diff.preamble.len();
+            if file_diff.has_crs() {
+                let path = file_diff.target_path(strip, None)?;
+                let uses_crlf = read_lines(&strip_path(&path, strip)?)?
+                    .first()
+                    .is_some_and(|line| line.ends_with("\r\n"));
+                if !uses_crlf {
+                    file_diff.drop_crs();
+                    warnings.push(ParseWarning::new(
+                        index,
+                        format!("CRLF line endings taken to be LF as {path:?} doesn't use them"),
+                    ));
+                }
+            }
+            index += file_diff.lines_consumed;
+        }
+        Ok(warnings)
+    }
+}
+
+impl GnuPatch for UnifiedPatch {
+    type FileDiff = UnifiedFileDiff;
+
+    fn from_file_diffs(file_diffs: Vec<UnifiedFileDiff>) -> Self {
+        Self { file_diffs }
+    }
+
+    fn file_diffs(&self) -> &[UnifiedFileDiff] {
+        &self.file_diffs
+    }
+}
+
 pub struct UnifiedClumpText {
     pub header: String,
     pub lines: Vec<String>,
 }
 
+pub const NO_NEWLINE_AT_END: &str = "\\ No newline at end of file\n";
+
+/// Iterates over the clumps of `changes` rendered as unified diff clumps
+/// (with all deleted lines preceding the inserted lines that replace them).
 pub struct UnifiedClumpIter<'a> {
     pub before: &'a Seq<String>,
     pub after: &'a Seq<String>,
     pub iter: ChangeClumpIter<'a, String>,
 }
 
-impl<'a> Iterator for UnifiedClumpIter<'a> {
-    type Item = UnifiedClumpText;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let change_clump = self.iter.next()?;
+impl<'a> UnifiedClumpIter<'a> {
+    pub fn new(changes: &'a Changes<String>, context: u8) -> Self {
+        Self {
+            before: &changes.before,
+            after: &changes.after,
+            iter: changes.change_clumps(context),
+        }
+    }
 
-        let starts = change_clump.starts();
-        let ends = change_clump.ends();
-        let before_start_and_end = StartAndLength {
-            start: starts.0,
-            length: ends.0 - starts.0,
-        };
-        let after_start_and_end = StartAndLength {
-            start: starts.1,
-            length: ends.1 - starts.1,
-        };
+    /// The text of `change_clump` with the function line found by
+    /// `function_line_matcher` in its header and painted with `palette`
+    /// (if any).
+    fn clump_text(
+        &self,
+        change_clump: &ChangeClump<'a, String>,
+        function_line_matcher: Option<&FunctionLineMatcher>,
+        palette: Option<&Palette>,
+    ) -> UnifiedClumpText {
+        // Empty ranges start at the line before (as GNU does).
+        let file_start = |start: usize, length: usize| if length == 0 { start } else { start + 1 };
+        let (before_range, after_range) = change_clump.ranges();
         let starts_and_lengths = StartsAndLengths {
-            before: before_start_and_end,
-            after: after_start_and_end,
+            before: StartAndLength {
+                start: file_start(before_range.start(), before_range.len()),
+                length: before_range.len(),
+            },
+            after: StartAndLength {
+                start: file_start(after_range.start(), after_range.len()),
+                length: after_range.len(),
+            },
+        };
+        let mut header = match palette {
+            Some(palette) => palette.paint(&palette.clump_header, &starts_and_lengths.to_string()),
+            None => starts_and_lengths.to_string(),
         };
-        let header = format!("{starts_and_lengths}");
+        if let Some(heading) = function_line_matcher
+            .and_then(|matcher| matcher.heading(self.before, before_range.start()))
+        {
+            header = format!("{header} {heading}");
+        }
 
         let mut lines = vec![];
         for change in change_clump.iter() {
             use Change::*;
             match change {
-                NoChange(common_subsequence) => {
-                    for line in self.before.subsequence(common_subsequence.before_range()) {
-                        lines.push(format!(" {line}"));
-                    }
-                }
-                Delete(before_range, _) => {
-                    for line in self.before.subsequence(*before_range) {
-                        lines.push(format!("-{line}"));
-                    }
-                }
-                Insert(_, after_range) => {
-                    for line in self.after.subsequence(*after_range) {
-                        lines.push(format!("+{line}"));
-                    }
-                }
+                NoChange(common_subsequence) => push_lines(
+                    &mut lines,
+                    ' ',
+                    self.before.subsequence(common_subsequence.before_range()),
+                    &[],
+                    palette,
+                ),
+                Delete(before_range, _) => push_lines(
+                    &mut lines,
+                    '-',
+                    self.before.subsequence(*before_range),
+                    &[],
+                    palette,
+                ),
+                Insert(_, after_range) => push_lines(
+                    &mut lines,
+                    '+',
+                    self.after.subsequence(*after_range),
+                    &[],
+                    palette,
+                ),
                 Replace(before_range, after_range) => {
-                    if before_range.len() < after_range.len() {
-                        for line in self.before.subsequence(*before_range) {
-                            lines.push(format!("-{line}"));
-                        }
-                        for line in self.after.subsequence(*after_range) {
-                            lines.push(format!("+{line}"));
-                        }
+                    let (before_highlights, after_highlights) = if palette.is_some() {
+                        replaced_lines_highlights(
+                            &self.before.subsequence(*before_range).collect::<Vec<_>>(),
+                            &self.after.subsequence(*after_range).collect::<Vec<_>>(),
+                        )
                     } else {
-                        for line in self.after.subsequence(*after_range) {
-                            lines.push(format!("+{line}"));
-                        }
-                        for line in self.before.subsequence(*before_range) {
-                            lines.push(format!("-{line}"));
-                        }
-                    }
+                        (vec![], vec![])
+                    };
+                    push_lines(
+                        &mut lines,
+                        '-',
+                        self.before.subsequence(*before_range),
+                        &before_highlights,
+                        palette,
+                    );
+                    push_lines(
+                        &mut lines,
+                        '+',
+                        self.after.subsequence(*after_range),
+                        &after_highlights,
+                        palette,
+                    );
                 }
             }
         }
-        if !lines
-            .last()
-            .expect("impl Iterator for UnifiedClumpIter")
-            .ends_with("\n")
-        {
-            lines.push("\n\\\n".to_string());
+
+        UnifiedClumpText { header, lines }
+    }
+}
+
+/// Changed lines are painted (with the ranges in `highlights` highlighted)
+/// if there's a palette.
+fn push_lines<'b>(
+    lines: &mut Vec<String>,
+    prefix: char,
+    content: impl Iterator<Item = &'b String>,
+    highlights: &[Vec<Range>],
+    palette: Option<&Palette>,
+) {
+    for (i, line) in content.enumerate() {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        match palette {
+            Some(palette) if prefix != ' ' => {
+                let highlights = highlights.get(i).map_or(&[][..], Vec::as_slice);
+                let painted = palette
+                    .highlighter(prefix == '-', highlights)
+                    .paint(&prefix.to_string(), text);
+                lines.push(painted + "\n");
+            }
+            _ => lines.push(format!("{prefix}{text}\n")),
+        }
+        if !line.ends_with('\n') {
+            lines.push(NO_NEWLINE_AT_END.to_string());
+        }
+    }
+}
+
+impl<'a> Iterator for UnifiedClumpIter<'a> {
+    type Item = UnifiedClumpText;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let change_clump = self.iter.next()?;
+        Some(self.clump_text(&change_clump, None, None))
+    }
+}
+
+/// Iterates over the clumps of `changes` rendered as GNU `diff -u` does:
+/// clumps whose context would overlap or abut are merged and the headers
+/// show function lines found by `function_line_matcher` (if any).
+pub struct GnuUnifiedClumpIter<'a> {
+    clumps: UnifiedClumpIter<'a>,
+    /// A clump that couldn't be merged into the last one.
+    pending: Option<ChangeClump<'a, String>>,
+    function_line_matcher: Option<&'a FunctionLineMatcher>,
+    palette: Option<&'a Palette>,
+}
+
+impl<'a> GnuUnifiedClumpIter<'a> {
+    pub fn new(
+        changes: &'a Changes<String>,
+        context: u8,
+        function_line_matcher: Option<&'a FunctionLineMatcher>,
+        palette: Option<&'a Palette>,
+    ) -> Self {
+        Self {
+            clumps: UnifiedClumpIter::new(changes, context),
+            pending: None,
+            function_line_matcher,
+            palette,
         }
+    }
+}
+
+impl<'a> Iterator for GnuUnifiedClumpIter<'a> {
+    type Item = UnifiedClumpText;
 
-        Some(UnifiedClumpText { header, lines })
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut change_clump = self.pending.take().or_else(|| self.clumps.iter.next())?;
+        for next_clump in self.clumps.iter.by_ref() {
+            if next_clump.starts() == change_clump.ends() {
+                change_clump.extend(next_clump.changes);
+            } else {
+                self.pending = Some(next_clump);
+                break;
+            }
+        }
+        Some(
+            self.clumps
+                .clump_text(&change_clump, self.function_line_matcher, self.palette),
+        )
+    }
+}
+
+/// The differences between two files in GNU `diff -u` format.
+pub struct UnifiedDiff {
+    before: PathAndTimestamp,
+    after: PathAndTimestamp,
+    changes: Changes<String>,
+    context: u8,
+    function_line_matcher: Option<FunctionLineMatcher>,
+    palette: Option<Palette>,
+}
+
+impl UnifiedDiff {
+    pub fn from_lines(
+        before: PathAndTimestamp,
+        before_lines: Seq<String>,
+        after: PathAndTimestamp,
+        after_lines: Seq<String>,
+        context: u8,
+    ) -> Self {
+        Self {
+            before,
+            after,
+            changes: gnu_changes(before_lines, after_lines, context),
+            context,
+            function_line_matcher: None,
+            palette: None,
+        }
+    }
+}
+
+impl GnuTextDiff for UnifiedDiff {
+    const FORMAT_NAME: &'static str = "unified";
+    const DIFF_OPTIONS: &'static str = "-Nru";
+
+    fn new(before_file_path: &Path, after_file_path: &Path, context: u8) -> io::Result<Self> {
+        Ok(Self::from_lines(
+            PathAndTimestamp::of_file(before_file_path)?,
+            read_lines(before_file_path)?,
+            PathAndTimestamp::of_file(after_file_path)?,
+            read_lines(after_file_path)?,
+            context,
+        ))
+    }
+
+    fn has_changes(&self) -> bool {
+        has_changes(&self.changes)
+    }
+
+    fn set_function_line_matcher(&mut self, matcher: Option<FunctionLineMatcher>) {
+        self.function_line_matcher = matcher;
+    }
+
+    fn set_palette(&mut self, palette: Option<Palette>) {
+        self.palette = palette;
+    }
+
+    fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
+        if !self.has_changes() {
+            return Ok(());
+        }
+        match &self.palette {
+            Some(palette) => write!(
+                into,
+                "{}\n{}\n",
+                palette.paint(&palette.header, &format!("--- {}", self.before)),
+                palette.paint(&palette.header, &format!("+++ {}", self.after))
+            )?,
+            None => write!(into, "--- {}\n+++ {}\n", self.before, self.after)?,
+        }
+        let clumps = GnuUnifiedClumpIter::new(
+            &self.changes,
+            self.context,
+            self.function_line_matcher.as_ref(),
+            self.palette.as_ref(),
+        );
+        for clump in clumps {
+            writeln!(into, "{}", clump.header)?;
+            for line in clump.lines.iter() {
+                into.write_all(line.as_bytes())?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -332,7 +1134,15 @@ mod tests {
 
     use pw_diff_lib::sequence::*;
 
-    use crate::unified_diff::UnifiedDiffClump;
+    use pw_diff_lib::apply_text::ApplyClumpsFuzzy;
+    use pw_diff_lib::diff::Diff;
+
+    use std::path::Path;
+
+    use crate::colour::Palette;
+    use crate::function_line::FunctionLineMatcher;
+    use crate::text_diff::{GnuFileDiff, GnuPatch, GnuTextDiff, PathAndTimestamp};
+    use crate::unified_diff::{UnifiedDiff, UnifiedDiffClump, UnifiedPatch};
 
     static UNIFIED_DIFF_CLUMP: &str = "--- lao	2002-02-21 23:30:39.942229878 -0800
 +++ tzu	2002-02-21 23:30:50.442260588 -0800
@@ -380,4 +1190,426 @@ mod tests {
         let result = result.unwrap();
         assert!(result.is_none());
     }
+
+    static LAO: &str = "The Way that can be told of is not the eternal Way;
+The name that can be named is not the eternal name.
+The Nameless is the origin of Heaven and Earth;
+The Named is the mother of all things.
+Therefore let there always be non-being,
+  so we may see their subtlety,
+And let there always be being,
+  so we may see their outcome.
+The two are the same,
+But after they are produced,
+  they have different names.
+";
+
+    static TZU: &str = "The Nameless is the origin of Heaven and Earth;
+The named is the mother of all things.
+
+Therefore let there always be non-being,
+  so we may see their subtlety,
+And let there always be being,
+  so we may see their outcome.
+The two are the same,
+But after they are produced,
+  they have different names.
+They both may be called deep and profound.
+Deeper and more profound,
+The door of all subtleties!
+";
+
+    fn unified_diff_text(before: &str, after: &str, context: u8) -> String {
+        let path_and_time_stamp = |file_path: &str, time_stamp: &str| PathAndTimestamp {
+            file_path: file_path.to_string(),
+            time_stamp: Some(time_stamp.to_string()),
+        };
+        let unified_diff = UnifiedDiff::from_lines(
+            path_and_time_stamp("lao", "2002-02-21 23:30:39.942229878 -0800"),
+            Seq::<String>::from(before),
+            path_and_time_stamp("tzu", "2002-02-21 23:30:50.442260588 -0800"),
+            Seq::<String>::from(after),
+            context,
+        );
+        let mut text = vec![];
+        unified_diff.write_into(&mut text).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn unified_diff_matches_gnu_diff() {
+        assert_eq!(unified_diff_text(LAO, TZU, 3), UNIFIED_DIFF_CLUMP);
+        assert_eq!(unified_diff_text(LAO, LAO, 3), "");
+        // Abutting clumps are merged, the later of two identical lines is
+        // deleted and missing final newlines are noted.
+        let expected = "--- lao\t2002-02-21 23:30:39.942229878 -0800
++++ tzu\t2002-02-21 23:30:50.442260588 -0800
+@@ -1,11 +1,10 @@
+ a
+ b
+-b
+ c
+ d
+-e
++E
+ f
+ g
+ h
+ i
+-j
+\\ No newline at end of file
++J
+\\ No newline at end of file
+";
+        assert_eq!(
+            unified_diff_text(
+                "a\nb\nb\nc\nd\ne\nf\ng\nh\ni\nj",
+                "a\nb\nc\nd\nE\nf\ng\nh\ni\nJ",
+                2
+            ),
+            expected
+        );
+        let expected = "--- lao\t2002-02-21 23:30:39.942229878 -0800
++++ tzu\t2002-02-21 23:30:50.442260588 -0800
+@@ -0,0 +1,2 @@
++new
++file
+\\ No newline at end of file
+";
+        assert_eq!(unified_diff_text("", "new\nfile", 3), expected);
+        // The lines that differ are those GNU `diff` picks.
+        let expected = "--- lao\t2002-02-21 23:30:39.942229878 -0800
++++ tzu\t2002-02-21 23:30:50.442260588 -0800
+@@ -1,8 +1,8 @@
+-e
++a
+ c
+-b
+ d
+-a
++e
++b
+ c
+ d
+ c
+";
+        assert_eq!(
+            unified_diff_text("e\nc\nb\nd\na\nc\nd\nc\n", "a\nc\nd\ne\nb\nc\nd\nc\n", 3),
+            expected
+        );
+    }
+
+    #[test]
+    fn unified_clump_iters() {
+        use pw_diff_lib::changes::Changes;
+
+        use crate::unified_diff::{GnuUnifiedClumpIter, UnifiedClumpIter};
+
+        let changes =
+            Changes::<String>::new(Seq::from("a\nb\nc\nd\ne\n"), Seq::from("A\nb\nc\nD\ne\n"));
+        let clumps = UnifiedClumpIter {
+            before: &changes.before,
+            after: &changes.after,
+            iter: changes.change_clumps(1),
+        };
+        let headers: Vec<String> = clumps.map(|clump| clump.header).collect();
+        assert_eq!(headers, vec!["@@ -1,2 +1,2 @@", "@@ -3,3 +3,3 @@"]);
+        let clumps = GnuUnifiedClumpIter::new(&changes, 1, None, None);
+        let headers: Vec<String> = clumps.map(|clump| clump.header).collect();
+        assert_eq!(headers, vec!["@@ -1,5 +1,5 @@"]);
+    }
+
+    #[test]
+    fn unified_patch_parse_and_apply() {
+        let patch_text = format!(
+            "Some explanatory text.
+diff -Nru a/new b/new
+--- a/new\t1970-01-01 00:00:00.000000000 +0000
++++ b/new\t2024-05-01 10:00:00.000000000 +1000
+@@ -0,0 +1,2 @@
++new
++file
+\\ No newline at end of file
+{UNIFIED_DIFF_CLUMP}diff --git a/gone b/gone
+deleted file mode 100644
+--- a/gone
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+-- 
+A signature
+"
+        );
+        let patch = UnifiedPatch::from_lines(&Seq::<String>::from(patch_text)).unwrap();
+        assert_eq!(patch.file_diffs.len(), 3);
+        assert_eq!(
+            patch.file_diffs[0].preamble,
+            vec!["Some explanatory text.\n", "diff -Nru a/new b/new\n"]
+        );
+        assert!(patch.file_diffs[0].is_creation() && !patch.file_diffs[0].is_removal());
+        assert!(!patch.file_diffs[1].is_creation() && !patch.file_diffs[1].is_removal());
+        assert!(patch.file_diffs[2].is_removal());
+        assert_eq!(
+            patch.file_diffs[1].target_path(0, None).unwrap(),
+            std::path::Path::new("lao")
+        );
+
+        let lao_tzu = &patch.file_diffs[1];
+        let mut patched = vec![];
+        let report = lao_tzu
+            .apply_into_reporting(&Seq::<String>::from(LAO), &mut patched, false)
+            .unwrap();
+        assert!(report.succeeded());
+        assert_eq!(String::from_utf8(patched).unwrap(), TZU);
+        let mut unpatched = vec![];
+        lao_tzu
+            .apply_into(&Seq::<String>::from(TZU), &mut unpatched, true)
+            .unwrap();
+        assert_eq!(String::from_utf8(unpatched).unwrap(), LAO);
+
+        let diff_set = patch.to_diff_set(1, None).unwrap();
+        let diffs: Vec<&Diff> = diff_set.diffs().collect();
+        assert!(matches!(diffs[0], Diff::TextAdd(_)));
+        assert!(matches!(diffs[1], Diff::TextChange(_)));
+        assert!(matches!(diffs[2], Diff::TextRemove(_)));
+    }
+
+    #[test]
+    fn clump_headings() {
+        let before = "fn a() {\n    1\n    2\n    3\n    4\n}\n";
+        let after = "fn a() {\n    1\n    2\n    3\n    four\n}\n";
+        let mut unified_diff = UnifiedDiff::from_lines(
+            PathAndTimestamp {
+                file_path: "a/x.rs".to_string(),
+                time_stamp: None,
+            },
+            Seq::<String>::from(before),
+            PathAndTimestamp {
+                file_path: "b/x.rs".to_string(),
+                time_stamp: None,
+            },
+            Seq::<String>::from(after),
+            1,
+        );
+        unified_diff
+            .set_function_line_matcher(Some(FunctionLineMatcher::for_path(Path::new("x.rs"))));
+        let mut text = vec![];
+        unified_diff.write_into(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("\n@@ -4,3 +4,3 @@ fn a() {\n"));
+
+        let patch = UnifiedPatch::from_lines(&Seq::<String>::from(text)).unwrap();
+        let clump = &patch.file_diffs[0].clumps[0];
+        assert_eq!(clump.heading.as_deref(), Some("fn a() {"));
+        let report = patch.file_diffs[0]
+            .apply_into_reporting(&Seq::<String>::from(before), &mut std::io::sink(), false)
+            .unwrap();
+        assert_eq!(report.clump_label(1), "Clump #1 (fn a() {)");
+    }
+
+    #[test]
+    fn coloured_unified_diff() {
+        let mut unified_diff = UnifiedDiff::from_lines(
+            PathAndTimestamp {
+                file_path: "a".to_string(),
+                time_stamp: None,
+            },
+            Seq::<String>::from("same\nlet x = 1;\n"),
+            PathAndTimestamp {
+                file_path: "b".to_string(),
+                time_stamp: None,
+            },
+            Seq::<String>::from("same\nlet y = 1;\nnew"),
+            1,
+        );
+        unified_diff.set_palette(Some(Palette::default()));
+        let mut text = vec![];
+        unified_diff.write_into(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "\x1b[1m--- a\x1b[0m
+\x1b[1m+++ b\x1b[0m
+\x1b[36m@@ -1,2 +1,3 @@\x1b[0m
+ same
+\x1b[31m-let \x1b[7mx\x1b[0m\x1b[31m = 1;\x1b[0m
+\x1b[32m+let \x1b[7my\x1b[0m\x1b[32m = 1;\x1b[0m
+\x1b[32m+new\x1b[0m
+\\ No newline at end of file
+"
+        );
+    }
+
+    #[test]
+    fn lenient_unified_patch_parse() {
+        let before = "one\ntwo\n\nthree\nfour\n";
+        let after = "one\ntwo\n\nthree\nFOUR\nfive\n";
+        // A hand edited diff: wrong counts and a blank context line's space lost.
+        let patch_text = "--- a/f\n+++ b/f\n@@ -2,3 +2,9 @@\n two\n\n three\n-four\n+FOUR\n+five\n";
+        let lines = Seq::<String>::from(patch_text);
+        assert!(UnifiedPatch::from_lines(&lines).is_err());
+        let (patch, warnings) = UnifiedPatch::from_lines_leniently(&lines).unwrap();
+        assert_eq!(
+            warnings.iter().map(|w| w.line_index).collect::<Vec<_>>(),
+            vec![4, 2]
+        );
+        assert_eq!(
+            patch.file_diffs[0].clumps[0].starts_and_lengths.to_string(),
+            "@@ -2,4 +2,5 @@"
+        );
+        let mut patched = vec![];
+        patch.file_diffs[0]
+            .apply_into(&Seq::<String>::from(before), &mut patched, false)
+            .unwrap();
+        assert_eq!(String::from_utf8(patched).unwrap(), after);
+
+        // Too few lines followed by the next file's diff.
+        let patch_text = "--- a/f\r\n+++ b/f\r\n@@ -1,3 +1,3 @@\r\n-a\r\n+b\r\n--- a/g\r\n+++ b/g\r\n@@ -1 +1 @@\r\n-c\r\n+d\r\n";
+        let (patch, warnings) =
+            UnifiedPatch::from_lines_leniently(&Seq::<String>::from(patch_text)).unwrap();
+        assert_eq!(patch.file_diffs.len(), 2);
+        assert_eq!(
+            warnings.iter().map(|w| w.line_index).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert_eq!(patch.file_diffs[1].clumps[0].after_lines[0], "d\r\n");
+
+        // Too many lines up to the next clump.
+        let patch_text = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n c\n@@ -5 +5 @@\n-e\n+E\n";
+        let (patch, warnings) =
+            UnifiedPatch::from_lines_leniently(&Seq::<String>::from(patch_text)).unwrap();
+        assert_eq!(
+            warnings.iter().map(|w| w.line_index).collect::<Vec<_>>(),
+            vec![6, 2]
+        );
+        assert_eq!(
+            patch.file_diffs[0].clumps[0].starts_and_lengths.to_string(),
+            "@@ -1,3 +1,3 @@"
+        );
+        let mut patched = vec![];
+        patch.file_diffs[0]
+            .apply_into(&Seq::<String>::from("a\nb\nc\nd\ne\n"), &mut patched, false)
+            .unwrap();
+        assert_eq!(String::from_utf8(patched).unwrap(), "a\nB\nc\nd\nE\n");
+
+        // A valid clump followed by an email signature.
+        let patch_text = "--- a/f\n+++ b/f\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n-- \n2.43.0\n";
+        let (patch, warnings) =
+            UnifiedPatch::from_lines_leniently(&Seq::<String>::from(patch_text)).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            patch.file_diffs[0].clumps[0].starts_and_lengths.to_string(),
+            "@@ -1,3 +1,3 @@"
+        );
+        let mut patched = vec![];
+        patch.file_diffs[0]
+            .apply_into(&Seq::<String>::from("a\nb\nc\n"), &mut patched, false)
+            .unwrap();
+        assert_eq!(String::from_utf8(patched).unwrap(), "a\nB\nc\n");
+    }
+
+    #[test]
+    fn lenient_unified_patch_crs() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let crlf_path = temp_dir.path().join("crlf");
+        let lf_path = temp_dir.path().join("lf");
+        std::fs::write(&crlf_path, "a\r\nb\r\n").unwrap();
+        std::fs::write(&lf_path, "a\nb\n").unwrap();
+        // Only some of the lines have been given CRs.
+        let patch_text = format!(
+            "--- {0}\r\n+++ {0}\r\n@@ -1,2 +1,2 @@\r\n a\r\n-b\r\n+B\r\n--- {1}\n+++ {1}\r\n@@ -1,2 +1,2 @@\n a\r\n-b\n+B\r\n",
+            crlf_path.display(),
+            lf_path.display()
+        );
+        let (mut patch, warnings) =
+            UnifiedPatch::from_lines_leniently(&Seq::<String>::from(patch_text)).unwrap();
+        assert_eq!(
+            warnings.iter().map(|w| w.line_index).collect::<Vec<_>>(),
+            vec![0]
+        );
+        let warnings = patch.drop_stray_crs(0).unwrap();
+        assert_eq!(
+            warnings.iter().map(|w| w.line_index).collect::<Vec<_>>(),
+            vec![6]
+        );
+        let expected = [(&crlf_path, "a\r\nB\r\n"), (&lf_path, "a\nB\n")];
+        for (file_diff, (path, after)) in patch.file_diffs.iter().zip(expected) {
+            let before = Seq::<String>::read(File::open(path).unwrap()).unwrap();
+            let mut patched = vec![];
+            file_diff.apply_into(&before, &mut patched, false).unwrap();
+            assert_eq!(String::from_utf8(patched).unwrap(), after);
+        }
+    }
+
+    #[test]
+    fn git_patch_to_diffs() {
+        let patch_text = "diff --git a/old b/new
+similarity index 95%
+rename from old
+rename to new
+index 1234567..89abcde 100644
+--- a/old
++++ b/new
+@@ -1 +1 @@
+-one
++two
+diff --git a/script b/script
+old mode 100644
+new mode 100755
+diff --git a/tool b/tool
+new file mode 100755
+index 0000000..e69de29
+--- /dev/null
++++ b/tool
+@@ -0,0 +1 @@
++run
+diff --git a/link b/link
+new file mode 120000
+index 0000000..e69de29
+--- /dev/null
++++ b/link
+@@ -0,0 +1 @@
++target
+\\ No newline at end of file
+diff --git a/copied b/copy
+similarity index 100%
+copy from copied
+copy to copy
+diff --git a/h b/h
+new file mode 100644
+index 0000000000000000000000000000000000000000..ba18e3dcc474e720bdd955f81c8848324c862840
+GIT binary patch
+literal 6
+Ncmc~u&B@7U000O<0u=xN
+
+literal 0
+HcmV?d00001
+
+diff --git a/image.png b/image.png
+index 1234567..89abcde 100644
+Binary files a/image.png and b/image.png differ
+";
+        let patch = UnifiedPatch::from_lines(&Seq::<String>::from(patch_text)).unwrap();
+        assert_eq!(patch.file_diffs.len(), 7);
+        assert!(patch.to_diff_set(1, None).is_err());
+
+        let diffs: Vec<Diff> = patch.file_diffs[..6]
+            .iter()
+            .map(|file_diff| file_diff.to_diff(1, None).unwrap())
+            .collect();
+        match &diffs[0] {
+            Diff::TextRename(relocation) => assert_eq!(relocation.similarity(), 95),
+            _ => panic!("expected a rename"),
+        }
+        assert!(matches!(diffs[1], Diff::ModeChange(_)));
+        match &diffs[2] {
+            Diff::TextAdd(path_and_lines) => assert_eq!(path_and_lines.mode(), Some(0o755)),
+            _ => panic!("expected an addition"),
+        }
+        assert!(matches!(diffs[3], Diff::SymlinkAdd(_)));
+        assert!(matches!(diffs[4], Diff::TextCopy(_)));
+        match &diffs[5] {
+            Diff::ByteAdd(path_and_bytes) => assert_eq!(path_and_bytes.bytes(), b"hello\0"),
+            _ => panic!("expected a binary addition"),
+        }
+    }
 }
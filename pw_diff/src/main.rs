@@ -1,14 +1,49 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
-use log;
-use stderrlog;
+use clap::{Parser, ValueEnum};
 use stderrlog::LogLevelNum;
 
+use pw_cub_diff_lib::colour::Palette;
+use pw_cub_diff_lib::context_diff::ContextDiff;
+use pw_cub_diff_lib::ed_diff::EdDiff;
+use pw_cub_diff_lib::function_line::{FunctionLineMatcher, FunctionLines};
+use pw_cub_diff_lib::html_diff::{write_html_diff, write_html_diff_set};
+use pw_cub_diff_lib::normal_diff::NormalDiff;
+use pw_cub_diff_lib::side_by_side::{SideBySideDiff, SideBySideOptions};
+use pw_cub_diff_lib::text_diff::{write_diff_set_with, write_diff_with, GnuTextDiff};
+use pw_cub_diff_lib::unified_diff::UnifiedDiff;
 use pw_diff_lib::diff::Diff;
+use pw_diff_lib::diff_set::DiffSet;
+use pw_diff_lib::dir_diff::{PathFilter, RenameDetection};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// The pw_patch (JSON) format
+    Json,
+    /// The GNU `diff -u` format (`diff -Nru` for directories)
+    Unified,
+    /// The GNU `diff -c` format (`diff -Nrc` for directories)
+    Context,
+    /// The GNU `diff` normal format (`diff -Nr` for directories)
+    Normal,
+    /// The GNU `diff -e` ed script format (`diff -Nre` for directories)
+    Ed,
+    /// The GNU `diff -y` two column format (`diff -Nry` for directories)
+    SideBySide,
+    /// A self contained HTML page
+    Html,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum When {
+    Always,
+    Never,
+    /// If the output is a terminal (and NO_COLOR isn't set)
+    Auto,
+}
 
 #[derive(Parser)]
 struct Cli {
@@ -17,16 +52,126 @@ struct Cli {
     #[arg(
         short,
         long,
-        help = "Number of lines of context to use",
-        default_value = "2"
+        help = "Number of lines of context to use [default: 2, or 3 for unified and context diffs]"
     )]
-    context: u8,
+    context: Option<u8>,
+    #[arg(long, value_enum, default_value = "json", help = "Output format")]
+    format: Format,
+    #[arg(
+        short = 'p',
+        long,
+        help = "Show the function containing each change in unified and context diffs (found by the file's language)"
+    )]
+    show_function_line: bool,
+    #[arg(
+        short = 'F',
+        long,
+        value_name = "RE",
+        help = "Show the most recent line matching RE before each change in unified and context diffs"
+    )]
+    function_line: Option<String>,
+    #[arg(
+        short = 'W',
+        long,
+        value_name = "NUM",
+        default_value_t = 130,
+        help = "Output at most NUM columns in side by side diffs"
+    )]
+    width: usize,
+    #[arg(
+        short = 't',
+        long,
+        help = "Expand tabs to spaces in side by side diffs"
+    )]
+    expand_tabs: bool,
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u8).range(1..),
+        help = "Tab stops are every NUM columns in side by side diffs"
+    )]
+    tab_size: u8,
+    #[arg(
+        long,
+        help = "Don't show lines that are the same in side by side diffs"
+    )]
+    suppress_common_lines: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        default_value = "auto",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "always",
+        help = "Colour unified and side by side diffs"
+    )]
+    color: When,
+    #[arg(
+        long,
+        value_name = "PALETTE",
+        help = "Colours (SGR parameters) to use, e.g. \"ad=32:de=31:hd=1:ln=36:ah=7:dh=7\""
+    )]
+    palette: Option<Palette>,
+    #[arg(
+        long,
+        help = "Write changes to binary files in unified and context diffs as git binary patches"
+    )]
+    binary: bool,
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "When comparing directories, only compare files matching GLOB"
+    )]
+    include: Vec<String>,
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "When comparing directories, skip files and directories matching GLOB"
+    )]
+    exclude: Vec<String>,
+    #[arg(
+        long,
+        help = "When comparing directories, don't honour .gitignore and .ignore files"
+    )]
+    no_ignore_files: bool,
+    #[arg(
+        short = 'M',
+        long,
+        value_name = "PERCENT",
+        num_args = 0..=1,
+        default_missing_value = "50",
+        help = "When comparing directories, detect renamed files at least PERCENT similar"
+    )]
+    find_renames: Option<u8>,
+    #[arg(
+        short = 'C',
+        long,
+        help = "When comparing directories, also detect copied files (implies --find-renames)"
+    )]
+    find_copies: bool,
     #[arg(required = true)]
     before_file_path: PathBuf,
     #[arg(required = true)]
     after_file_path: PathBuf,
 }
 
+/// Makes text diffs (for `write_diff_with()` and `write_diff_set_with()`)
+/// showing the given function lines in the given colours.
+fn text_diff_maker<'a, D: GnuTextDiff>(
+    context: u8,
+    function_lines: &'a FunctionLines,
+    palette: &'a Option<Palette>,
+) -> impl Fn(&Path, &Path) -> io::Result<D> + 'a {
+    move |before_file_path, after_file_path| {
+        let mut text_diff = D::new(before_file_path, after_file_path, context)?;
+        text_diff.set_function_line_matcher(function_lines.matcher_for(before_file_path));
+        text_diff.set_palette(palette.clone());
+        Ok(text_diff)
+    }
+}
+
 fn main() {
     let args = Cli::parse();
 
@@ -36,15 +181,193 @@ fn main() {
         .init()
         .unwrap();
 
-    let diff = match Diff::new(&args.before_file_path, &args.after_file_path, args.context) {
-        Ok(diff) => diff,
-        Err(err) => {
-            log::error!("Error: {err}");
-            std::process::exit(1);
+    let context = args.context.unwrap_or(match args.format {
+        Format::Json => 2,
+        Format::Unified
+        | Format::Context
+        | Format::Normal
+        | Format::Ed
+        | Format::SideBySide
+        | Format::Html => 3,
+    });
+    let function_lines = match &args.function_line {
+        Some(pattern) => match FunctionLineMatcher::new(pattern) {
+            Ok(matcher) => FunctionLines::Matching(matcher),
+            Err(err) => {
+                log::error!("Error: {err}");
+                std::process::exit(2);
+            }
+        },
+        None if args.show_function_line => FunctionLines::ByLanguage,
+        None => FunctionLines::None,
+    };
+    let side_by_side_options = SideBySideOptions {
+        width: args.width,
+        expand_tabs: args.expand_tabs,
+        tab_size: args.tab_size as usize,
+        suppress_common_lines: args.suppress_common_lines,
+    };
+    let colour = match args.color {
+        When::Always => true,
+        When::Never => false,
+        When::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    };
+    let palette = colour.then(|| args.palette.clone().unwrap_or_default());
+    let new_side_by_side_diff = |before_file_path: &Path, after_file_path: &Path| {
+        let mut side_by_side_diff: SideBySideDiff =
+            text_diff_maker(context, &function_lines, &palette)(before_file_path, after_file_path)?;
+        side_by_side_diff.set_options(side_by_side_options);
+        Ok(side_by_side_diff)
+    };
+    let result = if args.before_file_path.is_dir() && args.after_file_path.is_dir() {
+        let filter = match PathFilter::new(&args.include, &args.exclude, !args.no_ignore_files) {
+            Ok(filter) => filter,
+            Err(err) => {
+                log::error!("Error: {err}");
+                std::process::exit(2);
+            }
+        };
+        let renames = if args.find_renames.is_some() || args.find_copies {
+            if args.format != Format::Json {
+                log::error!("Error: renames and copies can only be shown in the JSON format");
+                std::process::exit(2);
+            }
+            Some(RenameDetection {
+                threshold: args.find_renames.unwrap_or(50),
+                find_copies: args.find_copies,
+            })
+        } else {
+            None
+        };
+        let diff_set = match DiffSet::from_dirs(
+            &args.before_file_path,
+            &args.after_file_path,
+            context,
+            &filter,
+            renames,
+        ) {
+            Ok(diff_set) => diff_set,
+            Err(err) => {
+                log::error!("Error: {err}");
+                std::process::exit(1);
+            }
+        };
+        match args.format {
+            Format::Json => diff_set
+                .to_writer(&mut io::stdout())
+                .map_err(io::Error::from),
+            Format::Unified => write_diff_set_with(
+                &diff_set,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<UnifiedDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Context => write_diff_set_with(
+                &diff_set,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<ContextDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Normal => write_diff_set_with(
+                &diff_set,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<NormalDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Ed => write_diff_set_with(
+                &diff_set,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<EdDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::SideBySide => write_diff_set_with(
+                &diff_set,
+                &args.before_file_path,
+                &args.after_file_path,
+                new_side_by_side_diff,
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Html => write_html_diff_set(
+                &diff_set,
+                &args.before_file_path,
+                &args.after_file_path,
+                context,
+                &mut io::stdout(),
+            ),
+        }
+    } else if args.before_file_path.is_dir() || args.after_file_path.is_dir() {
+        log::error!("Error: can't compare a file with a directory");
+        std::process::exit(2);
+    } else {
+        let diff = match Diff::new(&args.before_file_path, &args.after_file_path, context) {
+            Ok(diff) => diff,
+            Err(err) => {
+                log::error!("Error: {err}");
+                std::process::exit(1);
+            }
+        };
+        match args.format {
+            Format::Json => diff.to_writer(&mut io::stdout()).map_err(io::Error::from),
+            Format::Unified => write_diff_with(
+                &diff,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<UnifiedDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Context => write_diff_with(
+                &diff,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<ContextDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Normal => write_diff_with(
+                &diff,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<NormalDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Ed => write_diff_with(
+                &diff,
+                &args.before_file_path,
+                &args.after_file_path,
+                text_diff_maker::<EdDiff>(context, &function_lines, &palette),
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::SideBySide => write_diff_with(
+                &diff,
+                &args.before_file_path,
+                &args.after_file_path,
+                new_side_by_side_diff,
+                args.binary,
+                &mut io::stdout(),
+            ),
+            Format::Html => write_html_diff(
+                &diff,
+                &args.before_file_path,
+                &args.after_file_path,
+                context,
+                &mut io::stdout(),
+            ),
         }
     };
 
-    match diff.to_writer(&mut io::stdout()) {
+    match result {
         Ok(_) => (),
         Err(err) => {
             log::error!("Error writing diff: {err}");
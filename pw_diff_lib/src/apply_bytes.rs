@@ -4,23 +4,30 @@ use std::io;
 
 use log;
 
+use crate::apply_clump::ApplyClump;
+use crate::apply_text::{ApplyReport, ClumpOutcome, Statistics};
 use crate::sequence::{ConsumableSeq, ConsumableSeqIfce, Seq};
 
-pub trait ApplyClumpClean {
-    fn will_apply(&self, se: &Seq<u8>, reverse: bool) -> bool;
-    fn is_already_applied(&self, se: &Seq<u8>, reverse: bool) -> bool;
+/// Application of byte clumps.  Like their text counterparts, byte clumps will
+/// be applied at an offset from their recorded position and/or with reduced
+/// context (as found by `ApplyClump`) if they don't apply cleanly.
+pub trait ApplyClumpClean: ApplyClump<u8> {
     fn apply_into<W: io::Write>(
         &self,
-        pd: &mut ConsumableSeq<u8>,
         into: &mut W,
-        reverse: bool,
-    ) -> io::Result<()>;
-    fn already_applied_into<W: io::Write>(
-        &self,
         pd: &mut ConsumableSeq<u8>,
-        into: &mut W,
+        offset: isize,
+        reductions: Option<(u8, u8)>,
         reverse: bool,
-    ) -> io::Result<()>;
+    ) -> io::Result<()> {
+        let end = self.before_adjusted_start(offset, reductions, reverse) as usize;
+        pd.write_into_upto(into, end)?;
+        let range = self.my_after_range(reductions, reverse);
+        let bytes: Vec<u8> = self.after_items(range, reverse).copied().collect();
+        into.write_all(&bytes)?;
+        pd.advance_consumed_by(self.before_adjusted_length(reductions, reverse));
+        Ok(())
+    }
 }
 
 pub trait ApplyClumpsClean<'a, C>
@@ -31,37 +38,100 @@ where
     where
         C: 'b;
 
+    /// Apply the clumps to `patchable` writing the result into `into`.
+    ///
+    /// Unlike text, binary data has no sensible conflict representation, so
+    /// an error is returned if any clump can neither be applied nor found to
+    /// have been already applied (in which case the contents of `into` should
+    /// be discarded). Use `apply_into_reporting()` to apply regardless.
     fn apply_into<W: io::Write>(
         &self,
         patchable: &'a Seq<u8>,
         into: &mut W,
         reverse: bool,
-    ) -> io::Result<()> {
+    ) -> io::Result<Statistics> {
+        let stats = self
+            .apply_into_reporting(patchable, into, reverse)?
+            .statistics();
+        if stats.failed > 0 {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} clump(s) could NOT be applied", stats.failed),
+            ))
+        } else {
+            Ok(stats)
+        }
+    }
+
+    fn apply_into_reporting<W: io::Write>(
+        &self,
+        patchable: &'a Seq<u8>,
+        into: &mut W,
+        reverse: bool,
+    ) -> io::Result<ApplyReport> {
         let mut pd = ConsumableSeq::<u8>::new(patchable);
-        let mut iter = self.clumps();
-        let mut clump_num = 0;
+        let mut report = ApplyReport::default();
+        let mut iter = self.clumps().peekable();
+        let mut offset: isize = 0;
         while let Some(clump) = iter.next() {
-            clump_num += 1; // for human consumption
-            if clump.will_apply(patchable, reverse) {
-                clump.apply_into(&mut pd, into, reverse)?;
-                log::info!("Clump #{clump_num} applies cleanly.");
-            } else if clump.is_already_applied(patchable, reverse) {
-                clump.already_applied_into(&mut pd, into, reverse)?;
-                log::warn!("Clump #{clump_num} already applied");
+            let outcome = if let Some(will_apply) =
+                clump.will_apply(patchable, offset, &u8::eq, reverse)
+            {
+                let reductions = will_apply.reductions();
+                clump.apply_into(into, &mut pd, offset, reductions, reverse)?;
+                ClumpOutcome::Applied(0, reductions)
+            } else if let Some((offset_adj, will_apply)) =
+                clump.will_apply_nearby(&pd, iter.peek().copied(), offset, &u8::eq, reverse)
+            {
+                offset += offset_adj;
+                let reductions = will_apply.reductions();
+                clump.apply_into(into, &mut pd, offset, reductions, reverse)?;
+                ClumpOutcome::Applied(offset_adj, reductions)
+            } else if let Some(applied) =
+                clump.is_already_applied(patchable, offset, &u8::eq, reverse)
+            {
+                let reductions = applied.reductions();
+                clump.already_applied_into(into, &mut pd, offset, reductions, reverse)?;
+                ClumpOutcome::AlreadyApplied(0, reductions)
+            } else if let Some((offset_adj, applied)) =
+                clump.is_already_applied_nearby(&pd, iter.peek().copied(), offset, &u8::eq, reverse)
+            {
+                offset += offset_adj;
+                let reductions = applied.reductions();
+                clump.already_applied_into(into, &mut pd, offset, reductions, reverse)?;
+                ClumpOutcome::AlreadyApplied(offset_adj, reductions)
             } else {
-                log::error!("Clump #{clump_num} could NOT be applied!");
-            }
+                ClumpOutcome::Failed
+            };
+            report.push(outcome);
         }
-        pd.write_remainder(into)
+        pd.write_remainder(into)?;
+        Ok(report)
+    }
+
+    /// Report what would happen if this diff were applied to `patchable`
+    /// without writing anything.
+    fn will_apply(&self, patchable: &'a Seq<u8>, reverse: bool) -> ApplyReport {
+        self.apply_into_reporting(patchable, &mut io::sink(), reverse)
+            .expect("writing to a sink cannot fail")
     }
 
     fn already_applied(&self, patchable: &Seq<u8>, reverse: bool) -> bool {
-        let mut clump_num = 0;
+        let pd = ConsumableSeq::<u8>::new(patchable);
         let mut iter = self.clumps().peekable();
+        let mut clump_num = 0;
+        let mut offset: isize = 0;
         while let Some(clump) = iter.next() {
             clump_num += 1; // for human consumption
-            if clump.is_already_applied(patchable, reverse) {
-                log::info!("Clump #{clump_num} already applied")
+            if let Some(applied) = clump.is_already_applied(patchable, offset, &u8::eq, reverse) {
+                let outcome = ClumpOutcome::AlreadyApplied(0, applied.reductions());
+                log::info!("Clump #{clump_num} {outcome}")
+            } else if let Some((offset_adj, applied)) =
+                clump.is_already_applied_nearby(&pd, iter.peek().copied(), offset, &u8::eq, reverse)
+            {
+                offset += offset_adj;
+                let outcome = ClumpOutcome::AlreadyApplied(offset_adj, applied.reductions());
+                log::warn!("Clump #{clump_num} {outcome}")
             } else {
                 log::error!("Clump #{clump_num} NOT already applied!");
                 return false;
@@ -70,3 +140,6 @@ where
         true
     }
 }
+
+#[cfg(test)]
+mod apply_tests;
@@ -0,0 +1,144 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use crate::apply_bytes::*;
+use crate::apply_text::ClumpOutcome;
+use crate::byte_diff::*;
+use crate::changes::*;
+use crate::sequence::*;
+
+struct WrappedByteClumps(pub Vec<ByteChangeClump>);
+
+impl ApplyClumpsClean<'_, ByteChangeClump> for WrappedByteClumps {
+    fn clumps<'b>(&'b self) -> impl Iterator<Item = &'b ByteChangeClump>
+    where
+        ByteChangeClump: 'b,
+    {
+        self.0.iter()
+    }
+}
+
+fn wrapped_clumps(before: &[u8], after: &[u8], context: u8) -> WrappedByteClumps {
+    let changes = Changes::<u8>::new(Seq::from(before), Seq::from(after));
+    WrappedByteClumps(
+        changes
+            .change_clumps(context)
+            .map(ByteChangeClump::from)
+            .collect(),
+    )
+}
+
+#[test]
+fn clean_patch() {
+    let before = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let after = b"abcdeFghijklmnopqrstUvwxyz0123456789";
+    let patch = wrapped_clumps(before, after, 3);
+    let mut patched = vec![];
+    let stats = patch
+        .apply_into(&Seq::from(&before[..]), &mut patched, false)
+        .unwrap();
+    assert_eq!(stats.clean, 2);
+    assert_eq!(stats.already_applied, 0);
+    assert_eq!(stats.failed, 0);
+    assert_eq!(patched, after.to_vec());
+
+    let mut patched = vec![];
+    let stats = patch
+        .apply_into(&Seq::from(&after[..]), &mut patched, true)
+        .unwrap();
+    assert_eq!(stats.clean, 2);
+    assert_eq!(patched, before.to_vec());
+}
+
+#[test]
+fn already_applied() {
+    let before = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let after = b"abcdeFghijklmnopqrstUvwxyz0123456789";
+    let patch = wrapped_clumps(before, after, 3);
+    let mut patched = vec![];
+    let stats = patch
+        .apply_into(&Seq::from(&after[..]), &mut patched, false)
+        .unwrap();
+    assert_eq!(stats.clean, 0);
+    assert_eq!(stats.already_applied, 2);
+    assert_eq!(patched, after.to_vec());
+    assert!(patch.already_applied(&Seq::from(&after[..]), false));
+}
+
+#[test]
+fn failure_is_an_error() {
+    let before = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let after = b"abcdeFghijklmnopqrstUvwxyz0123456789";
+    let patch = wrapped_clumps(before, after, 3);
+    let patchable = Seq::from(&b"abcdefghijklmnopqrst-vwxyz0123456789"[..]);
+    let mut patched = vec![];
+    assert!(patch.apply_into(&patchable, &mut patched, false).is_err());
+
+    let mut patched = vec![];
+    let report = patch
+        .apply_into_reporting(&patchable, &mut patched, false)
+        .unwrap();
+    assert!(!report.succeeded());
+    let stats = report.statistics();
+    assert_eq!(stats.clean, 1);
+    assert_eq!(stats.failed, 1);
+}
+
+#[test]
+fn displaced() {
+    let before = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let after = b"abcdeFghijklmnopqrstUvwxyz0123456789";
+    let patch = wrapped_clumps(before, after, 3);
+    let mut patched = vec![];
+    let report = patch
+        .apply_into_reporting(
+            &Seq::from(&b"+abcdefghijklmnopqrstuvwxyz0123456789"[..]),
+            &mut patched,
+            false,
+        )
+        .unwrap();
+    assert_eq!(
+        report.outcomes,
+        vec![
+            ClumpOutcome::Applied(1, None),
+            ClumpOutcome::Applied(0, None)
+        ]
+    );
+    assert_eq!(patched, b"+abcdeFghijklmnopqrstUvwxyz0123456789".to_vec());
+
+    let mut patched = vec![];
+    let report = patch
+        .apply_into_reporting(
+            &Seq::from(&b"abcdefghij--klmnopqrstuvwxyz0123456789"[..]),
+            &mut patched,
+            false,
+        )
+        .unwrap();
+    assert_eq!(
+        report.outcomes,
+        vec![
+            ClumpOutcome::Applied(0, None),
+            ClumpOutcome::Applied(2, None)
+        ]
+    );
+    assert_eq!(patched, b"abcdeFghij--klmnopqrstUvwxyz0123456789".to_vec());
+}
+
+#[test]
+fn fuzzy() {
+    let before = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let after = b"abcdefghijklmnopqrstUvwxyz0123456789";
+    let patch = wrapped_clumps(before, after, 3);
+    let mut patched = vec![];
+    let report = patch
+        .apply_into_reporting(
+            &Seq::from(&b"abcdefghijklmnopqRstuvwxyz0123456789"[..]),
+            &mut patched,
+            false,
+        )
+        .unwrap();
+    assert_eq!(
+        report.outcomes,
+        vec![ClumpOutcome::Applied(0, Some((1, 1)))]
+    );
+    assert_eq!(patched, b"abcdefghijklmnopqRstUvwxyz0123456789".to_vec());
+}
@@ -0,0 +1,201 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::io;
+
+use crate::apply_text::WillApply;
+use crate::changes::ChangeBasics;
+use crate::range::{Len, Range};
+use crate::sequence::{ConsumableSeq, ConsumableSeqIfce, Seq, WriteDataInto};
+
+/// Finding where a clump of `T`s applies: at its recorded position or at an
+/// offset from it and/or with reduced context.  Text and byte clumps get
+/// these via `ApplyClumpFuzzy` and `ApplyClumpClean` respectively.
+pub trait ApplyClump<T: PartialEq + Clone>: ChangeBasics
+where
+    Seq<T>: WriteDataInto,
+{
+    fn context_lengths(&self) -> (u8, u8);
+    fn before_items<'a>(&'a self, range: Range, reverse: bool) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a;
+    fn after_items<'a>(&'a self, range: Range, reverse: bool) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.before_items(range, !reverse)
+    }
+
+    fn before_adjusted_start(
+        &self,
+        offset: isize,
+        reductions: Option<(u8, u8)>,
+        reverse: bool,
+    ) -> isize {
+        if let Some((start_redn, _)) = reductions {
+            self.before_start(reverse) as isize + offset + start_redn as isize
+        } else {
+            self.before_start(reverse) as isize + offset
+        }
+    }
+
+    fn after_adjusted_start(
+        &self,
+        offset: isize,
+        reductions: Option<(u8, u8)>,
+        reverse: bool,
+    ) -> isize {
+        self.before_adjusted_start(offset, reductions, !reverse)
+    }
+
+    fn before_adjusted_length(&self, reductions: Option<(u8, u8)>, reverse: bool) -> usize {
+        if let Some((start_redn, end_redn)) = reductions {
+            self.before_length(reverse) - start_redn as usize - end_redn as usize
+        } else {
+            self.before_length(reverse)
+        }
+    }
+
+    fn after_adjusted_length(&self, reductions: Option<(u8, u8)>, reverse: bool) -> usize {
+        self.before_adjusted_length(reductions, !reverse)
+    }
+
+    /// Whether the clump's before items (less any `reductions`) are found in
+    /// `patchable` at `at` when items are compared using `matches`.
+    fn before_is_subsequence_in_at<M: Fn(&T, &T) -> bool>(
+        &self,
+        patchable: &Seq<T>,
+        at: usize,
+        reductions: Option<(u8, u8)>,
+        matches: &M,
+        reverse: bool,
+    ) -> bool {
+        let my_range = self.my_before_range(reductions, reverse);
+        let end = at + my_range.len();
+        end <= patchable.len()
+            && self
+                .before_items(my_range, reverse)
+                .zip(patchable.subsequence(Range(at, end)))
+                .all(|(l, r)| matches(l, r))
+    }
+
+    fn will_apply<M: Fn(&T, &T) -> bool>(
+        &self,
+        patchable: &Seq<T>,
+        offset: isize,
+        matches: &M,
+        reverse: bool,
+    ) -> Option<WillApply> {
+        let start = self.before_adjusted_start(offset, None, reverse);
+        if !start.is_negative()
+            && self.before_is_subsequence_in_at(patchable, start as usize, None, matches, reverse)
+        {
+            Some(WillApply::Cleanly)
+        } else {
+            let (start_context_len, end_context_len) = self.context_lengths();
+            let max_reduction = start_context_len.max(end_context_len);
+            for redn in 1..max_reduction {
+                let start_redn = redn.min(start_context_len);
+                let end_redn = redn.min(end_context_len);
+                let adj_start = start + start_redn as isize;
+                if !adj_start.is_negative()
+                    && self.before_is_subsequence_in_at(
+                        patchable,
+                        adj_start as usize,
+                        Some((start_redn, end_redn)),
+                        matches,
+                        reverse,
+                    )
+                {
+                    return Some(WillApply::WithReductions((start_redn, end_redn)));
+                }
+            }
+            None
+        }
+    }
+
+    /// Search outwards from `offset` for the nearest offset at which the
+    /// clump will apply without overlapping what's already been consumed or
+    /// where `next_clump` (if any) is expected.
+    fn will_apply_nearby<M: Fn(&T, &T) -> bool>(
+        &self,
+        pd: &ConsumableSeq<T>,
+        next_clump: Option<&Self>,
+        offset: isize,
+        matches: &M,
+        reverse: bool,
+    ) -> Option<(isize, WillApply)> {
+        let length = self.before_adjusted_length(Some(self.context_lengths()), reverse) as isize;
+        let not_after = if let Some(next_clump) = next_clump {
+            next_clump.before_adjusted_start(offset, Some(next_clump.context_lengths()), reverse)
+                - length
+        } else {
+            pd.data().len() as isize - length
+        };
+        let mut backward_done = false;
+        let mut forward_done = false;
+        for i in 1isize.. {
+            if !backward_done {
+                let adjusted_offset = offset - i;
+                if self.before_adjusted_start(adjusted_offset, None, reverse)
+                    < pd.consumed() as isize
+                {
+                    backward_done = true;
+                } else if let Some(will_apply) =
+                    self.will_apply(pd.data(), adjusted_offset, matches, reverse)
+                {
+                    return Some((-i, will_apply));
+                }
+            }
+            if !forward_done {
+                let adjusted_offset = offset + i;
+                if self.before_adjusted_start(adjusted_offset, None, reverse) < not_after {
+                    if let Some(will_apply) =
+                        self.will_apply(pd.data(), adjusted_offset, matches, reverse)
+                    {
+                        return Some((i, will_apply));
+                    }
+                } else {
+                    forward_done = true
+                }
+            }
+            if forward_done && backward_done {
+                break;
+            }
+        }
+        None
+    }
+
+    fn is_already_applied<M: Fn(&T, &T) -> bool>(
+        &self,
+        patchable: &Seq<T>,
+        offset: isize,
+        matches: &M,
+        reverse: bool,
+    ) -> Option<WillApply> {
+        self.will_apply(patchable, offset, matches, !reverse)
+    }
+
+    fn is_already_applied_nearby<M: Fn(&T, &T) -> bool>(
+        &self,
+        pd: &ConsumableSeq<T>,
+        next_clump: Option<&Self>,
+        offset: isize,
+        matches: &M,
+        reverse: bool,
+    ) -> Option<(isize, WillApply)> {
+        self.will_apply_nearby(pd, next_clump, offset, matches, !reverse)
+    }
+
+    fn already_applied_into<W: io::Write>(
+        &self,
+        into: &mut W,
+        pd: &mut ConsumableSeq<T>,
+        offset: isize,
+        reductions: Option<(u8, u8)>,
+        reverse: bool,
+    ) -> io::Result<()> {
+        let end = self.after_adjusted_start(offset, reductions, reverse) as usize
+            + self.after_adjusted_length(reductions, reverse);
+        pd.write_into_upto(into, end)
+    }
+}
@@ -1,12 +1,14 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
+use std::fmt;
 use std::io;
 
 use log;
 
-use crate::changes::ChangeBasics;
-use crate::range::{Len, Range};
-use crate::sequence::{ConsumableSeq, ConsumableSeqIfce, Seq};
+use crate::apply_clump;
+use crate::changes::{Change, ChangeBasics, Changes};
+use crate::range::Range;
+use crate::sequence::{ConsumableSeq, ConsumableSeqIfce, Seq, WriteDataInto};
 
 pub trait TextClumpBasics: ChangeBasics {
     fn context_lengths(&self) -> (u8, u8);
@@ -14,8 +16,15 @@ pub trait TextClumpBasics: ChangeBasics {
     fn after_lines(&self, range: Option<Range>, reverse: bool) -> impl Iterator<Item = &String> {
         self.before_lines(range, !reverse)
     }
+
+    /// The text (e.g. the first line of the function containing the clump)
+    /// from the clump's header, if any.
+    fn heading(&self) -> Option<&str> {
+        None
+    }
 }
 
+/// Application of text clumps (with where they apply found by `ApplyClump`).
 pub trait ApplyClumpFuzzy: TextClumpBasics {
     fn before_adjusted_start(
         &self,
@@ -23,11 +32,7 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         reductions: Option<(u8, u8)>,
         reverse: bool,
     ) -> isize {
-        if let Some((start_redn, _)) = reductions {
-            self.before_start(reverse) as isize + offset + start_redn as isize
-        } else {
-            self.before_start(reverse) as isize + offset
-        }
+        apply_clump::ApplyClump::before_adjusted_start(self, offset, reductions, reverse)
     }
 
     fn after_adjusted_start(
@@ -36,19 +41,15 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         reductions: Option<(u8, u8)>,
         reverse: bool,
     ) -> isize {
-        self.before_adjusted_start(offset, reductions, !reverse)
+        apply_clump::ApplyClump::after_adjusted_start(self, offset, reductions, reverse)
     }
 
     fn before_adjusted_length(&self, reductions: Option<(u8, u8)>, reverse: bool) -> usize {
-        if let Some((start_redn, end_redn)) = reductions {
-            self.before_length(reverse) - start_redn as usize - end_redn as usize
-        } else {
-            self.before_length(reverse)
-        }
+        apply_clump::ApplyClump::before_adjusted_length(self, reductions, reverse)
     }
 
     fn after_adjusted_length(&self, reductions: Option<(u8, u8)>, reverse: bool) -> usize {
-        self.before_adjusted_length(reductions, !reverse)
+        apply_clump::ApplyClump::after_adjusted_length(self, reductions, reverse)
     }
 
     fn before_is_subsequence_in_at(
@@ -58,16 +59,27 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         reductions: Option<(u8, u8)>,
         reverse: bool,
     ) -> bool {
-        let my_range = self.my_before_range(reductions, reverse);
-        let end = at + my_range.len();
-        if end > patchable.len() {
-            false
-        } else {
-            let other_range = Range(at, end);
-            self.before_lines(Some(my_range), reverse)
-                .zip(patchable.subsequence(other_range))
-                .all(|(l, r)| l == r)
-        }
+        self.before_is_subsequence_in_at_with_matching(
+            patchable,
+            at,
+            reductions,
+            LineMatching::Exact,
+            reverse,
+        )
+    }
+
+    fn before_is_subsequence_in_at_with_matching(
+        &self,
+        patchable: &Seq<String>,
+        at: usize,
+        reductions: Option<(u8, u8)>,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> bool {
+        let matches = |l: &String, r: &String| matching.matches(l, r);
+        apply_clump::ApplyClump::before_is_subsequence_in_at(
+            self, patchable, at, reductions, &matches, reverse,
+        )
     }
 
     fn before_write_into<W: io::Write>(
@@ -77,7 +89,7 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         reverse: bool,
     ) -> io::Result<()> {
         if reductions.is_some() {
-            let range = self.before_range(reductions, reverse);
+            let range = self.my_before_range(reductions, reverse);
             for line in self.before_lines(Some(range), reverse) {
                 into.write_all(line.as_bytes())?;
             }
@@ -104,31 +116,18 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         offset: isize,
         reverse: bool,
     ) -> Option<WillApply> {
-        let start = self.before_adjusted_start(offset, None, reverse);
-        if !start.is_negative()
-            && self.before_is_subsequence_in_at(patchable, start as usize, None, reverse)
-        {
-            Some(WillApply::Cleanly)
-        } else {
-            let (start_context_len, end_context_len) = self.context_lengths();
-            let max_reduction = start_context_len.max(end_context_len);
-            for redn in 1..max_reduction {
-                let start_redn = redn.min(start_context_len);
-                let end_redn = redn.min(end_context_len);
-                let adj_start = start + start_redn as isize;
-                if !adj_start.is_negative()
-                    && self.before_is_subsequence_in_at(
-                        patchable,
-                        adj_start as usize,
-                        Some((start_redn, end_redn)),
-                        reverse,
-                    )
-                {
-                    return Some(WillApply::WithReductions((start_redn, end_redn)));
-                }
-            }
-            None
-        }
+        self.will_apply_with_matching(patchable, offset, LineMatching::Exact, reverse)
+    }
+
+    fn will_apply_with_matching(
+        &self,
+        patchable: &Seq<String>,
+        offset: isize,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> Option<WillApply> {
+        let matches = |l: &String, r: &String| matching.matches(l, r);
+        apply_clump::ApplyClump::will_apply(self, patchable, offset, &matches, reverse)
     }
 
     fn apply_into<W: io::Write>(
@@ -139,56 +138,70 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         reductions: Option<(u8, u8)>,
         reverse: bool,
     ) -> io::Result<()> {
-        let end = self.before_adjusted_start(offset, reductions, reverse) as usize;
-        pd.write_into_upto(into, end)?;
-        self.after_write_into(into, reductions, reverse)?;
-        pd.advance_consumed_by(self.before_adjusted_length(reductions, reverse));
-        Ok(())
+        self.apply_into_with_matching(into, pd, offset, reductions, LineMatching::Exact, reverse)
     }
 
-    fn will_apply_nearby(
+    fn apply_into_with_matching<W: io::Write>(
         &self,
-        pd: &ConsumableSeq<String>,
-        next_clump: Option<&Self>,
+        into: &mut W,
+        pd: &mut ConsumableSeq<String>,
         offset: isize,
+        reductions: Option<(u8, u8)>,
+        matching: LineMatching,
         reverse: bool,
-    ) -> Option<(isize, WillApply)> {
-        let not_after = if let Some(next_clump) = next_clump {
-            next_clump.before_adjusted_start(offset, Some(self.context_lengths()), reverse) as usize
-                - self.before_adjusted_length(Some(self.context_lengths()), reverse)
+    ) -> io::Result<()> {
+        let start = self.before_adjusted_start(offset, reductions, reverse) as usize;
+        pd.write_into_upto(into, start)?;
+        if matching == LineMatching::Exact {
+            self.after_write_into(into, reductions, reverse)?;
         } else {
-            pd.data().len() - self.before_adjusted_length(Some(self.context_lengths()), reverse)
-        };
-        let mut backward_done = false;
-        let mut forward_done = false;
-        for i in 1isize.. {
-            if !backward_done {
-                let adjusted_offset = offset - i;
-                if self.before_adjusted_start(adjusted_offset, None, reverse)
-                    < pd.consumed() as isize
-                {
-                    backward_done = true;
-                } else {
-                    if let Some(will_apply) = self.will_apply(pd.data(), adjusted_offset, reverse) {
-                        return Some((-i, will_apply));
+            // Context lines may only match modulo whitespace so take them
+            // from the patchable data rather than from the clump.
+            let before =
+                self.before_lines(Some(self.my_before_range(reductions, reverse)), reverse);
+            let after = self.after_lines(Some(self.my_after_range(reductions, reverse)), reverse);
+            let changes = Changes::<String>::new(
+                Seq(before.cloned().collect()),
+                Seq(after.cloned().collect()),
+            );
+            for change in changes.changes.iter() {
+                match change {
+                    Change::NoChange(common_subsequence) => {
+                        let range = common_subsequence.before_range();
+                        pd.data()
+                            .write_into(into, Range(start + range.start(), start + range.end()))?;
                     }
-                }
-            }
-            if !forward_done {
-                let adjusted_offset = offset + i;
-                if self.before_adjusted_start(adjusted_offset, None, reverse) < not_after as isize {
-                    if let Some(will_apply) = self.will_apply(pd.data(), adjusted_offset, reverse) {
-                        return Some((i, will_apply));
+                    Change::Delete(_, _) => (),
+                    Change::Insert(_, after_range) | Change::Replace(_, after_range) => {
+                        changes.after.write_into(into, *after_range)?;
                     }
-                } else {
-                    forward_done = true
                 }
             }
-            if forward_done && backward_done {
-                break;
-            }
         }
-        None
+        pd.advance_consumed_by(self.before_adjusted_length(reductions, reverse));
+        Ok(())
+    }
+
+    fn will_apply_nearby(
+        &self,
+        pd: &ConsumableSeq<String>,
+        next_clump: Option<&Self>,
+        offset: isize,
+        reverse: bool,
+    ) -> Option<(isize, WillApply)> {
+        self.will_apply_nearby_with_matching(pd, next_clump, offset, LineMatching::Exact, reverse)
+    }
+
+    fn will_apply_nearby_with_matching(
+        &self,
+        pd: &ConsumableSeq<String>,
+        next_clump: Option<&Self>,
+        offset: isize,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> Option<(isize, WillApply)> {
+        let matches = |l: &String, r: &String| matching.matches(l, r);
+        apply_clump::ApplyClump::will_apply_nearby(self, pd, next_clump, offset, &matches, reverse)
     }
 
     fn is_already_applied(
@@ -200,6 +213,16 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         self.will_apply(patchable, offset, !reverse)
     }
 
+    fn is_already_applied_with_matching(
+        &self,
+        patchable: &Seq<String>,
+        offset: isize,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> Option<WillApply> {
+        self.will_apply_with_matching(patchable, offset, matching, !reverse)
+    }
+
     fn is_already_applied_nearby(
         &self,
         pd: &ConsumableSeq<String>,
@@ -210,6 +233,17 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         self.will_apply_nearby(pd, next_clump, offset, !reverse)
     }
 
+    fn is_already_applied_nearby_with_matching(
+        &self,
+        pd: &ConsumableSeq<String>,
+        next_clump: Option<&Self>,
+        offset: isize,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> Option<(isize, WillApply)> {
+        self.will_apply_nearby_with_matching(pd, next_clump, offset, matching, !reverse)
+    }
+
     fn already_applied_into<W: io::Write>(
         &self,
         into: &mut W,
@@ -218,9 +252,7 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
         reductions: Option<(u8, u8)>,
         reverse: bool,
     ) -> io::Result<()> {
-        let end = self.after_adjusted_start(offset, reductions, reverse) as usize
-            + self.after_adjusted_length(reductions, reverse);
-        pd.write_into_upto(into, end)
+        apply_clump::ApplyClump::already_applied_into(self, into, pd, offset, reductions, reverse)
     }
 
     fn write_failure_data_into<W: io::Write>(&self, into: &mut W, reverse: bool) -> io::Result<()> {
@@ -232,12 +264,104 @@ pub trait ApplyClumpFuzzy: TextClumpBasics {
     }
 }
 
+impl<C: TextClumpBasics + ?Sized> apply_clump::ApplyClump<String> for C {
+    fn context_lengths(&self) -> (u8, u8) {
+        TextClumpBasics::context_lengths(self)
+    }
+
+    fn before_items<'a>(&'a self, range: Range, reverse: bool) -> impl Iterator<Item = &'a String>
+    where
+        String: 'a,
+    {
+        self.before_lines(Some(range), reverse)
+    }
+}
+
+/// How context lines in a clump are matched against the patchable data.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum LineMatching {
+    #[default]
+    Exact,
+    /// Lines match if they contain the same words regardless of the amount
+    /// (or presence) of whitespace between, before or after them.
+    IgnoreWhitespace,
+}
+
+impl LineMatching {
+    /// Example:
+    /// ```
+    /// use pw_diff_lib::apply_text::LineMatching;
+    /// assert!(LineMatching::IgnoreWhitespace.matches("  a  b\n", "\ta b  \n"));
+    /// assert!(!LineMatching::IgnoreWhitespace.matches("ab\n", "a b\n"));
+    /// assert!(!LineMatching::Exact.matches("a b\n", "a b \n"));
+    /// ```
+    pub fn matches(&self, l: &str, r: &str) -> bool {
+        match self {
+            LineMatching::Exact => l == r,
+            LineMatching::IgnoreWhitespace => l.split_whitespace().eq(r.split_whitespace()),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum WillApply {
     Cleanly,
     WithReductions((u8, u8)),
 }
 
+impl WillApply {
+    pub fn reductions(&self) -> Option<(u8, u8)> {
+        match self {
+            WillApply::Cleanly => None,
+            WillApply::WithReductions(reductions) => Some(*reductions),
+        }
+    }
+}
+
+/// The outcome of applying (or checking) a single clump.  Offsets are relative
+/// to the position implied by the clumps that precede it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ClumpOutcome {
+    Applied(isize, Option<(u8, u8)>),
+    AlreadyApplied(isize, Option<(u8, u8)>),
+    Failed,
+}
+
+impl ClumpOutcome {
+    pub fn is_clean(&self) -> bool {
+        *self == ClumpOutcome::Applied(0, None)
+    }
+
+    pub fn is_failure(&self) -> bool {
+        *self == ClumpOutcome::Failed
+    }
+}
+
+impl fmt::Display for ClumpOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ClumpOutcome::*;
+        match self {
+            Applied(0, None) => write!(f, "applies cleanly"),
+            Applied(offset, None) => write!(f, "applies with offset {offset}"),
+            Applied(0, Some(reductions)) => write!(f, "applies with {reductions:?} reductions"),
+            Applied(offset, Some(reductions)) => write!(
+                f,
+                "applies with {reductions:?} reductions and offset {offset}"
+            ),
+            AlreadyApplied(0, None) => write!(f, "already applied"),
+            AlreadyApplied(offset, None) => write!(f, "already applied with offset {offset}"),
+            AlreadyApplied(0, Some(reductions)) => {
+                write!(f, "already applied with {reductions:?} reductions")
+            }
+            AlreadyApplied(offset, Some(reductions)) => write!(
+                f,
+                "already applied with {reductions:?} reductions and offset {offset}"
+            ),
+            Failed => write!(f, "could NOT be applied"),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Statistics {
     pub clean: usize,
@@ -247,6 +371,66 @@ pub struct Statistics {
     pub failed: usize,
 }
 
+fn clump_label(clump_num: usize, heading: Option<&str>) -> String {
+    match heading {
+        Some(heading) => format!("Clump #{clump_num} ({heading})"),
+        None => format!("Clump #{clump_num}"),
+    }
+}
+
+/// Per clump outcomes (in clump order) of applying, or checking, a diff.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ApplyReport {
+    pub outcomes: Vec<ClumpOutcome>,
+    /// The clumps' headings (in the same order).
+    pub headings: Vec<Option<String>>,
+}
+
+impl ApplyReport {
+    pub fn push(&mut self, outcome: ClumpOutcome) {
+        self.push_with_heading(outcome, None)
+    }
+
+    pub fn push_with_heading(&mut self, outcome: ClumpOutcome, heading: Option<&str>) {
+        let clump_num = self.outcomes.len() + 1; // for human consumption
+        let clump = clump_label(clump_num, heading);
+        if outcome.is_clean() {
+            log::info!("{clump} {outcome}.");
+        } else if outcome.is_failure() {
+            log::error!("{clump} {outcome}!");
+        } else {
+            log::warn!("{clump} {outcome}.");
+        }
+        self.outcomes.push(outcome);
+        self.headings.push(heading.map(str::to_string));
+    }
+
+    /// How the clump numbered `clump_num` (from 1) is described in reports.
+    pub fn clump_label(&self, clump_num: usize) -> String {
+        let heading = self.headings.get(clump_num - 1).and_then(Option::as_deref);
+        clump_label(clump_num, heading)
+    }
+
+    pub fn succeeded(&self) -> bool {
+        !self.outcomes.iter().any(|o| o.is_failure())
+    }
+
+    pub fn statistics(&self) -> Statistics {
+        use ClumpOutcome::*;
+        let mut stats = Statistics::default();
+        for outcome in self.outcomes.iter() {
+            match outcome {
+                Applied(0, None) => stats.clean += 1,
+                Applied(_, _) => stats.fuzzy += 1,
+                AlreadyApplied(0, None) => stats.already_applied += 1,
+                AlreadyApplied(_, _) => stats.already_applied_fuzzy += 1,
+                Failed => stats.failed += 1,
+            }
+        }
+        stats
+    }
+}
+
 pub trait ApplyClumpsFuzzy<C>
 where
     C: ApplyClumpFuzzy,
@@ -261,93 +445,100 @@ where
         into: &mut W,
         reverse: bool,
     ) -> io::Result<Statistics> {
+        Ok(self
+            .apply_into_reporting(patchable, into, reverse)?
+            .statistics())
+    }
+
+    fn apply_into_reporting<W: io::Write>(
+        &self,
+        patchable: &Seq<String>,
+        into: &mut W,
+        reverse: bool,
+    ) -> io::Result<ApplyReport> {
+        self.apply_into_reporting_with_matching(patchable, into, LineMatching::Exact, reverse)
+    }
+
+    /// As `apply_into_reporting()` but with context lines matched as
+    /// specified by `matching`.
+    fn apply_into_reporting_with_matching<W: io::Write>(
+        &self,
+        patchable: &Seq<String>,
+        into: &mut W,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> io::Result<ApplyReport> {
         let mut pd = ConsumableSeq::<String>::new(patchable);
-        let mut stats = Statistics::default();
+        let mut report = ApplyReport::default();
         let mut iter = self.clumps().peekable();
-        let mut clump_num = 0;
         let mut offset: isize = 0;
         while let Some(clump) = iter.next() {
-            clump_num += 1; // for human consumption
-            if let Some(will_apply) = clump.will_apply(patchable, offset, reverse) {
-                match will_apply {
-                    WillApply::Cleanly => {
-                        clump.apply_into(into, &mut pd, offset, None, reverse)?;
-                        stats.clean += 1;
-                        log::info!("Clump #{clump_num} applies cleanly.");
-                    }
-                    WillApply::WithReductions(reductions) => {
-                        clump.apply_into(into, &mut pd, offset, Some(reductions), reverse)?;
-                        stats.fuzzy += 1;
-                        log::warn!("Clump #{clump_num} applies with {reductions:?} reductions.");
-                    }
-                }
-            } else if let Some((offset_adj, will_apply)) =
-                clump.will_apply_nearby(&pd, iter.peek().copied(), offset, reverse)
+            let outcome = if let Some(will_apply) =
+                clump.will_apply_with_matching(patchable, offset, matching, reverse)
             {
+                let reductions = will_apply.reductions();
+                clump.apply_into_with_matching(
+                    into, &mut pd, offset, reductions, matching, reverse,
+                )?;
+                ClumpOutcome::Applied(0, reductions)
+            } else if let Some((offset_adj, will_apply)) = clump.will_apply_nearby_with_matching(
+                &pd,
+                iter.peek().copied(),
+                offset,
+                matching,
+                reverse,
+            ) {
                 offset += offset_adj;
-                match will_apply {
-                    WillApply::Cleanly => {
-                        clump.apply_into(into, &mut pd, offset, None, reverse)?;
-                        stats.fuzzy += 1;
-                        log::warn!("Clump #{clump_num} applies with offset {offset_adj}.");
-                    }
-                    WillApply::WithReductions(reductions) => {
-                        clump.apply_into(into, &mut pd, offset, Some(reductions), reverse)?;
-                        stats.fuzzy += 1;
-                        log::warn!("Clump #{clump_num} applies with {reductions:?} reductions and offset {offset_adj}.");
-                    }
-                }
-            } else if let Some(appplied) = clump.is_already_applied(patchable, offset, reverse) {
-                match appplied {
-                    WillApply::Cleanly => {
-                        clump.already_applied_into(into, &mut pd, offset, None, reverse)?;
-                        stats.already_applied += 1;
-                        log::warn!("Clump #{clump_num} already applied")
-                    }
-                    WillApply::WithReductions(reductions) => {
-                        clump.already_applied_into(
-                            into,
-                            &mut pd,
-                            offset,
-                            Some(reductions),
-                            reverse,
-                        )?;
-                        stats.already_applied_fuzzy += 1;
-                        log::warn!(
-                            "Clump #{clump_num} already applied with {reductions:?} reductions."
-                        );
-                    }
-                }
-            } else if let Some((offset_adj, applied)) =
-                clump.is_already_applied_nearby(&pd, iter.peek().copied(), offset, reverse)
+                let reductions = will_apply.reductions();
+                clump.apply_into_with_matching(
+                    into, &mut pd, offset, reductions, matching, reverse,
+                )?;
+                ClumpOutcome::Applied(offset_adj, reductions)
+            } else if let Some(applied) =
+                clump.is_already_applied_with_matching(patchable, offset, matching, reverse)
+            {
+                let reductions = applied.reductions();
+                clump.already_applied_into(into, &mut pd, offset, reductions, reverse)?;
+                ClumpOutcome::AlreadyApplied(0, reductions)
+            } else if let Some((offset_adj, applied)) = clump
+                .is_already_applied_nearby_with_matching(
+                    &pd,
+                    iter.peek().copied(),
+                    offset,
+                    matching,
+                    reverse,
+                )
             {
                 offset += offset_adj;
-                match applied {
-                    WillApply::Cleanly => {
-                        clump.already_applied_into(into, &mut pd, offset, None, reverse)?;
-                        stats.already_applied_fuzzy += 1;
-                        log::warn!("Clump #{clump_num} already applied with offset {offset_adj}")
-                    }
-                    WillApply::WithReductions(reductions) => {
-                        clump.already_applied_into(
-                            into,
-                            &mut pd,
-                            offset,
-                            Some(reductions),
-                            reverse,
-                        )?;
-                        stats.already_applied_fuzzy += 1;
-                        log::warn!("Clump #{clump_num} already applied with {reductions:?} reductions and offset {offset_adj}.")
-                    }
-                }
+                let reductions = applied.reductions();
+                clump.already_applied_into(into, &mut pd, offset, reductions, reverse)?;
+                ClumpOutcome::AlreadyApplied(offset_adj, reductions)
             } else {
-                stats.failed += 1;
                 clump.write_failure_data_into(into, reverse)?;
-                log::error!("Clump #{clump_num} could NOT be applied!");
-            }
+                ClumpOutcome::Failed
+            };
+            report.push_with_heading(outcome, clump.heading());
         }
         pd.write_remainder(into)?;
-        Ok(stats)
+        Ok(report)
+    }
+
+    /// Report what would happen if this diff were applied to `patchable`
+    /// without writing anything.
+    fn will_apply(&self, patchable: &Seq<String>, reverse: bool) -> ApplyReport {
+        self.will_apply_with_matching(patchable, LineMatching::Exact, reverse)
+    }
+
+    /// As `will_apply()` but with context lines matched as specified by
+    /// `matching`.
+    fn will_apply_with_matching(
+        &self,
+        patchable: &Seq<String>,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> ApplyReport {
+        self.apply_into_reporting_with_matching(patchable, &mut io::sink(), matching, reverse)
+            .expect("writing to a sink cannot fail")
     }
 
     fn is_already_applied(&self, patchable: &Seq<String>, reverse: bool) -> bool {
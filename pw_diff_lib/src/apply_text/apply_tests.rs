@@ -35,11 +35,10 @@ impl Stringy for BufWriter<Vec<u8>> {
 fn clean_patch() {
     let before_lines = "A\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\n";
     let after_lines = "A\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\n";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     let mut patched = BufWriter::new(vec![]);
@@ -59,11 +58,10 @@ fn clean_patch() {
 fn clean_patch_in_middle() {
     let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\n";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_lumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_lumps);
     let mut patched = BufWriter::new(vec![]);
@@ -82,11 +80,10 @@ fn clean_patch_in_middle() {
 fn already_fully_applied() {
     let before_lines = "A\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\n";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     let mut patched = BufWriter::new(vec![]);
@@ -105,11 +102,10 @@ fn already_fully_applied() {
 fn clean_patch_reverse() {
     let before_lines = "A\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\n";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     let mut patched = BufWriter::new(vec![]);
@@ -128,11 +124,10 @@ fn clean_patch_reverse() {
 fn displaced() {
     let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\n";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     let mut patched = BufWriter::new(vec![]);
@@ -155,11 +150,10 @@ fn displaced() {
 fn displaced_no_final_eol_1() {
     let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\n";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     let mut patched = BufWriter::new(vec![]);
@@ -182,11 +176,10 @@ fn displaced_no_final_eol_1() {
 fn displaced_no_final_eol_2() {
     let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\na";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     let mut patched = BufWriter::new(vec![]);
@@ -209,11 +202,10 @@ fn displaced_no_final_eol_2() {
 fn displaced_no_final_eol_3() {
     let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     let mut patched = BufWriter::new(vec![]);
@@ -236,14 +228,107 @@ fn displaced_no_final_eol_3() {
 fn already_applied() {
     let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
     let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz";
-    let modifications =
-        Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
     let diff_clumps: Vec<TextChangeClump> = modifications
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let patch = WrappedDiffClumps(diff_clumps);
     assert!(patch.is_already_applied(&Seq::from(after_lines), false));
     assert!(!patch.is_already_applied(&Seq::from(before_lines), false));
     assert!(patch.is_already_applied(&Seq::from("x\ny\nz\n".to_owned() + after_lines), false));
 }
+
+#[test]
+fn will_apply_report() {
+    let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
+    let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\n";
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let diff_clumps: Vec<TextChangeClump> = modifications
+        .change_clumps(2)
+        .map(TextChangeClump::from)
+        .collect();
+    let patch = WrappedDiffClumps(diff_clumps);
+    let report = patch.will_apply(&Seq::from("x\ny\nz\n".to_owned() + before_lines), false);
+    assert_eq!(
+        report.outcomes,
+        vec![
+            ClumpOutcome::Applied(3, None),
+            ClumpOutcome::Applied(0, None)
+        ]
+    );
+    assert!(report.succeeded());
+    let report = patch.will_apply(&Seq::from(after_lines), false);
+    assert_eq!(
+        report.outcomes,
+        vec![
+            ClumpOutcome::AlreadyApplied(0, None),
+            ClumpOutcome::AlreadyApplied(0, None)
+        ]
+    );
+    let report = patch.will_apply(&Seq::from("1\n2\n3\n"), false);
+    assert_eq!(
+        report.outcomes,
+        vec![ClumpOutcome::Failed, ClumpOutcome::Failed]
+    );
+    assert!(!report.succeeded());
+    assert_eq!(report.statistics().failed, 2);
+}
+
+#[test]
+fn ignore_whitespace() {
+    let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
+    let after_lines = "a\nb\nc\nd\nA\nC\nD\nEf\nFg\nG\nH\nI\nJ\nK\nH\nL\nM\nx\ny\nz\n";
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let diff_clumps: Vec<TextChangeClump> = modifications
+        .change_clumps(2)
+        .map(TextChangeClump::from)
+        .collect();
+    let patch = WrappedDiffClumps(diff_clumps);
+    let patchable = "a\nb\nc\nd\n  A\n  B\n  C \n  D\nE\nF\nG\nH\nI\nJ\n\tK\nL \nM\nx\ny\nz\n";
+
+    let report = patch.will_apply(&Seq::from(patchable), false);
+    assert!(!report.succeeded());
+
+    let mut patched = BufWriter::new(vec![]);
+    let stats = patch
+        .apply_into_reporting_with_matching(
+            &Seq::from(patchable),
+            &mut patched,
+            LineMatching::IgnoreWhitespace,
+            false,
+        )
+        .unwrap()
+        .statistics();
+    assert_eq!(stats.clean, 2);
+    assert_eq!(stats.failed, 0);
+    assert_eq!(
+        patched.to_string(),
+        "a\nb\nc\nd\n  A\n  C \n  D\nEf\nFg\nG\nH\nI\nJ\n\tK\nH\nL \nM\nx\ny\nz\n"
+    );
+}
+
+#[test]
+fn reduced_context() {
+    let before_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nG\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
+    let after_lines = "a\nb\nc\nd\nA\nB\nC\nD\nE\nF\nGg\nH\nI\nJ\nK\nL\nM\nx\ny\nz\n";
+    let modifications = Changes::<String>::new(Seq::from(before_lines), Seq::from(after_lines));
+    let diff_clumps: Vec<TextChangeClump> = modifications
+        .change_clumps(3)
+        .map(TextChangeClump::from)
+        .collect();
+    let patch = WrappedDiffClumps(diff_clumps);
+    let patchable = "a\nb\nc\nd\nA\nB\nC\nD\nE!\nF\nG\nH\nI\nJ!\nK\nL\nM\nx\ny\nz\n";
+    let mut patched = BufWriter::new(vec![]);
+    let report = patch
+        .apply_into_reporting(&Seq::from(patchable), &mut patched, false)
+        .unwrap();
+    assert_eq!(
+        report.outcomes,
+        vec![ClumpOutcome::Applied(0, Some((2, 2)))]
+    );
+    assert_eq!(
+        patched.to_string(),
+        "a\nb\nc\nd\nA\nB\nC\nD\nE!\nF\nGg\nH\nI\nJ!\nK\nL\nM\nx\ny\nz\n"
+    );
+}
@@ -0,0 +1,263 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::metadata::{file_mode, path_exists, set_file_mode};
+
+fn create_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::create_dir_all(dir),
+        _ => Ok(()),
+    }
+}
+
+/// How backup files are named (as for GNU `patch --version-control`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BackupControl {
+    /// `file` is backed up to `file` + suffix.
+    #[default]
+    Simple,
+    /// `file` is backed up to `file.~N~` where N is one more than the
+    /// highest existing backup number.
+    Numbered,
+    /// Numbered if numbered backups of `file` already exist, else simple.
+    Existing,
+}
+
+impl FromStr for BackupControl {
+    type Err = String;
+
+    /// Example:
+    /// ```
+    /// use pw_diff_lib::backup::BackupControl;
+    /// assert_eq!("t".parse::<BackupControl>(), Ok(BackupControl::Numbered));
+    /// assert!("sometimes".parse::<BackupControl>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" | "never" => Ok(Self::Simple),
+            "numbered" | "t" => Ok(Self::Numbered),
+            "existing" | "nil" => Ok(Self::Existing),
+            _ => Err(format!(
+                "{s:?}: expected one of simple, numbered or existing"
+            )),
+        }
+    }
+}
+
+/// Where and how to keep copies of files before they are changed.
+#[derive(Debug, Clone)]
+pub struct Backups {
+    control: BackupControl,
+    suffix: String,
+    dir: Option<PathBuf>,
+}
+
+impl Default for Backups {
+    fn default() -> Self {
+        Self {
+            control: BackupControl::default(),
+            suffix: ".orig".to_string(),
+            dir: None,
+        }
+    }
+}
+
+impl Backups {
+    /// Backups are kept beside the files they are for unless `dir` is given
+    /// in which case they are kept at the same relative path within `dir`.
+    pub fn new(control: BackupControl, suffix: &str, dir: Option<&Path>) -> Self {
+        Self {
+            control,
+            suffix: suffix.to_string(),
+            dir: dir.map(|dir| dir.to_path_buf()),
+        }
+    }
+
+    fn located(&self, path: &Path) -> PathBuf {
+        match &self.dir {
+            Some(dir) => dir.join(
+                path.components()
+                    .filter(|c| matches!(c, Component::Normal(_)))
+                    .collect::<PathBuf>(),
+            ),
+            None => path.to_path_buf(),
+        }
+    }
+
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    fn numbered(path: &Path, number: u32) -> PathBuf {
+        Self::with_suffix(path, &format!(".~{number}~"))
+    }
+
+    /// The highest number of the existing numbered backups of `path`.
+    fn highest_number(&self, path: &Path) -> io::Result<Option<u32>> {
+        let located = self.located(path);
+        let (Some(dir), Some(name)) = (located.parent(), located.file_name()) else {
+            return Ok(None);
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        let prefix = format!("{}.~", name.to_string_lossy());
+        let mut highest = None;
+        for entry in fs::read_dir(dir)? {
+            let entry_name = entry?.file_name();
+            let entry_name = entry_name.to_string_lossy();
+            if let Some(number) = entry_name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix('~'))
+                .and_then(|number| number.parse::<u32>().ok())
+            {
+                highest = highest.max(Some(number));
+            }
+        }
+        Ok(highest)
+    }
+
+    /// The path that a new backup of `path` should have.
+    pub fn backup_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let located = self.located(path);
+        let highest = self.highest_number(path)?;
+        match (self.control, highest) {
+            (BackupControl::Simple, _) | (BackupControl::Existing, None) => {
+                Ok(Self::with_suffix(&located, &self.suffix))
+            }
+            (_, highest) => Ok(Self::numbered(&located, highest.unwrap_or(0) + 1)),
+        }
+    }
+
+    /// The most recent existing backup of `path` (if any).
+    pub fn latest_backup(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        let located = self.located(path);
+        if self.control != BackupControl::Simple {
+            if let Some(number) = self.highest_number(path)? {
+                return Ok(Some(Self::numbered(&located, number)));
+            }
+        }
+        let simple = Self::with_suffix(&located, &self.suffix);
+        Ok(path_exists(&simple).then_some(simple))
+    }
+
+    /// Move the file at `path` to its backup path.
+    pub fn keep(&self, path: &Path) -> io::Result<PathBuf> {
+        let backup_path = self.backup_path(path)?;
+        create_parent_dir(&backup_path)?;
+        fs::rename(path, &backup_path)?;
+        Ok(backup_path)
+    }
+
+    /// Record that `path` did not exist (as GNU `patch` does, with an empty
+    /// unreadable backup file).
+    pub fn keep_absence(&self, path: &Path) -> io::Result<PathBuf> {
+        let backup_path = self.backup_path(path)?;
+        create_parent_dir(&backup_path)?;
+        fs::File::create(&backup_path)?;
+        set_file_mode(&backup_path, 0)?;
+        Ok(backup_path)
+    }
+
+    fn records_absence(backup_path: &Path) -> io::Result<bool> {
+        let metadata = fs::symlink_metadata(backup_path)?;
+        Ok(metadata.is_file()
+            && metadata.len() == 0
+            && file_mode(backup_path)?.is_none_or(|mode| mode == 0))
+    }
+
+    /// Put the most recent backup of `path` back in its place (removing
+    /// `path` if it did not exist when the backup was made).  Returns false
+    /// if there is no backup.
+    pub fn restore(&self, path: &Path) -> io::Result<bool> {
+        match self.latest_backup(path)? {
+            Some(backup_path) => {
+                if Self::records_absence(&backup_path)? {
+                    if path_exists(path) {
+                        fs::remove_file(path)?;
+                    }
+                    fs::remove_file(&backup_path)?;
+                } else {
+                    create_parent_dir(path)?;
+                    fs::rename(&backup_path, path)?;
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod backup_tests {
+    use super::*;
+
+    #[test]
+    fn backup_names() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        let simple = Backups::default();
+        assert_eq!(
+            simple.backup_path(&path).unwrap(),
+            temp_dir.path().join("file.orig")
+        );
+        let numbered = Backups::new(BackupControl::Numbered, ".orig", None);
+        let existing = Backups::new(BackupControl::Existing, "~", None);
+        assert_eq!(
+            numbered.backup_path(&path).unwrap(),
+            temp_dir.path().join("file.~1~")
+        );
+        assert_eq!(
+            existing.backup_path(&path).unwrap(),
+            temp_dir.path().join("file~")
+        );
+        fs::write(temp_dir.path().join("file.~3~"), "").unwrap();
+        assert_eq!(
+            numbered.backup_path(&path).unwrap(),
+            temp_dir.path().join("file.~4~")
+        );
+        assert_eq!(
+            existing.backup_path(&path).unwrap(),
+            temp_dir.path().join("file.~4~")
+        );
+        let in_dir = Backups::new(BackupControl::Simple, ".orig", Some(Path::new("backups")));
+        assert_eq!(
+            in_dir.backup_path(Path::new("../src/file")).unwrap(),
+            Path::new("backups/src/file.orig")
+        );
+    }
+
+    #[test]
+    fn keep_and_restore() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        let backups = Backups::new(
+            BackupControl::Numbered,
+            ".orig",
+            Some(&temp_dir.path().join("backups")),
+        );
+        fs::write(&path, "first\n").unwrap();
+        backups.keep(&path).unwrap();
+        fs::write(&path, "second\n").unwrap();
+        assert!(backups.restore(&path).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first\n");
+        assert!(!backups.restore(&path).unwrap());
+
+        let new_path = temp_dir.path().join("new");
+        backups.keep_absence(&new_path).unwrap();
+        fs::write(&new_path, "new\n").unwrap();
+        assert!(backups.restore(&new_path).unwrap());
+        assert!(!path_exists(&new_path));
+    }
+}
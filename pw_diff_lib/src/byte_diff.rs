@@ -7,11 +7,15 @@ use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 
 use crate::apply_bytes::{ApplyClumpClean, ApplyClumpsClean};
-use crate::changes::{ChangeClump, Changes};
-use crate::range::Len;
-use crate::snippet::{Snippet, SnippetWrite};
+use crate::apply_clump::ApplyClump;
+use crate::apply_text::ApplyReport;
+use crate::changes::{ChangeBasics, ChangeClump, Changes};
+use crate::digest::{Digest, DigestWriter, Digests, Verification};
+use crate::metadata::{file_mode, Modes};
+use crate::range::{Len, Range};
+use crate::snippet::Snippet;
 
-use crate::sequence::{ConsumableSeq, ConsumableSeqIfce, Seq};
+use crate::sequence::Seq;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ByteChangeClump {
@@ -50,45 +54,40 @@ impl ByteChangeClump {
     }
 }
 
-impl<'a> ApplyClumpClean for ByteChangeClump {
-    fn will_apply(&self, data: &Seq<u8>, reverse: bool) -> bool {
-        let before = self.before(reverse);
-        data.has_subsequence_at(&before.items, before.start)
+impl ChangeBasics for ByteChangeClump {
+    fn before_start(&self, reverse: bool) -> usize {
+        self.before(reverse).start
     }
 
-    fn is_already_applied(&self, data: &Seq<u8>, reverse: bool) -> bool {
-        let after = self.after(reverse);
-        data.has_subsequence_at(&after.items, after.start)
+    fn before_end(&self, reverse: bool) -> usize {
+        let before = self.before(reverse);
+        before.start + before.len()
     }
+}
 
-    fn apply_into<W: io::Write>(
-        &self,
-        pd: &mut ConsumableSeq<u8>,
-        into: &mut W,
-        reverse: bool,
-    ) -> io::Result<()> {
-        let before = self.before(reverse);
-        pd.write_into_upto(into, before.start)?;
-        self.after(reverse).write_into(into, None)?;
-        pd.advance_consumed_by(before.len());
-        Ok(())
+impl ApplyClump<u8> for ByteChangeClump {
+    fn context_lengths(&self) -> (u8, u8) {
+        self.context_lengths
     }
 
-    fn already_applied_into<W: io::Write>(
-        &self,
-        pd: &mut ConsumableSeq<u8>,
-        into: &mut W,
-        reverse: bool,
-    ) -> io::Result<()> {
-        let after = self.after(reverse);
-        pd.write_into_upto(into, after.start + after.len())
+    fn before_items<'a>(&'a self, range: Range, reverse: bool) -> impl Iterator<Item = &'a u8>
+    where
+        u8: 'a,
+    {
+        self.before(reverse).items[range.start()..range.end()].iter()
     }
 }
 
+impl ApplyClumpClean for ByteChangeClump {}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ByteChangeDiff {
     before_path: PathBuf,
     after_path: PathBuf,
+    #[serde(default)]
+    digests: Option<Digests>,
+    #[serde(default)]
+    modes: Option<Modes>,
     compressed: bool,
     clumps: Box<[ByteChangeClump]>,
 }
@@ -97,17 +96,40 @@ impl ByteChangeDiff {
     pub fn new(before_file_path: &Path, after_file_path: &Path, context: u8) -> io::Result<Self> {
         let before_bytes = Seq::<u8>::read(File::open(before_file_path)?)?;
         let after_bytes = Seq::<u8>::read(File::open(after_file_path)?)?;
+        let mut diff = Self::from_bytes(
+            before_file_path,
+            after_file_path,
+            before_bytes,
+            after_bytes,
+            context,
+        );
+        diff.modes = Modes::of_files(before_file_path, after_file_path)?;
+        Ok(diff)
+    }
+
+    /// A diff made from contents obtained elsewhere (e.g. decoded from a
+    /// git binary patch).
+    pub fn from_bytes(
+        before_file_path: &Path,
+        after_file_path: &Path,
+        before_bytes: Seq<u8>,
+        after_bytes: Seq<u8>,
+        context: u8,
+    ) -> Self {
+        let digests = Digests::new(Digest::from(&before_bytes), Digest::from(&after_bytes));
         let modifications = Changes::<u8>::new(before_bytes, after_bytes);
 
-        Ok(Self {
+        Self {
             before_path: before_file_path.to_path_buf(),
             after_path: after_file_path.to_path_buf(),
+            digests: Some(digests),
+            modes: None,
             compressed: false,
             clumps: modifications
                 .change_clumps(context)
-                .map(|c| ByteChangeClump::from(c))
+                .map(ByteChangeClump::from)
                 .collect(),
-        })
+        }
     }
 
     pub fn from_reader<R: io::Read>(reader: &mut R) -> Result<Self, serde_json::Error> {
@@ -122,9 +144,41 @@ impl ByteChangeDiff {
         &self.after_path
     }
 
+    pub fn digests(&self) -> Option<&Digests> {
+        self.digests.as_ref()
+    }
+
+    /// The file's before and after modes if they differ.
+    pub fn modes(&self) -> Option<&Modes> {
+        self.modes.as_ref()
+    }
+
+    pub fn set_modes(&mut self, modes: Option<Modes>) {
+        self.modes = modes
+    }
+
     pub fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<(), serde_json::Error> {
         serde_json::to_writer_pretty(writer, self)
     }
+
+    /// Apply this diff (regardless of failures) and verify the result against
+    /// the digests recorded when the diff was created.
+    pub fn apply_into_verifying<W: io::Write>(
+        &self,
+        patchable: &Seq<u8>,
+        into: &mut W,
+        reverse: bool,
+    ) -> io::Result<(ApplyReport, Verification)> {
+        let mut writer = DigestWriter::new(into);
+        let report = self.apply_into_reporting(patchable, &mut writer, reverse)?;
+        let verification = match &self.digests {
+            Some(digests) => {
+                digests.verify(&Digest::from(patchable), &writer.digest(), &report, reverse)
+            }
+            None => Verification::Unverifiable,
+        };
+        Ok((report, verification))
+    }
 }
 
 impl ApplyClumpsClean<'_, ByteChangeClump> for ByteChangeDiff {
@@ -139,6 +193,8 @@ impl ApplyClumpsClean<'_, ByteChangeClump> for ByteChangeDiff {
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct PathAndBytes {
     path: PathBuf,
+    #[serde(default)]
+    mode: Option<u32>,
     compressed: bool,
     bytes: Box<[u8]>,
 }
@@ -152,19 +208,37 @@ impl PathAndBytes {
 
         Ok(Self {
             path: path.to_path_buf(),
+            mode: file_mode(path)?,
             compressed: false,
             bytes: bytes.into_boxed_slice(),
         })
     }
 
+    pub fn from_bytes(path: &Path, mode: Option<u32>, bytes: Vec<u8>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            mode,
+            compressed: false,
+            bytes: bytes.into_boxed_slice(),
+        }
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
     pub fn change_path(&mut self, new_path: &Path) {
         self.path = new_path.to_path_buf()
     }
 
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
     pub fn write_into<W: io::Write>(&self, into: &mut W) -> io::Result<()> {
         into.write_all(&self.bytes)
     }
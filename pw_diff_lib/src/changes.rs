@@ -190,15 +190,15 @@ impl<'a, T: PartialEq + Clone, I: ContentItemIndices<T>> ChangesGenerator<'a, T,
                     .min(best_lcs.after_start()),
             );
 
-            if best_lcs.before_end() + 1 < before_range.end()
-                && best_lcs.after_end() + 1 < after_range.end()
+            if best_lcs.before_end() < before_range.end()
+                && best_lcs.after_end() < after_range.end()
             {
                 let count = self
                     .before
-                    .subsequence(Range(best_lcs.before_end() + 1, before_range.end()))
+                    .subsequence(Range(best_lcs.before_end(), before_range.end()))
                     .zip(
                         self.after
-                            .subsequence(Range(best_lcs.after_end() + 1, after_range.end())),
+                            .subsequence(Range(best_lcs.after_end(), after_range.end())),
                     )
                     .take_while(|(a, b)| a == b)
                     .count();
@@ -309,15 +309,9 @@ impl<'a, T: PartialEq + Clone, I: ContentItemIndices<T>> ChangesGenerator<'a, T,
                 self.after.range_from(j),
             ));
         } else if i < self.before.len() {
-            changes.push(Change::Delete(
-                self.before.range_from(i),
-                self.after.len(),
-            ));
+            changes.push(Change::Delete(self.before.range_from(i), self.after.len()));
         } else if j < self.after.len() {
-            changes.push(Change::Insert(
-                self.before.len(),
-                self.after.range_from(j),
-            ));
+            changes.push(Change::Insert(self.before.len(), self.after.range_from(j)));
         }
 
         changes
@@ -332,6 +326,28 @@ pub struct Changes<T: PartialEq + Clone> {
 }
 
 impl Changes<String> {
+    /// The changes that turn `before` into `after`.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use pw_diff_lib::common_subsequence::CommonSubsequence;
+    /// use pw_diff_lib::sequence::*;
+    /// use pw_diff_lib::changes::{Changes, Change};
+    /// use pw_diff_lib::range::Range;
+    /// use Change::*;
+    ///
+    /// // A single changed line between common lines isn't swallowed.
+    /// let changes = Changes::<String>::new(Seq::from("A\nB\nC\n"), Seq::from("A\nX\nC\n"));
+    /// assert_eq!(
+    ///     changes.changes,
+    ///     vec![
+    ///         NoChange(CommonSubsequence(0, 0, 1)),
+    ///         Replace(Range(1, 2), Range(1, 2)),
+    ///         NoChange(CommonSubsequence(2, 2, 1)),
+    ///     ]
+    /// );
+    /// ```
     pub fn new(before: Seq<String>, after: Seq<String>) -> Self {
         let changes =
             ChangesGenerator::<String, StringItemIndices>::new(&before, &after).generate();
@@ -434,19 +450,13 @@ impl<'a, T: PartialEq + Clone> ChangeClump<'a, T> {
 
     pub fn context_lengths(&self) -> (u8, u8) {
         use Change::NoChange;
-        let start = if let Some(change) = self.first() {
-            match change {
-                NoChange(match_) => match_.len(),
-                _ => 0,
-            }
+        let start = if let Some(NoChange(match_)) = self.first() {
+            match_.len()
         } else {
             0
         };
-        let end = if let Some(op_code) = self.last() {
-            match op_code {
-                NoChange(match_) => match_.len(),
-                _ => 0,
-            }
+        let end = if let Some(NoChange(match_)) = self.last() {
+            match_.len()
         } else {
             0
         };
@@ -560,4 +570,31 @@ impl<T: PartialEq + Clone> Changes<T> {
             stash: None,
         }
     }
+
+    /// The percentage of the items in `before` and `after` that are common to both.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use pw_diff_lib::sequence::*;
+    /// use pw_diff_lib::changes::Changes;
+    ///
+    /// let changes = Changes::<String>::new(Seq::from("A\nB\nC\nD\n"), Seq::from("A\nB\nC\nE\n"));
+    /// assert_eq!(changes.similarity(), 75);
+    /// let changes = Changes::<String>::new(Seq::from("A\nB\n"), Seq::from("C\nD\n"));
+    /// assert_eq!(changes.similarity(), 0);
+    /// ```
+    pub fn similarity(&self) -> u8 {
+        let common: usize = self
+            .changes
+            .iter()
+            .map(|change| match change {
+                Change::NoChange(common_subsequence) => common_subsequence.len(),
+                _ => 0,
+            })
+            .sum();
+        (common * 200)
+            .checked_div(self.before.len() + self.after.len())
+            .map_or(100, |similarity| similarity as u8)
+    }
 }
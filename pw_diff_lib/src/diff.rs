@@ -1,14 +1,48 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
+use std::fs;
 use std::io;
 use std::io::ErrorKind;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
 use crate::byte_diff::{ByteChangeDiff, PathAndBytes};
+use crate::metadata::{is_symlink, path_exists, ModeChange, Modes, Symlink, SymlinkChange};
 use crate::text_diff::{PathAndLines, TextChangeDiff};
 
+/// A file that is renamed (or copied) to `new_path` with any changes to
+/// its content described by `diff`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Relocation<D> {
+    new_path: PathBuf,
+    similarity: u8,
+    diff: D,
+}
+
+impl<D> Relocation<D> {
+    pub fn new(new_path: &Path, similarity: u8, diff: D) -> Self {
+        Self {
+            new_path: new_path.to_path_buf(),
+            similarity,
+            diff,
+        }
+    }
+
+    pub fn new_path(&self) -> &Path {
+        &self.new_path
+    }
+
+    /// The percentage of the content common to the original and the relocated file.
+    pub fn similarity(&self) -> u8 {
+        self.similarity
+    }
+
+    pub fn diff(&self) -> &D {
+        &self.diff
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Diff {
     TextChange(TextChangeDiff),
@@ -17,43 +51,162 @@ pub enum Diff {
     ByteChange(ByteChangeDiff),
     ByteAdd(PathAndBytes),
     ByteRemove(PathAndBytes),
+    TextRename(Relocation<TextChangeDiff>),
+    TextCopy(Relocation<TextChangeDiff>),
+    ByteRename(Relocation<ByteChangeDiff>),
+    ByteCopy(Relocation<ByteChangeDiff>),
+    ModeChange(ModeChange),
+    SymlinkAdd(Symlink),
+    SymlinkRemove(Symlink),
+    SymlinkChange(SymlinkChange),
 }
 
 impl Diff {
     pub fn new(before_file_path: &Path, after_file_path: &Path, context: u8) -> io::Result<Self> {
-        if before_file_path.exists() {
-            if after_file_path.exists() {
-                match TextChangeDiff::new(before_file_path, after_file_path, context) {
-                    Ok(text_change_diff) => Ok(Self::TextChange(text_change_diff)),
-                    Err(_) => Ok(Self::ByteChange(ByteChangeDiff::new(
+        if path_exists(before_file_path) {
+            if path_exists(after_file_path) {
+                match (is_symlink(before_file_path), is_symlink(after_file_path)) {
+                    (true, true) => Ok(Self::SymlinkChange(SymlinkChange::new(
                         before_file_path,
                         after_file_path,
-                        context,
                     )?)),
+                    (false, false) => {
+                        Self::new_content_change(before_file_path, after_file_path, context)
+                    }
+                    _ => Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "Can't compare a symbolic link with a file!",
+                    )),
                 }
             } else {
-                match PathAndLines::new(before_file_path) {
-                    Ok(path_and_lines) => Ok(Self::TextRemove(path_and_lines)),
-                    Err(_) => Ok(Self::ByteRemove(PathAndBytes::new(before_file_path)?)),
-                }
+                Self::new_removal(before_file_path)
             }
-        } else if after_file_path.exists() {
+        } else if path_exists(after_file_path) {
+            Self::new_addition(after_file_path, before_file_path)
+        } else {
+            Err(io::Error::new(
+                ErrorKind::NotFound,
+                "Neither input file exists!",
+            ))
+        }
+    }
+
+    /// A `Diff` that removes the file (or symbolic link) at `path`.
+    pub fn new_removal(path: &Path) -> io::Result<Self> {
+        if is_symlink(path) {
+            Ok(Self::SymlinkRemove(Symlink::new(path)?))
+        } else {
+            match PathAndLines::new(path) {
+                Ok(path_and_lines) => Ok(Self::TextRemove(path_and_lines)),
+                Err(_) => Ok(Self::ByteRemove(PathAndBytes::new(path)?)),
+            }
+        }
+    }
+
+    /// A `Diff` that creates a copy of the file (or symbolic link) at
+    /// `after_file_path` at `path`.
+    pub fn new_addition(after_file_path: &Path, path: &Path) -> io::Result<Self> {
+        if is_symlink(after_file_path) {
+            let mut symlink = Symlink::new(after_file_path)?;
+            symlink.change_path(path);
+            Ok(Self::SymlinkAdd(symlink))
+        } else {
             match PathAndLines::new(after_file_path) {
                 Ok(mut path_and_lines) => {
-                    path_and_lines.change_path(&before_file_path);
+                    path_and_lines.change_path(path);
                     Ok(Self::TextAdd(path_and_lines))
                 }
                 Err(_) => {
                     let mut path_and_bytes = PathAndBytes::new(after_file_path)?;
-                    path_and_bytes.change_path(&before_file_path);
+                    path_and_bytes.change_path(path);
                     Ok(Self::ByteAdd(path_and_bytes))
                 }
             }
-        } else {
-            Err(io::Error::new(
-                ErrorKind::NotFound,
-                "Neither input file exists!",
-            ))
+        }
+    }
+
+    fn new_content_change(
+        before_file_path: &Path,
+        after_file_path: &Path,
+        context: u8,
+    ) -> io::Result<Self> {
+        if let Some(modes) = Modes::of_files(before_file_path, after_file_path)? {
+            if fs::read(before_file_path)? == fs::read(after_file_path)? {
+                return Ok(Self::ModeChange(ModeChange::new(before_file_path, modes)));
+            }
+        }
+        match TextChangeDiff::new(before_file_path, after_file_path, context) {
+            Ok(text_change_diff) => Ok(Self::TextChange(text_change_diff)),
+            Err(_) => Ok(Self::ByteChange(ByteChangeDiff::new(
+                before_file_path,
+                after_file_path,
+                context,
+            )?)),
+        }
+    }
+
+    /// A `Diff` that renames the file at `before_file_path` to `new_path`
+    /// changing its content to that of `after_file_path`.
+    pub fn new_rename(
+        before_file_path: &Path,
+        after_file_path: &Path,
+        new_path: &Path,
+        similarity: u8,
+        context: u8,
+    ) -> io::Result<Self> {
+        match TextChangeDiff::new(before_file_path, after_file_path, context) {
+            Ok(diff) => Ok(Self::TextRename(Relocation::new(
+                new_path, similarity, diff,
+            ))),
+            Err(_) => {
+                let diff = ByteChangeDiff::new(before_file_path, after_file_path, context)?;
+                Ok(Self::ByteRename(Relocation::new(
+                    new_path, similarity, diff,
+                )))
+            }
+        }
+    }
+
+    /// A `Diff` that copies the file at `before_file_path` to `new_path`
+    /// changing the copy's content to that of `after_file_path`.
+    pub fn new_copy(
+        before_file_path: &Path,
+        after_file_path: &Path,
+        new_path: &Path,
+        similarity: u8,
+        context: u8,
+    ) -> io::Result<Self> {
+        match TextChangeDiff::new(before_file_path, after_file_path, context) {
+            Ok(diff) => Ok(Self::TextCopy(Relocation::new(new_path, similarity, diff))),
+            Err(_) => {
+                let diff = ByteChangeDiff::new(before_file_path, after_file_path, context)?;
+                Ok(Self::ByteCopy(Relocation::new(new_path, similarity, diff)))
+            }
+        }
+    }
+
+    /// The paths of the files that applying this diff changes.
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            Self::TextChange(diff) => vec![diff.before_path()],
+            Self::ByteChange(diff) => vec![diff.before_path()],
+            Self::TextAdd(path_and_lines) | Self::TextRemove(path_and_lines) => {
+                vec![path_and_lines.path()]
+            }
+            Self::ByteAdd(path_and_bytes) | Self::ByteRemove(path_and_bytes) => {
+                vec![path_and_bytes.path()]
+            }
+            Self::TextRename(relocation) => {
+                vec![relocation.diff().before_path(), relocation.new_path()]
+            }
+            Self::ByteRename(relocation) => {
+                vec![relocation.diff().before_path(), relocation.new_path()]
+            }
+            Self::TextCopy(relocation) => vec![relocation.new_path()],
+            Self::ByteCopy(relocation) => vec![relocation.new_path()],
+            Self::ModeChange(mode_change) => vec![mode_change.path()],
+            Self::SymlinkAdd(symlink) | Self::SymlinkRemove(symlink) => vec![symlink.path()],
+            Self::SymlinkChange(change) => vec![change.path()],
         }
     }
 
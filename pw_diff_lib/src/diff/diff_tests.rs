@@ -15,7 +15,7 @@ fn diff_clump_applies() {
     );
     let diff_clumps: Vec<TextChangeClump> = changes
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
 
     for diff_clump in diff_clumps.iter() {
@@ -89,7 +89,7 @@ fn find_compromise() {
     );
     let diff_clumps: Vec<TextChangeClump> = changes
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
     let lines = Seq::<String>::from(before_lines);
     let mut pd = ConsumableSeq::new(&lines);
@@ -123,7 +123,7 @@ fn find_compromise_edges() {
     );
     let diff_clumps: Vec<TextChangeClump> = changes
         .change_clumps(2)
-        .map(|c| TextChangeClump::from(c))
+        .map(TextChangeClump::from)
         .collect();
 
     assert_eq!(diff_clumps.len(), 3);
@@ -149,3 +149,59 @@ fn find_compromise_edges() {
         Some((2, WillApply::WithReductions((1, 1))))
     );
 }
+
+#[test]
+fn verify_digests() {
+    use crate::digest::Verification;
+    use std::fs::File;
+    use std::path::Path;
+
+    let before_path = Path::new("../test_files/file_1_original");
+    let after_path = Path::new("../test_files/file_1_modified");
+    let diff = TextChangeDiff::new(before_path, after_path, 2).unwrap();
+    let before_lines = Seq::<String>::read(File::open(before_path).unwrap()).unwrap();
+    let after_lines = Seq::<String>::read(File::open(after_path).unwrap()).unwrap();
+
+    let mut patched = vec![];
+    let (_, verification) = diff
+        .apply_into_verifying(&before_lines, &mut patched, false)
+        .unwrap();
+    assert_eq!(verification, Verification::Exact);
+
+    let mut patched = vec![];
+    let (_, verification) = diff
+        .apply_into_verifying(&after_lines, &mut patched, true)
+        .unwrap();
+    assert_eq!(verification, Verification::Exact);
+
+    let mut text = String::from("Extra line\n");
+    before_lines.iter().for_each(|line| text.push_str(line));
+    let mut patched = vec![];
+    let (report, verification) = diff
+        .apply_into_verifying(&Seq::<String>::from(text), &mut patched, false)
+        .unwrap();
+    assert!(report.succeeded());
+    assert_eq!(verification, Verification::FuzzyResultDiffers);
+
+    let mut patched = vec![];
+    let (_, verification) = diff
+        .apply_into_verifying(&Seq::<String>::from("A\nB\nC\n"), &mut patched, false)
+        .unwrap();
+    assert_eq!(verification, Verification::WrongBase);
+
+    // A diff parsed from a patch in another format has no digests.
+    let changes = Changes::<String>::new(
+        Seq::<String>::read(File::open(before_path).unwrap()).unwrap(),
+        after_lines,
+    );
+    let clumps = changes
+        .change_clumps(2)
+        .map(TextChangeClump::from)
+        .collect();
+    let diff = TextChangeDiff::from_clumps(before_path, after_path, clumps);
+    let mut patched = vec![];
+    let (_, verification) = diff
+        .apply_into_verifying(&before_lines, &mut patched, false)
+        .unwrap();
+    assert_eq!(verification, Verification::Unverifiable);
+}
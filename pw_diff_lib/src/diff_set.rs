@@ -0,0 +1,141 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::diff::Diff;
+
+/// An ordered series of `Diff`s (e.g. for a change touching several files)
+/// together with metadata describing the change as a whole.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DiffSet {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    date: Option<String>,
+    diffs: Vec<Diff>,
+}
+
+impl From<Diff> for DiffSet {
+    fn from(diff: Diff) -> Self {
+        Self {
+            diffs: vec![diff],
+            ..Self::default()
+        }
+    }
+}
+
+impl From<Vec<Diff>> for DiffSet {
+    fn from(diffs: Vec<Diff>) -> Self {
+        Self {
+            diffs,
+            ..Self::default()
+        }
+    }
+}
+
+impl DiffSet {
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.to_string()
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn set_author(&mut self, author: Option<&str>) {
+        self.author = author.map(|s| s.to_string())
+    }
+
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    pub fn set_date(&mut self, date: Option<&str>) {
+        self.date = date.map(|s| s.to_string())
+    }
+
+    pub fn push(&mut self, diff: Diff) {
+        self.diffs.push(diff)
+    }
+
+    pub fn diffs(&self) -> impl DoubleEndedIterator<Item = &Diff> {
+        self.diffs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diffs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diffs.is_empty()
+    }
+
+    /// Read a `DiffSet` or, for compatibility, a single `Diff` (which is
+    /// converted into a `DiffSet` containing only that `Diff`).
+    pub fn from_reader<R: io::Read>(reader: &mut R) -> Result<Self, serde_json::Error> {
+        let mut text = String::new();
+        reader
+            .read_to_string(&mut text)
+            .map_err(serde_json::Error::io)?;
+        match serde_json::from_str::<Self>(&text) {
+            Ok(diff_set) => Ok(diff_set),
+            Err(err) => match serde_json::from_str::<Diff>(&text) {
+                Ok(diff) => Ok(Self::from(diff)),
+                Err(_) => Err(err),
+            },
+        }
+    }
+
+    pub fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+#[cfg(test)]
+mod diff_set_tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn round_trip() {
+        let diff = Diff::new(
+            Path::new("../test_files/file_1_original"),
+            Path::new("../test_files/file_1_modified"),
+            2,
+        )
+        .unwrap();
+        let mut diff_set = DiffSet::from(diff);
+        diff_set.set_description("A test change");
+        diff_set.set_author(Some("A. Author <author@example.com>"));
+        let mut json = vec![];
+        diff_set.to_writer(&mut json).unwrap();
+        let read_back = DiffSet::from_reader(&mut &json[..]).unwrap();
+        assert_eq!(read_back.description(), "A test change");
+        assert_eq!(read_back.author(), Some("A. Author <author@example.com>"));
+        assert_eq!(read_back.date(), None);
+        assert_eq!(read_back.len(), 1);
+    }
+
+    #[test]
+    fn read_single_diff() {
+        let diff = Diff::new(
+            Path::new("../test_files/file_1_original"),
+            Path::new("../test_files/file_1_modified"),
+            2,
+        )
+        .unwrap();
+        let mut json = vec![];
+        diff.to_writer(&mut json).unwrap();
+        let diff_set = DiffSet::from_reader(&mut &json[..]).unwrap();
+        assert_eq!(diff_set.len(), 1);
+        assert_eq!(diff_set.description(), "");
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::fmt;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::apply_text::ApplyReport;
+use crate::sequence::Seq;
+
+/// A (hex encoded) SHA-256 digest of a file's content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Digest(String);
+
+impl Digest {
+    fn from_hasher(hasher: Sha256) -> Self {
+        Self(
+            hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&[u8]> for Digest {
+    fn from(bytes: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        Self::from_hasher(hasher)
+    }
+}
+
+impl From<&Seq<u8>> for Digest {
+    fn from(bytes: &Seq<u8>) -> Self {
+        Self::from(&bytes[..])
+    }
+}
+
+impl From<&Seq<String>> for Digest {
+    /// Example:
+    /// ```
+    /// use pw_diff_lib::digest::Digest;
+    /// use pw_diff_lib::sequence::Seq;
+    /// let text = "A\nB\nC";
+    /// assert_eq!(Digest::from(&Seq::<String>::from(text)), Digest::from(text.as_bytes()));
+    /// ```
+    fn from(lines: &Seq<String>) -> Self {
+        let mut hasher = Sha256::new();
+        for line in lines.iter() {
+            hasher.update(line.as_bytes());
+        }
+        Self::from_hasher(hasher)
+    }
+}
+
+/// A writer that calculates the digest of everything written through it.
+pub struct DigestWriter<'a, W: io::Write> {
+    writer: &'a mut W,
+    hasher: Sha256,
+}
+
+impl<'a, W: io::Write> DigestWriter<'a, W> {
+    pub fn new(writer: &'a mut W) -> Self {
+        Self {
+            writer,
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn digest(self) -> Digest {
+        Digest::from_hasher(self.hasher)
+    }
+}
+
+impl<W: io::Write> io::Write for DigestWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// The before and after digests recorded in a diff when it was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Digests {
+    before: Digest,
+    after: Digest,
+}
+
+impl Digests {
+    pub fn new(before: Digest, after: Digest) -> Self {
+        Self { before, after }
+    }
+
+    pub fn before(&self, reverse: bool) -> &Digest {
+        if reverse {
+            &self.after
+        } else {
+            &self.before
+        }
+    }
+
+    pub fn after(&self, reverse: bool) -> &Digest {
+        self.before(!reverse)
+    }
+
+    /// Classify the result of an application given the digests of the file
+    /// that was patched (`base`) and of the `result` and how its clumps
+    /// applied.
+    pub fn verify(
+        &self,
+        base: &Digest,
+        result: &Digest,
+        report: &ApplyReport,
+        reverse: bool,
+    ) -> Verification {
+        if result == self.after(reverse) {
+            Verification::Exact
+        } else if base != self.before(reverse) && !report.succeeded() {
+            Verification::WrongBase
+        } else {
+            Verification::FuzzyResultDiffers
+        }
+    }
+}
+
+/// How the result of applying a diff compares to the result its author got.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Verification {
+    /// The result is identical to the author's.
+    Exact,
+    /// Every clump was applied (e.g. at an offset) but the result differs
+    /// from the author's.
+    FuzzyResultDiffers,
+    /// The patched file is not the one the diff was made from and some
+    /// clumps could not be applied.
+    WrongBase,
+    /// The diff carries no digests so nothing could be verified.
+    Unverifiable,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Verification::*;
+        match self {
+            Exact => write!(f, "exact"),
+            FuzzyResultDiffers => write!(f, "fuzzy but result differs"),
+            WrongBase => write!(f, "wrong base"),
+            Unverifiable => write!(f, "unverifiable"),
+        }
+    }
+}
@@ -0,0 +1,500 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+
+use crate::changes::Changes;
+use crate::diff::Diff;
+use crate::diff_set::DiffSet;
+use crate::metadata::{is_symlink, path_exists, Modes};
+use crate::sequence::Seq;
+
+/// Decide which files take part in a directory comparison.
+#[derive(Debug)]
+pub struct PathFilter {
+    includes: Option<GlobSet>,
+    excludes: GlobSet,
+    honour_ignore_files: bool,
+}
+
+impl Default for PathFilter {
+    fn default() -> Self {
+        Self {
+            includes: None,
+            excludes: GlobSet::empty(),
+            honour_ignore_files: true,
+        }
+    }
+}
+
+fn glob_set(patterns: &[String]) -> io::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err.to_string()))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err.to_string()))
+}
+
+impl PathFilter {
+    /// Files (relative to the directory being compared) are included if they
+    /// match any of `includes` (or `includes` is empty) and don't match any
+    /// of `excludes`.  Directories that match `excludes` are not entered.
+    /// If `honour_ignore_files` is true, files ignored by `.gitignore` or
+    /// `.ignore` files within the directory are skipped as is `.git`.
+    pub fn new(
+        includes: &[String],
+        excludes: &[String],
+        honour_ignore_files: bool,
+    ) -> io::Result<Self> {
+        let includes = if includes.is_empty() {
+            None
+        } else {
+            Some(glob_set(includes)?)
+        };
+        Ok(Self {
+            includes,
+            excludes: glob_set(excludes)?,
+            honour_ignore_files,
+        })
+    }
+
+    fn includes(&self, rel_path: &Path) -> bool {
+        if let Some(includes) = &self.includes {
+            includes.is_match(rel_path)
+        } else {
+            true
+        }
+    }
+
+    /// The sorted relative paths of the regular files and symbolic links in
+    /// `dir` that pass this filter.
+    pub fn file_paths(&self, dir: &Path) -> io::Result<BTreeSet<PathBuf>> {
+        let mut builder = WalkBuilder::new(dir);
+        builder
+            .standard_filters(false)
+            .follow_links(false)
+            .sort_by_file_name(|a, b| a.cmp(b));
+        if self.honour_ignore_files {
+            builder.git_ignore(true).ignore(true).require_git(false);
+        }
+        let root = dir.to_path_buf();
+        let excludes = self.excludes.clone();
+        let honour_ignore_files = self.honour_ignore_files;
+        builder.filter_entry(move |entry| {
+            if honour_ignore_files && entry.file_name() == ".git" {
+                false
+            } else if let Ok(rel_path) = entry.path().strip_prefix(&root) {
+                rel_path.as_os_str().is_empty() || !excludes.is_match(rel_path)
+            } else {
+                true
+            }
+        });
+        let mut paths = BTreeSet::new();
+        for entry in builder.build() {
+            let entry = entry.map_err(|err| io::Error::other(err.to_string()))?;
+            if entry
+                .file_type()
+                .is_some_and(|ft| ft.is_file() || ft.is_symlink())
+            {
+                if let Ok(rel_path) = entry.path().strip_prefix(dir) {
+                    if self.includes(rel_path) {
+                        paths.insert(rel_path.to_path_buf());
+                    }
+                }
+            }
+        }
+        Ok(paths)
+    }
+}
+
+fn same_content(before_path: &Path, after_path: &Path) -> io::Result<bool> {
+    match (is_symlink(before_path), is_symlink(after_path)) {
+        (true, true) => Ok(fs::read_link(before_path)? == fs::read_link(after_path)?),
+        (false, false) => {
+            if !before_path.is_file()
+                || !after_path.is_file()
+                || fs::metadata(before_path)?.len() != fs::metadata(after_path)?.len()
+                || Modes::of_files(before_path, after_path)?.is_some()
+            {
+                Ok(false)
+            } else {
+                Ok(fs::read(before_path)? == fs::read(after_path)?)
+            }
+        }
+        _ => Ok(false),
+    }
+}
+
+// Whether there's a file or symbolic link (rather than nothing or a
+// directory) at `path`.
+fn is_file_or_link(path: &Path) -> bool {
+    path_exists(path) && (is_symlink(path) || !path.is_dir())
+}
+
+/// Control the detection of renamed and copied files in directory comparisons.
+#[derive(Debug, Clone, Copy)]
+pub struct RenameDetection {
+    /// The minimum similarity (percentage of common content) for a file to
+    /// be considered to be a renamed or copied version of another.
+    pub threshold: u8,
+    /// Also look for added files that are copies of files that still exist.
+    pub find_copies: bool,
+}
+
+impl Default for RenameDetection {
+    fn default() -> Self {
+        Self {
+            threshold: 50,
+            find_copies: false,
+        }
+    }
+}
+
+/// The similarity of the content of two files (as a percentage) or zero if
+/// their sizes alone show that it would be less than `threshold`.
+fn similarity(before_path: &Path, after_path: &Path, threshold: u8) -> io::Result<u8> {
+    let before_len = fs::metadata(before_path)?.len();
+    let after_len = fs::metadata(after_path)?.len();
+    let total = before_len + after_len;
+    if total > 0 && before_len.min(after_len) * 200 / total < threshold as u64 {
+        return Ok(0);
+    }
+    let before_lines = File::open(before_path).and_then(Seq::<String>::read);
+    let after_lines = File::open(after_path).and_then(Seq::<String>::read);
+    match (before_lines, after_lines) {
+        (Ok(before_lines), Ok(after_lines)) => {
+            Ok(Changes::<String>::new(before_lines, after_lines).similarity())
+        }
+        _ => {
+            let before_bytes = File::open(before_path).and_then(Seq::<u8>::read)?;
+            let after_bytes = File::open(after_path).and_then(Seq::<u8>::read)?;
+            Ok(Changes::<u8>::new(before_bytes, after_bytes).similarity())
+        }
+    }
+}
+
+/// The most similar of `candidates` to `after_path` (and its similarity)
+/// provided that similarity is at least `threshold`.
+fn most_similar<'a>(
+    candidates: impl Iterator<Item = &'a PathBuf>,
+    before_dir: &Path,
+    after_path: &Path,
+    threshold: u8,
+) -> io::Result<Option<(&'a PathBuf, u8)>> {
+    let mut best: Option<(&PathBuf, u8)> = None;
+    for candidate in candidates {
+        let score = similarity(&before_dir.join(candidate), after_path, threshold)?;
+        if score >= threshold && best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((candidate, score));
+        }
+    }
+    Ok(best)
+}
+
+impl DiffSet {
+    /// Compare the files in two directory trees (paired by their paths
+    /// relative to `before_dir` and `after_dir`) and create a `DiffSet` with
+    /// a `Diff` for each file that differs.  If `renames_wanted` is given, added
+    /// files that are sufficiently similar to a removed (or, optionally,
+    /// surviving) file are described as a rename (or copy) of that file.
+    /// Copies come first so that they are made before their originals change.
+    pub fn from_dirs(
+        before_dir: &Path,
+        after_dir: &Path,
+        context: u8,
+        filter: &PathFilter,
+        renames_wanted: Option<RenameDetection>,
+    ) -> io::Result<Self> {
+        let mut rel_paths = filter.file_paths(before_dir)?;
+        rel_paths.append(&mut filter.file_paths(after_dir)?);
+        let mut removed = BTreeSet::new();
+        let mut added = vec![];
+        let mut survivors = vec![];
+        let mut differing = BTreeSet::new();
+        for rel_path in rel_paths.iter() {
+            let before_path = before_dir.join(rel_path);
+            let after_path = after_dir.join(rel_path);
+            if !same_content(&before_path, &after_path)? {
+                differing.insert(rel_path);
+            }
+            // Only regular files take part in rename detection.
+            let is_file = |path: &Path| path.is_file() && !is_symlink(path);
+            match (is_file_or_link(&before_path), is_file_or_link(&after_path)) {
+                (true, false) if is_file(&before_path) => {
+                    removed.insert(rel_path);
+                }
+                (false, true) if is_file(&after_path) => added.push(rel_path),
+                (true, true) if is_file(&before_path) && is_file(&after_path) => {
+                    survivors.push(rel_path)
+                }
+                _ => (),
+            }
+        }
+
+        let mut copies = vec![];
+        let mut renames = BTreeMap::new();
+        let mut relocated = BTreeSet::new();
+        if let Some(detection) = renames_wanted {
+            for rel_path in added {
+                let after_path = after_dir.join(rel_path);
+                let new_path = before_dir.join(rel_path);
+                let rename = most_similar(
+                    removed.iter().copied(),
+                    before_dir,
+                    &after_path,
+                    detection.threshold,
+                )?;
+                let copy = if detection.find_copies {
+                    most_similar(
+                        survivors.iter().copied(),
+                        before_dir,
+                        &after_path,
+                        detection.threshold,
+                    )?
+                } else {
+                    None
+                };
+                match (rename, copy) {
+                    (Some((source, score)), copy)
+                        if copy.is_none_or(|(_, copy_score)| score >= copy_score) =>
+                    {
+                        let diff = Diff::new_rename(
+                            &before_dir.join(source),
+                            &after_path,
+                            &new_path,
+                            score,
+                            context,
+                        )?;
+                        removed.remove(source);
+                        relocated.insert(source);
+                        renames.insert(rel_path, diff);
+                    }
+                    (_, Some((source, score))) => {
+                        let diff = Diff::new_copy(
+                            &before_dir.join(source),
+                            &after_path,
+                            &new_path,
+                            score,
+                            context,
+                        )?;
+                        relocated.insert(rel_path);
+                        copies.push(diff);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let mut diff_set = DiffSet::from(copies);
+        for rel_path in rel_paths.iter() {
+            if let Some(diff) = renames.remove(rel_path) {
+                diff_set.push(diff);
+            } else if differing.contains(rel_path) && !relocated.contains(rel_path) {
+                let before_path = before_dir.join(rel_path);
+                let after_path = after_dir.join(rel_path);
+                // A file replaced by a directory (or vice versa) is removed
+                // or added and the directory's files are dealt with in
+                // their own right.
+                match (is_file_or_link(&before_path), is_file_or_link(&after_path)) {
+                    (true, false) => diff_set.push(Diff::new_removal(&before_path)?),
+                    (false, true) => diff_set.push(Diff::new_addition(&after_path, &before_path)?),
+                    (true, true) if is_symlink(&before_path) != is_symlink(&after_path) => {
+                        // A file replaced by a symbolic link (or vice versa).
+                        diff_set.push(Diff::new_removal(&before_path)?);
+                        diff_set.push(Diff::new_addition(&after_path, &before_path)?);
+                    }
+                    _ => diff_set.push(Diff::new(&before_path, &after_path, context)?),
+                }
+            }
+        }
+        Ok(diff_set)
+    }
+}
+
+#[cfg(test)]
+mod dir_diff_tests {
+    use super::*;
+
+    fn populate(dir: &Path, files: &[(&str, &str)]) {
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    #[test]
+    fn compare_dirs() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let before_dir = temp_dir.path().join("a");
+        let after_dir = temp_dir.path().join("b");
+        populate(
+            &before_dir,
+            &[
+                (".gitignore", "target/\n"),
+                ("same.txt", "same\n"),
+                ("src/changed.rs", "fn main() {}\n"),
+                ("src/removed.rs", "gone\n"),
+                ("target/junk.o", "junk\n"),
+                ("notes.md", "notes\n"),
+            ],
+        );
+        populate(
+            &after_dir,
+            &[
+                (".gitignore", "target/\n"),
+                ("same.txt", "same\n"),
+                ("src/changed.rs", "fn main() {\n}\n"),
+                ("src/added.rs", "new\n"),
+                ("target/junk.o", "other junk\n"),
+                ("notes.md", "more notes\n"),
+            ],
+        );
+
+        let filter = PathFilter::default();
+        let diff_set = DiffSet::from_dirs(&before_dir, &after_dir, 2, &filter, None).unwrap();
+        let variants: Vec<&str> = diff_set
+            .diffs()
+            .map(|diff| match diff {
+                Diff::TextChange(_) => "TextChange",
+                Diff::TextAdd(_) => "TextAdd",
+                Diff::TextRemove(_) => "TextRemove",
+                _ => "Other",
+            })
+            .collect();
+        assert_eq!(
+            variants,
+            vec!["TextChange", "TextAdd", "TextChange", "TextRemove"]
+        );
+
+        let filter =
+            PathFilter::new(&["src/**".to_string()], &["*added*".to_string()], false).unwrap();
+        let diff_set = DiffSet::from_dirs(&before_dir, &after_dir, 2, &filter, None).unwrap();
+        assert_eq!(diff_set.len(), 2);
+
+        let filter = PathFilter::new(&[], &[], false).unwrap();
+        let diff_set = DiffSet::from_dirs(&before_dir, &after_dir, 2, &filter, None).unwrap();
+        assert_eq!(diff_set.len(), 5);
+
+        let filter = PathFilter::new(&[], &["target".to_string()], false).unwrap();
+        let diff_set = DiffSet::from_dirs(&before_dir, &after_dir, 2, &filter, None).unwrap();
+        assert_eq!(diff_set.len(), 4);
+    }
+
+    #[test]
+    fn detect_renames_and_copies() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let before_dir = temp_dir.path().join("a");
+        let after_dir = temp_dir.path().join("b");
+        let content = "A\nB\nC\nD\nE\nF\nG\nH\n";
+        populate(
+            &before_dir,
+            &[
+                ("old.txt", content),
+                ("kept.txt", "1\n2\n3\n4\n5\n6\n"),
+                ("gone.txt", "nothing like the others\n"),
+            ],
+        );
+        populate(
+            &after_dir,
+            &[
+                ("new.txt", "A\nB\nC\nD\nE\nF\nG\nX\n"),
+                ("kept.txt", "1\n2\n3\n4\n5\n6\n"),
+                ("kept_copy.txt", "1\n2\n3\n4\n5\n6\n7\n"),
+            ],
+        );
+
+        let filter = PathFilter::default();
+        let diff_set = DiffSet::from_dirs(&before_dir, &after_dir, 2, &filter, None).unwrap();
+        assert_eq!(diff_set.len(), 4);
+
+        let detection = RenameDetection::default();
+        let diff_set =
+            DiffSet::from_dirs(&before_dir, &after_dir, 2, &filter, Some(detection)).unwrap();
+        let variants: Vec<&str> = diff_set
+            .diffs()
+            .map(|diff| match diff {
+                Diff::TextRename(relocation) => {
+                    assert_eq!(relocation.new_path(), before_dir.join("new.txt"));
+                    assert_eq!(relocation.diff().before_path(), before_dir.join("old.txt"));
+                    assert_eq!(relocation.similarity(), 87);
+                    "TextRename"
+                }
+                Diff::TextAdd(_) => "TextAdd",
+                Diff::TextRemove(_) => "TextRemove",
+                _ => "Other",
+            })
+            .collect();
+        assert_eq!(variants, vec!["TextRemove", "TextAdd", "TextRename"]);
+
+        let detection = RenameDetection {
+            threshold: 50,
+            find_copies: true,
+        };
+        let diff_set =
+            DiffSet::from_dirs(&before_dir, &after_dir, 2, &filter, Some(detection)).unwrap();
+        let variants: Vec<&str> = diff_set
+            .diffs()
+            .map(|diff| match diff {
+                Diff::TextCopy(relocation) => {
+                    assert_eq!(relocation.new_path(), before_dir.join("kept_copy.txt"));
+                    assert_eq!(relocation.diff().before_path(), before_dir.join("kept.txt"));
+                    "TextCopy"
+                }
+                Diff::TextRename(_) => "TextRename",
+                Diff::TextRemove(_) => "TextRemove",
+                _ => "Other",
+            })
+            .collect();
+        assert_eq!(variants, vec!["TextCopy", "TextRemove", "TextRename"]);
+    }
+
+    #[test]
+    fn file_replaced_by_directory() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let before_dir = temp_dir.path().join("a");
+        let after_dir = temp_dir.path().join("b");
+        populate(&before_dir, &[("x", "file\n"), ("y/z", "nested\n")]);
+        populate(&after_dir, &[("x/w", "nested\n"), ("y", "file\n")]);
+
+        let filter = PathFilter::default();
+        for (before_dir, after_dir) in [(&before_dir, &after_dir), (&after_dir, &before_dir)] {
+            let diff_set = DiffSet::from_dirs(before_dir, after_dir, 2, &filter, None).unwrap();
+            let diffs: Vec<(&str, PathBuf)> = diff_set
+                .diffs()
+                .map(|diff| match diff {
+                    Diff::TextAdd(_) => ("TextAdd", diff.paths()[0].to_path_buf()),
+                    Diff::TextRemove(_) => ("TextRemove", diff.paths()[0].to_path_buf()),
+                    _ => ("Other", diff.paths()[0].to_path_buf()),
+                })
+                .collect();
+            let expected = if before_dir.ends_with("a") {
+                vec![
+                    ("TextRemove", before_dir.join("x")),
+                    ("TextAdd", before_dir.join("x/w")),
+                    ("TextAdd", before_dir.join("y")),
+                    ("TextRemove", before_dir.join("y/z")),
+                ]
+            } else {
+                vec![
+                    ("TextAdd", before_dir.join("x")),
+                    ("TextRemove", before_dir.join("x/w")),
+                    ("TextRemove", before_dir.join("y")),
+                    ("TextAdd", before_dir.join("y/z")),
+                ]
+            };
+            assert_eq!(diffs, expected);
+        }
+    }
+}
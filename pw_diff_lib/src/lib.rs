@@ -1,21 +1,29 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
 pub mod apply_bytes;
+pub mod apply_clump;
 pub mod apply_text;
+pub mod backup;
 // pub mod apply_text_copy;
 pub mod byte_diff;
 pub mod common_subsequence;
+pub mod digest;
 //pub mod data;
 // pub mod apply_bytes_copy;
 // pub mod byte_diff_copy;
 pub mod diff;
+pub mod diff_set;
+pub mod dir_diff;
 // pub mod diff_copy;
 pub mod changes;
 // pub mod modifications_copy;
+pub mod metadata;
+pub mod paths;
 pub mod range;
 pub mod sequence;
 pub mod snippet;
 pub mod text_diff;
+pub mod transaction;
 // pub mod text_diff_copy;
 
 // pub use apply_bytes::*;
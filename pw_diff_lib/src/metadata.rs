@@ -0,0 +1,282 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// The permission bits (including set-id and sticky bits) of the file at
+/// `path` or `None` on platforms without Unix style modes.
+pub fn file_mode(path: &Path) -> io::Result<Option<u32>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(Some(fs::metadata(path)?.permissions().mode() & 0o7777))
+    }
+    #[cfg(not(unix))]
+    {
+        fs::metadata(path)?;
+        Ok(None)
+    }
+}
+
+/// Set the permission bits of the file at `path` (a no-op on platforms
+/// without Unix style modes).
+pub fn set_file_mode(path: &Path, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+/// Whether there is a file, directory or symbolic link (dangling or not) at `path`.
+pub fn path_exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Create a symbolic link at `path` pointing to `target`.
+pub fn create_symlink(target: &Path, path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, path)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (target, path);
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "symbolic links are not supported on this platform",
+        ))
+    }
+}
+
+/// The before and after permission bits of a file whose mode changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modes {
+    before: u32,
+    after: u32,
+}
+
+impl Modes {
+    pub fn new(before: u32, after: u32) -> Self {
+        Self { before, after }
+    }
+
+    /// The `Modes` of the two files if they differ.
+    pub fn of_files(before_file_path: &Path, after_file_path: &Path) -> io::Result<Option<Self>> {
+        match (file_mode(before_file_path)?, file_mode(after_file_path)?) {
+            (Some(before), Some(after)) if before != after => Ok(Some(Self::new(before, after))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn before(&self, reverse: bool) -> u32 {
+        if reverse {
+            self.after
+        } else {
+            self.before
+        }
+    }
+
+    pub fn after(&self, reverse: bool) -> u32 {
+        self.before(!reverse)
+    }
+}
+
+/// A change to a file's permissions without any change to its content.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModeChange {
+    path: PathBuf,
+    modes: Modes,
+}
+
+impl ModeChange {
+    pub fn new(path: &Path, modes: Modes) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modes,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn modes(&self) -> &Modes {
+        &self.modes
+    }
+
+    /// Check that a file whose mode is `mode` (`None` if unknown) has the
+    /// mode it had before the change.
+    pub fn check(&self, mode: Option<u32>, reverse: bool) -> io::Result<()> {
+        match mode {
+            Some(mode) if mode != self.modes.before(reverse) => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{:?}: mode is {mode:o} rather than {:o}",
+                    self.path,
+                    self.modes.before(reverse)
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Set the file's mode to that after the change (or before it if `reverse`)
+    /// provided that it currently has the mode it had before the change.
+    pub fn apply(&self, reverse: bool) -> io::Result<()> {
+        self.check(file_mode(&self.path)?, reverse)?;
+        set_file_mode(&self.path, self.modes.after(reverse))
+    }
+}
+
+/// A symbolic link and its target.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Symlink {
+    path: PathBuf,
+    target: PathBuf,
+}
+
+impl Symlink {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            target: fs::read_link(path)?,
+        })
+    }
+
+    pub fn from_target(path: &Path, target: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            target: target.to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn change_path(&mut self, new_path: &Path) {
+        self.path = new_path.to_path_buf()
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn create(&self) -> io::Result<()> {
+        create_symlink(&self.target, &self.path)
+    }
+}
+
+/// A symbolic link whose target changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SymlinkChange {
+    path: PathBuf,
+    before_target: PathBuf,
+    after_target: PathBuf,
+}
+
+impl SymlinkChange {
+    pub fn new(before_file_path: &Path, after_file_path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: before_file_path.to_path_buf(),
+            before_target: fs::read_link(before_file_path)?,
+            after_target: fs::read_link(after_file_path)?,
+        })
+    }
+
+    pub fn from_targets(path: &Path, before_target: &Path, after_target: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            before_target: before_target.to_path_buf(),
+            after_target: after_target.to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn before_target(&self, reverse: bool) -> &Path {
+        if reverse {
+            &self.after_target
+        } else {
+            &self.before_target
+        }
+    }
+
+    pub fn after_target(&self, reverse: bool) -> &Path {
+        self.before_target(!reverse)
+    }
+
+    /// Whether the link currently points where it did before the change.
+    pub fn will_apply(&self, reverse: bool) -> bool {
+        fs::read_link(&self.path).is_ok_and(|target| target == self.before_target(reverse))
+    }
+
+    /// Point the link at its new target.
+    pub fn apply(&self, reverse: bool) -> io::Result<()> {
+        if self.will_apply(reverse) {
+            fs::remove_file(&self.path)?;
+            create_symlink(self.after_target(reverse), &self.path)
+        } else {
+            Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{:?} is not a symbolic link to {:?}",
+                    self.path,
+                    self.before_target(reverse)
+                ),
+            ))
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod metadata_tests {
+    use super::*;
+
+    #[test]
+    fn modes_and_symlinks() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("file");
+        fs::write(&file_path, "content\n").unwrap();
+        set_file_mode(&file_path, 0o644).unwrap();
+        let mode_change = ModeChange::new(&file_path, Modes::new(0o644, 0o755));
+        mode_change.apply(false).unwrap();
+        assert_eq!(file_mode(&file_path).unwrap(), Some(0o755));
+        assert!(mode_change.apply(false).is_err());
+        assert_eq!(file_mode(&file_path).unwrap(), Some(0o755));
+        mode_change.apply(true).unwrap();
+        assert_eq!(file_mode(&file_path).unwrap(), Some(0o644));
+        assert!(mode_change.apply(true).is_err());
+
+        let link_path = temp_dir.path().join("link");
+        let other_path = temp_dir.path().join("other");
+        create_symlink(Path::new("file"), &link_path).unwrap();
+        create_symlink(Path::new("dangling"), &other_path).unwrap();
+        assert!(is_symlink(&other_path) && path_exists(&other_path) && !other_path.exists());
+        let change = SymlinkChange::new(&link_path, &other_path).unwrap();
+        change.apply(false).unwrap();
+        assert_eq!(fs::read_link(&link_path).unwrap(), Path::new("dangling"));
+        assert!(change.apply(false).is_err());
+        change.apply(true).unwrap();
+        assert_eq!(
+            Symlink::new(&link_path).unwrap().target(),
+            Path::new("file")
+        );
+    }
+}
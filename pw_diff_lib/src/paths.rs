@@ -0,0 +1,107 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+use crate::metadata::is_symlink;
+
+/// `path` without its first `strip` components (as for `patch -p`).
+///
+/// Example:
+/// ```
+/// use std::path::Path;
+/// use pw_diff_lib::paths::strip_path;
+/// assert_eq!(strip_path(Path::new("a/src/lib.rs"), 1).unwrap(), Path::new("src/lib.rs"));
+/// assert_eq!(strip_path(Path::new("/tmp/a/lib.rs"), 2).unwrap(), Path::new("a/lib.rs"));
+/// assert!(strip_path(Path::new("a/lib.rs"), 2).is_err());
+/// ```
+pub fn strip_path(path: &Path, strip: usize) -> io::Result<PathBuf> {
+    let stripped: PathBuf = path.components().skip(strip).collect();
+    if stripped.as_os_str().is_empty() {
+        Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{path:?}: can't strip {strip} component(s)"),
+        ))
+    } else {
+        Ok(stripped)
+    }
+}
+
+/// `path` with `.` and `..` components removed, provided that it is a
+/// relative path that stays within `root` and (unless `follow_symlinks`)
+/// does not pass through any symbolic links below `root`.  The last
+/// component may itself be a symbolic link as it is never followed.
+///
+/// Example:
+/// ```
+/// use std::path::Path;
+/// use pw_diff_lib::paths::confined_path;
+/// let root = Path::new(".");
+/// assert_eq!(confined_path(root, Path::new("./a/../b/c"), false).unwrap(), Path::new("b/c"));
+/// assert!(confined_path(root, Path::new("a/../../etc/passwd"), false).is_err());
+/// assert!(confined_path(root, Path::new("/etc/passwd"), false).is_err());
+/// ```
+pub fn confined_path(root: &Path, path: &Path, follow_symlinks: bool) -> io::Result<PathBuf> {
+    let unsafe_path = |reason: &str| {
+        io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{path:?}: unsafe path: {reason}"),
+        )
+    };
+    let mut confined = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                return Err(unsafe_path("absolute paths are not allowed"))
+            }
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !confined.pop() {
+                    return Err(unsafe_path("it leads outside the directory being patched"));
+                }
+            }
+            Component::Normal(name) => confined.push(name),
+        }
+    }
+    if confined.as_os_str().is_empty() {
+        return Err(unsafe_path("it does not name a file"));
+    }
+    if !follow_symlinks {
+        for ancestor in confined.ancestors().skip(1) {
+            if !ancestor.as_os_str().is_empty() && is_symlink(&root.join(ancestor)) {
+                return Err(unsafe_path(&format!(
+                    "it passes through the symbolic link {ancestor:?}"
+                )));
+            }
+        }
+    }
+    Ok(confined)
+}
+
+#[cfg(all(test, unix))]
+mod paths_tests {
+    use super::*;
+    use crate::metadata::create_symlink;
+
+    #[test]
+    fn symlinks_are_not_followed() {
+        let temp_dir = temp_dir::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("dir")).unwrap();
+        create_symlink(Path::new("/etc"), &root.join("dir/link")).unwrap();
+        let through_link = Path::new("dir/link/passwd");
+        let err = confined_path(root, through_link, false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("symbolic link"));
+        assert_eq!(
+            confined_path(root, through_link, true).unwrap(),
+            through_link
+        );
+        assert_eq!(
+            confined_path(root, Path::new("dir/link"), false).unwrap(),
+            Path::new("dir/link")
+        );
+        assert!(confined_path(root, Path::new("dir/.."), false).is_err());
+    }
+}
@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::apply_text::*;
 use crate::changes::*;
+use crate::digest::{Digest, DigestWriter, Digests, Verification};
+use crate::metadata::{file_mode, Modes};
 use crate::range::Range;
 use crate::sequence::*;
 use crate::snippet::Snippet;
@@ -16,6 +18,8 @@ pub struct TextChangeClump {
     context_lengths: (u8, u8),
     before: Snippet<String>,
     after: Snippet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    heading: Option<String>,
 }
 
 impl From<ChangeClump<'_, String>> for TextChangeClump {
@@ -26,6 +30,7 @@ impl From<ChangeClump<'_, String>> for TextChangeClump {
             context_lengths: change_clump.context_lengths(),
             before: change_clump.before.extract_snippet(before_range),
             after: change_clump.after.extract_snippet(after_range),
+            heading: None,
         }
     }
 }
@@ -60,9 +65,28 @@ impl TextClumpBasics for TextChangeClump {
             self.before.items(range)
         }
     }
+
+    fn heading(&self) -> Option<&str> {
+        self.heading.as_deref()
+    }
 }
 
 impl TextChangeClump {
+    pub fn new(before: Snippet<String>, after: Snippet<String>, context_lengths: (u8, u8)) -> Self {
+        Self {
+            context_lengths,
+            before,
+            after,
+            heading: None,
+        }
+    }
+
+    /// Set the text (e.g. a function's first line) shown in reports about
+    /// this clump.
+    pub fn set_heading(&mut self, heading: Option<String>) {
+        self.heading = heading;
+    }
+
     pub fn before(&self, reverse: bool) -> &Snippet<String> {
         if reverse {
             &self.after
@@ -86,6 +110,10 @@ impl ApplyClumpFuzzy for TextChangeClump {}
 pub struct TextChangeDiff {
     before_path: PathBuf,
     after_path: PathBuf,
+    #[serde(default)]
+    digests: Option<Digests>,
+    #[serde(default)]
+    modes: Option<Modes>,
     clumps: Vec<TextChangeClump>,
 }
 
@@ -93,18 +121,37 @@ impl TextChangeDiff {
     pub fn new(before_file_path: &Path, after_file_path: &Path, context: u8) -> io::Result<Self> {
         let before_lines = Seq::<String>::read(File::open(before_file_path)?)?;
         let after_lines = Seq::<String>::read(File::open(after_file_path)?)?;
+        let digests = Digests::new(Digest::from(&before_lines), Digest::from(&after_lines));
         let modifications = Changes::<String>::new(before_lines, after_lines);
 
         Ok(Self {
             before_path: before_file_path.to_path_buf(),
             after_path: after_file_path.to_path_buf(),
+            digests: Some(digests),
+            modes: Modes::of_files(before_file_path, after_file_path)?,
             clumps: modifications
                 .change_clumps(context)
-                .map(|c| TextChangeClump::from(c))
+                .map(TextChangeClump::from)
                 .collect(),
         })
     }
 
+    /// A diff made from clumps obtained elsewhere (e.g. parsed from a
+    /// patch in another format).
+    pub fn from_clumps(
+        before_file_path: &Path,
+        after_file_path: &Path,
+        clumps: Vec<TextChangeClump>,
+    ) -> Self {
+        Self {
+            before_path: before_file_path.to_path_buf(),
+            after_path: after_file_path.to_path_buf(),
+            digests: None,
+            modes: None,
+            clumps,
+        }
+    }
+
     pub fn from_reader<R: io::Read>(reader: &mut R) -> Result<Self, serde_json::Error> {
         serde_json::from_reader(reader)
     }
@@ -117,9 +164,54 @@ impl TextChangeDiff {
         &self.after_path
     }
 
+    pub fn digests(&self) -> Option<&Digests> {
+        self.digests.as_ref()
+    }
+
+    /// The file's before and after modes if they differ.
+    pub fn modes(&self) -> Option<&Modes> {
+        self.modes.as_ref()
+    }
+
+    pub fn set_modes(&mut self, modes: Option<Modes>) {
+        self.modes = modes
+    }
+
     pub fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<(), serde_json::Error> {
         serde_json::to_writer_pretty(writer, self)
     }
+
+    /// Apply this diff (regardless of failures) and verify the result against
+    /// the digests recorded when the diff was created.
+    pub fn apply_into_verifying<W: io::Write>(
+        &self,
+        patchable: &Seq<String>,
+        into: &mut W,
+        reverse: bool,
+    ) -> io::Result<(ApplyReport, Verification)> {
+        self.apply_into_verifying_with_matching(patchable, into, LineMatching::Exact, reverse)
+    }
+
+    /// As `apply_into_verifying()` but with context lines matched as
+    /// specified by `matching`.
+    pub fn apply_into_verifying_with_matching<W: io::Write>(
+        &self,
+        patchable: &Seq<String>,
+        into: &mut W,
+        matching: LineMatching,
+        reverse: bool,
+    ) -> io::Result<(ApplyReport, Verification)> {
+        let mut writer = DigestWriter::new(into);
+        let report =
+            self.apply_into_reporting_with_matching(patchable, &mut writer, matching, reverse)?;
+        let verification = match &self.digests {
+            Some(digests) => {
+                digests.verify(&Digest::from(patchable), &writer.digest(), &report, reverse)
+            }
+            None => Verification::Unverifiable,
+        };
+        Ok((report, verification))
+    }
 }
 
 impl ApplyClumpsFuzzy<TextChangeClump> for TextChangeDiff {
@@ -134,6 +226,8 @@ impl ApplyClumpsFuzzy<TextChangeClump> for TextChangeDiff {
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct PathAndLines {
     path: PathBuf,
+    #[serde(default)]
+    mode: Option<u32>,
     lines: Box<[String]>,
 }
 
@@ -153,13 +247,27 @@ impl PathAndLines {
 
         Ok(Self {
             path: path.to_path_buf(),
+            mode: file_mode(path)?,
             lines: lines.into_boxed_slice(),
         })
     }
 
+    pub fn from_lines(path: &Path, mode: Option<u32>, lines: Vec<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            mode,
+            lines: lines.into_boxed_slice(),
+        }
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
+
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
     pub fn change_path(&mut self, new_path: &Path) {
         self.path = new_path.to_path_buf()
     }
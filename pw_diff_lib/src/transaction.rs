@@ -0,0 +1,662 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::apply_bytes::ApplyClumpsClean;
+use crate::apply_text::{ApplyClumpsFuzzy, ApplyReport, LineMatching};
+use crate::backup::Backups;
+use crate::byte_diff::ByteChangeDiff;
+use crate::diff::Diff;
+use crate::diff_set::DiffSet;
+use crate::digest::Verification;
+use crate::metadata::{create_symlink, file_mode, path_exists, set_file_mode, Modes};
+use crate::paths::{confined_path, strip_path};
+use crate::sequence::Seq;
+use crate::text_diff::TextChangeDiff;
+
+/// Control how a `Transaction` applies diffs.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ApplyOptions {
+    pub reverse: bool,
+    pub matching: LineMatching,
+    /// Check results against the digests recorded in the diffs and refuse
+    /// to patch files that aren't the ones the diffs were made from.
+    pub verify: bool,
+    /// Commit even if some clumps could not be applied.
+    pub force: bool,
+    /// The number of leading components to strip from the paths in diffs.
+    pub strip: usize,
+    /// Accept absolute paths and paths leading outside the current
+    /// directory (which are otherwise refused).
+    pub unsafe_paths: bool,
+    /// Allow paths to pass through symbolic links (which are otherwise refused).
+    pub follow_symlinks: bool,
+}
+
+/// What staging a diff will do to its target when committed.
+#[derive(Debug)]
+pub enum Action {
+    Create,
+    Delete,
+    Patch(ApplyReport, Option<Verification>),
+    Rename(PathBuf, ApplyReport, Option<Verification>),
+    Copy(PathBuf, ApplyReport, Option<Verification>),
+    ChangeMode(u32),
+    Repoint(PathBuf),
+}
+
+/// The result of staging a diff: the path that will change and how.
+#[derive(Debug)]
+pub struct Staged {
+    pub path: PathBuf,
+    pub action: Action,
+}
+
+impl Staged {
+    fn new(path: &Path, action: Action) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            action,
+        }
+    }
+
+    /// The report for the clumps applied (if any).
+    pub fn report(&self) -> Option<&ApplyReport> {
+        match &self.action {
+            Action::Patch(report, _)
+            | Action::Rename(_, report, _)
+            | Action::Copy(_, report, _) => Some(report),
+            _ => None,
+        }
+    }
+}
+
+/// The state of a file system entry as it will be after the transaction.
+#[derive(Debug, Clone)]
+enum Entry {
+    File { content: Vec<u8>, mode: Option<u32> },
+    Symlink(PathBuf),
+    Absent,
+}
+
+/// A set of changes to files that are worked out in memory (so that later
+/// diffs see the results of earlier ones) and then committed together.  If
+/// any part of a commit fails the files already changed are restored.
+/// Originals are discarded after a successful commit unless backups are set.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    options: ApplyOptions,
+    order: Vec<PathBuf>,
+    entries: HashMap<PathBuf, Entry>,
+    backups: Option<Backups>,
+    output: Option<PathBuf>,
+    problems: usize,
+}
+
+impl Transaction {
+    pub fn new(options: ApplyOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Keep backups of the files changed (or created) by the commit.
+    pub fn set_backups(&mut self, backups: Option<Backups>) {
+        self.backups = backups
+    }
+
+    /// Write the result to `output` (standard output if it's "-") instead of
+    /// changing files in place.  The diffs must then result in one file.
+    pub fn set_output(&mut self, output: Option<&Path>) {
+        self.output = output.map(|path| path.to_path_buf())
+    }
+
+    /// The number of diffs that could not be staged or had clumps that
+    /// could not be applied (and `force` wasn't set).
+    pub fn problems(&self) -> usize {
+        self.problems
+    }
+
+    fn set(&mut self, path: &Path, entry: Entry) {
+        if self.entries.insert(path.to_path_buf(), entry).is_none() {
+            self.order.push(path.to_path_buf());
+        }
+    }
+
+    fn current(&self, path: &Path) -> io::Result<Entry> {
+        if let Some(entry) = self.entries.get(path) {
+            Ok(entry.clone())
+        } else if !path_exists(path) {
+            Ok(Entry::Absent)
+        } else if fs::symlink_metadata(path)?.file_type().is_symlink() {
+            Ok(Entry::Symlink(fs::read_link(path)?))
+        } else {
+            Ok(Entry::File {
+                content: fs::read(path)?,
+                mode: file_mode(path)?,
+            })
+        }
+    }
+
+    fn exists(&self, path: &Path) -> io::Result<bool> {
+        Ok(!matches!(self.current(path)?, Entry::Absent))
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<(Vec<u8>, Option<u32>)> {
+        match self.current(path)? {
+            Entry::File { content, mode } => Ok((content, mode)),
+            Entry::Symlink(_) => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{path:?}: is a symbolic link"),
+            )),
+            Entry::Absent => Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("{path:?}: does not exist"),
+            )),
+        }
+    }
+
+    fn must_not_exist(&self, path: &Path) -> io::Result<()> {
+        if self.exists(path)? {
+            Err(io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{path:?} can NOT be created: it already exists"),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn must_exist(&self, path: &Path) -> io::Result<()> {
+        if self.exists(path)? {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("{path:?} can NOT be deleted: it does not exist"),
+            ))
+        }
+    }
+
+    fn check_outcome(
+        &mut self,
+        path: &Path,
+        report: &ApplyReport,
+        verification: Option<Verification>,
+    ) -> io::Result<()> {
+        if verification == Some(Verification::WrongBase) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("{path:?}: {}", Verification::WrongBase),
+            ));
+        }
+        if !report.succeeded() && !self.options.force {
+            self.problems += 1;
+        }
+        Ok(())
+    }
+
+    fn modes_after(&self, modes: Option<&Modes>, mode: Option<u32>) -> Option<u32> {
+        match modes {
+            Some(modes) => Some(modes.after(self.options.reverse)),
+            None => mode,
+        }
+    }
+
+    /// The patched content of the file at `path` and the mode it should have.
+    fn patch_text(
+        &mut self,
+        diff: &TextChangeDiff,
+        path: &Path,
+    ) -> io::Result<(Entry, ApplyReport, Option<Verification>)> {
+        let (content, mode) = self.read_file(path)?;
+        let lines = Seq::<String>::read(&content[..])?;
+        let options = self.options;
+        let mut patched = vec![];
+        let (report, verification) = if options.verify {
+            let (report, verification) = diff.apply_into_verifying_with_matching(
+                &lines,
+                &mut patched,
+                options.matching,
+                options.reverse,
+            )?;
+            (report, Some(verification))
+        } else {
+            let report = diff.apply_into_reporting_with_matching(
+                &lines,
+                &mut patched,
+                options.matching,
+                options.reverse,
+            )?;
+            (report, None)
+        };
+        self.check_outcome(path, &report, verification)?;
+        let entry = Entry::File {
+            content: patched,
+            mode: self.modes_after(diff.modes(), mode),
+        };
+        Ok((entry, report, verification))
+    }
+
+    fn patch_bytes(
+        &mut self,
+        diff: &ByteChangeDiff,
+        path: &Path,
+    ) -> io::Result<(Entry, ApplyReport, Option<Verification>)> {
+        let (content, mode) = self.read_file(path)?;
+        let bytes = Seq::<u8>::from(content);
+        let options = self.options;
+        let mut patched = vec![];
+        let (report, verification) = if options.verify {
+            let (report, verification) =
+                diff.apply_into_verifying(&bytes, &mut patched, options.reverse)?;
+            (report, Some(verification))
+        } else {
+            let report = diff.apply_into_reporting(&bytes, &mut patched, options.reverse)?;
+            (report, None)
+        };
+        self.check_outcome(path, &report, verification)?;
+        let entry = Entry::File {
+            content: patched,
+            mode: self.modes_after(diff.modes(), mode),
+        };
+        Ok((entry, report, verification))
+    }
+
+    fn create(&mut self, path: &Path, entry: Entry) -> io::Result<Staged> {
+        self.must_not_exist(path)?;
+        self.set(path, entry);
+        Ok(Staged::new(path, Action::Create))
+    }
+
+    fn delete(&mut self, path: &Path) -> io::Result<Staged> {
+        self.must_exist(path)?;
+        self.set(path, Entry::Absent);
+        Ok(Staged::new(path, Action::Delete))
+    }
+
+    fn rename(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        patched: (Entry, ApplyReport, Option<Verification>),
+    ) -> io::Result<Staged> {
+        self.must_not_exist(to)?;
+        let (entry, report, verification) = patched;
+        self.set(from, Entry::Absent);
+        self.set(to, entry);
+        Ok(Staged::new(
+            to,
+            Action::Rename(from.to_path_buf(), report, verification),
+        ))
+    }
+
+    fn copy(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        patched: (Entry, ApplyReport, Option<Verification>),
+    ) -> io::Result<Staged> {
+        self.must_not_exist(to)?;
+        let (entry, report, verification) = patched;
+        self.set(to, entry);
+        Ok(Staged::new(
+            to,
+            Action::Copy(from.to_path_buf(), report, verification),
+        ))
+    }
+
+    /// The path (relative to the current directory) to use for `path`.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = strip_path(path, self.options.strip)?;
+        if self.options.unsafe_paths {
+            return Ok(path);
+        }
+        let path = confined_path(Path::new("."), &path, self.options.follow_symlinks)?;
+        if !self.options.follow_symlinks {
+            let staged_link = path
+                .ancestors()
+                .skip(1)
+                .find(|ancestor| matches!(self.entries.get(*ancestor), Some(Entry::Symlink(_))));
+            if let Some(link) = staged_link {
+                return Err(io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("{path:?}: unsafe path: it passes through the symbolic link {link:?}"),
+                ));
+            }
+        }
+        Ok(path)
+    }
+
+    fn stage_diff(&mut self, diff: &Diff) -> io::Result<Staged> {
+        let reverse = self.options.reverse;
+        match diff {
+            Diff::TextChange(diff) => {
+                let path = self.resolve(diff.before_path())?;
+                let (entry, report, verification) = self.patch_text(diff, &path)?;
+                self.set(&path, entry);
+                Ok(Staged::new(&path, Action::Patch(report, verification)))
+            }
+            Diff::ByteChange(diff) => {
+                let path = self.resolve(diff.before_path())?;
+                let (entry, report, verification) = self.patch_bytes(diff, &path)?;
+                self.set(&path, entry);
+                Ok(Staged::new(&path, Action::Patch(report, verification)))
+            }
+            Diff::TextAdd(path_and_lines) | Diff::TextRemove(path_and_lines) => {
+                let path = self.resolve(path_and_lines.path())?;
+                if reverse == matches!(diff, Diff::TextAdd(_)) {
+                    self.delete(&path)
+                } else {
+                    let mut content = vec![];
+                    path_and_lines.write_into(&mut content)?;
+                    let mode = path_and_lines.mode();
+                    self.create(&path, Entry::File { content, mode })
+                }
+            }
+            Diff::ByteAdd(path_and_bytes) | Diff::ByteRemove(path_and_bytes) => {
+                let path = self.resolve(path_and_bytes.path())?;
+                if reverse == matches!(diff, Diff::ByteAdd(_)) {
+                    self.delete(&path)
+                } else {
+                    let mut content = vec![];
+                    path_and_bytes.write_into(&mut content)?;
+                    let mode = path_and_bytes.mode();
+                    self.create(&path, Entry::File { content, mode })
+                }
+            }
+            Diff::TextRename(relocation) => {
+                let diff = relocation.diff();
+                let (from, to) = self.rename_paths(diff.before_path(), relocation.new_path())?;
+                let patched = self.patch_text(diff, &from)?;
+                self.rename(&from, &to, patched)
+            }
+            Diff::ByteRename(relocation) => {
+                let diff = relocation.diff();
+                let (from, to) = self.rename_paths(diff.before_path(), relocation.new_path())?;
+                let patched = self.patch_bytes(diff, &from)?;
+                self.rename(&from, &to, patched)
+            }
+            Diff::TextCopy(relocation) => {
+                let diff = relocation.diff();
+                let new_path = self.resolve(relocation.new_path())?;
+                if reverse {
+                    self.delete(&new_path)
+                } else {
+                    let path = self.resolve(diff.before_path())?;
+                    let patched = self.patch_text(diff, &path)?;
+                    self.copy(&path, &new_path, patched)
+                }
+            }
+            Diff::ByteCopy(relocation) => {
+                let diff = relocation.diff();
+                let new_path = self.resolve(relocation.new_path())?;
+                if reverse {
+                    self.delete(&new_path)
+                } else {
+                    let path = self.resolve(diff.before_path())?;
+                    let patched = self.patch_bytes(diff, &path)?;
+                    self.copy(&path, &new_path, patched)
+                }
+            }
+            Diff::ModeChange(mode_change) => {
+                let path = self.resolve(mode_change.path())?;
+                let (content, mode) = self.read_file(&path)?;
+                mode_change.check(mode, reverse)?;
+                let mode = mode_change.modes().after(reverse);
+                self.set(
+                    &path,
+                    Entry::File {
+                        content,
+                        mode: Some(mode),
+                    },
+                );
+                Ok(Staged::new(&path, Action::ChangeMode(mode)))
+            }
+            Diff::SymlinkAdd(symlink) | Diff::SymlinkRemove(symlink) => {
+                let path = self.resolve(symlink.path())?;
+                if reverse == matches!(diff, Diff::SymlinkAdd(_)) {
+                    self.delete(&path)
+                } else {
+                    self.create(&path, Entry::Symlink(symlink.target().to_path_buf()))
+                }
+            }
+            Diff::SymlinkChange(change) => {
+                let path = self.resolve(change.path())?;
+                match self.current(&path)? {
+                    Entry::Symlink(target) if target == change.before_target(reverse) => {
+                        let target = change.after_target(reverse).to_path_buf();
+                        self.set(&path, Entry::Symlink(target.clone()));
+                        Ok(Staged::new(&path, Action::Repoint(target)))
+                    }
+                    _ => Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "{path:?} is not a symbolic link to {:?}",
+                            change.before_target(reverse)
+                        ),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// The (resolved) file to be renamed and its new name taking `reverse` into account.
+    fn rename_paths(&self, before_path: &Path, new_path: &Path) -> io::Result<(PathBuf, PathBuf)> {
+        let before_path = self.resolve(before_path)?;
+        let new_path = self.resolve(new_path)?;
+        if self.options.reverse {
+            Ok((new_path, before_path))
+        } else {
+            Ok((before_path, new_path))
+        }
+    }
+
+    /// Work out the effect of `diff` (in the light of those already staged).
+    /// Nothing on disk is changed until the transaction is committed.
+    pub fn stage(&mut self, diff: &Diff) -> io::Result<Staged> {
+        let result = self.stage_diff(diff);
+        if result.is_err() {
+            self.problems += 1;
+        }
+        result
+    }
+
+    /// Stage all the diffs in `diff_set` (in reverse order if reversing).
+    pub fn stage_set(&mut self, diff_set: &DiffSet) -> Vec<io::Result<Staged>> {
+        if self.options.reverse {
+            diff_set
+                .diffs()
+                .rev()
+                .map(|diff| self.stage(diff))
+                .collect()
+        } else {
+            diff_set.diffs().map(|diff| self.stage(diff)).collect()
+        }
+    }
+
+    /// Make the staged changes to the files.  Nothing is changed if there
+    /// are problems and if any change fails those already made are undone.
+    pub fn commit(self) -> io::Result<()> {
+        if self.problems > 0 {
+            return Err(io::Error::other(format!(
+                "{} problem(s) found: no files were changed",
+                self.problems
+            )));
+        }
+        if let Some(output) = &self.output {
+            return self.commit_to_output(output);
+        }
+        let mut journal = Journal::default();
+        match self.commit_into(&mut journal) {
+            Ok(_) => {
+                if self.backups.is_none() {
+                    journal.discard_backups();
+                }
+                Ok(())
+            }
+            Err(err) => {
+                journal.roll_back();
+                Err(err)
+            }
+        }
+    }
+
+    fn commit_to_output(&self, output: &Path) -> io::Result<()> {
+        let files: Vec<&Entry> = self
+            .order
+            .iter()
+            .map(|path| &self.entries[path])
+            .filter(|entry| !matches!(entry, Entry::Absent))
+            .collect();
+        match files[..] {
+            [Entry::File { content, .. }] if output == Path::new("-") => {
+                io::stdout().write_all(content)
+            }
+            [entry @ Entry::File { .. }] => {
+                let transaction = Self {
+                    order: vec![output.to_path_buf()],
+                    entries: HashMap::from([(output.to_path_buf(), entry.clone())]),
+                    backups: self.backups.clone(),
+                    ..Self::default()
+                };
+                transaction.commit()
+            }
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{output:?}: the patch must produce exactly one regular file"),
+            )),
+        }
+    }
+
+    fn commit_into(&self, journal: &mut Journal) -> io::Result<()> {
+        // Write everything new beside its destination before touching anything.
+        let mut temps = HashMap::new();
+        for path in self.order.iter() {
+            if let Some(Entry::File { content, mode }) = self.entries.get(path) {
+                if let Some(dir) = path.parent() {
+                    journal.create_dir_all(dir)?;
+                }
+                let temp_path = journal.temp_path(path, "new")?;
+                let mut file = fs::File::create_new(&temp_path)?;
+                journal.temps.push(temp_path.clone());
+                file.write_all(content)?;
+                if let Some(mode) = mode {
+                    set_file_mode(&temp_path, *mode)?;
+                }
+                temps.insert(path, temp_path);
+            }
+        }
+        for path in self.order.iter() {
+            let entry = &self.entries[path];
+            if path_exists(path) {
+                let backup_path = match &self.backups {
+                    Some(backups) => backups.keep(path)?,
+                    None => {
+                        let backup_path = journal.temp_path(path, "orig")?;
+                        fs::rename(path, &backup_path)?;
+                        backup_path
+                    }
+                };
+                journal.backups.push((path.clone(), backup_path));
+            } else if let (Some(backups), false) = (&self.backups, matches!(entry, Entry::Absent)) {
+                journal.temps.push(backups.keep_absence(path)?);
+            }
+            match entry {
+                Entry::File { .. } => {
+                    fs::rename(&temps[path], path)?;
+                    journal.installed.push(path.clone());
+                }
+                Entry::Symlink(target) => {
+                    if let Some(dir) = path.parent() {
+                        journal.create_dir_all(dir)?;
+                    }
+                    create_symlink(target, path)?;
+                    journal.installed.push(path.clone());
+                }
+                Entry::Absent => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A record of what a commit has done so far so that it can be undone.
+#[derive(Debug, Default)]
+struct Journal {
+    created_dirs: Vec<PathBuf>,
+    temps: Vec<PathBuf>,
+    backups: Vec<(PathBuf, PathBuf)>,
+    installed: Vec<PathBuf>,
+}
+
+impl Journal {
+    fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+        if dir.as_os_str().is_empty() || dir.is_dir() {
+            return Ok(());
+        }
+        if let Some(parent) = dir.parent() {
+            self.create_dir_all(parent)?;
+        }
+        fs::create_dir(dir)?;
+        self.created_dirs.push(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// An unused path in the same directory as `path` for a temporary file.
+    fn temp_path(&self, path: &Path, tag: &str) -> io::Result<PathBuf> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| {
+                io::Error::new(ErrorKind::InvalidInput, format!("{path:?}: no file name"))
+            })?
+            .to_string_lossy();
+        for i in 0.. {
+            let temp_path = path.with_file_name(format!(
+                ".{file_name}.pw_patch-{tag}-{}-{i}",
+                std::process::id()
+            ));
+            if !path_exists(&temp_path) {
+                return Ok(temp_path);
+            }
+        }
+        unreachable!()
+    }
+
+    fn discard_backups(&mut self) {
+        for (_, backup_path) in self.backups.drain(..) {
+            if let Err(err) = fs::remove_file(&backup_path) {
+                log::warn!("{backup_path:?}: could not be removed: {err}");
+            }
+        }
+    }
+
+    fn roll_back(&mut self) {
+        for path in self.installed.drain(..).rev() {
+            if let Err(err) = fs::remove_file(&path) {
+                log::error!("{path:?}: could not be removed during roll back: {err}");
+            }
+        }
+        for (path, backup_path) in self.backups.drain(..).rev() {
+            if let Err(err) = fs::rename(&backup_path, &path) {
+                log::error!("{path:?}: could not be restored from {backup_path:?}: {err}");
+            }
+        }
+        for temp_path in self.temps.drain(..) {
+            if path_exists(&temp_path) {
+                let _ = fs::remove_file(&temp_path);
+            }
+        }
+        for dir in self.created_dirs.drain(..).rev() {
+            let _ = fs::remove_dir(&dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod transaction_tests;
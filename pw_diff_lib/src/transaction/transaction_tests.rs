@@ -0,0 +1,216 @@
+// Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
+
+use std::fs;
+use std::path::Path;
+
+use crate::diff::Diff;
+use crate::diff_set::DiffSet;
+use crate::transaction::*;
+
+fn write(path: &Path, content: &str) {
+    fs::write(path, content).unwrap();
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+// The diffs in these tests use absolute paths within temporary directories.
+fn unconfined() -> ApplyOptions {
+    ApplyOptions {
+        unsafe_paths: true,
+        ..ApplyOptions::default()
+    }
+}
+
+#[test]
+fn later_diffs_see_earlier_ones() {
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    let file = dir.join("file");
+    let other = dir.join("other");
+    let mut diff_set = DiffSet::default();
+    write(&file, "A\nB\nC\nD\nE\n");
+    write(&other, "A\nB\nX\nD\nE\n");
+    diff_set.push(Diff::new(&file, &other, 1).unwrap());
+    write(&file, "A\nB\nX\nD\nE\n");
+    write(&other, "A\nB\nX\nD\nY\n");
+    diff_set.push(Diff::new(&file, &other, 1).unwrap());
+    write(&file, "A\nB\nX\nD\nY\n");
+    diff_set.push(Diff::new_removal(&file).unwrap());
+    write(&file, "A\nB\nC\nD\nE\n");
+
+    let mut transaction = Transaction::new(unconfined());
+    assert!(transaction.stage_set(&diff_set).iter().all(|r| r.is_ok()));
+    assert_eq!(read(&file), "A\nB\nC\nD\nE\n");
+    transaction.commit().unwrap();
+    assert!(!file.exists());
+
+    let options = ApplyOptions {
+        reverse: true,
+        ..unconfined()
+    };
+    let mut transaction = Transaction::new(options);
+    assert!(transaction.stage_set(&diff_set).iter().all(|r| r.is_ok()));
+    transaction.commit().unwrap();
+    assert_eq!(read(&file), "A\nB\nC\nD\nE\n");
+}
+
+#[test]
+fn nothing_changes_if_any_diff_fails() {
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    write(&dir.join("one"), "1\n2\n3\n");
+    write(&dir.join("one_new"), "1\n2\n3\n4\n");
+    write(&dir.join("two"), "a\nb\nc\n");
+    write(&dir.join("two_new"), "a\nB\nc\n");
+    let diff_set = DiffSet::from(vec![
+        Diff::new(&dir.join("one"), &dir.join("one_new"), 1).unwrap(),
+        Diff::new(&dir.join("two"), &dir.join("two_new"), 1).unwrap(),
+    ]);
+    write(&dir.join("two"), "x\ny\nz\n");
+
+    let mut transaction = Transaction::new(unconfined());
+    let results = transaction.stage_set(&diff_set);
+    assert!(results.iter().all(|r| r.is_ok()));
+    assert!(!results[1].as_ref().unwrap().report().unwrap().succeeded());
+    assert_eq!(transaction.problems(), 1);
+    assert!(transaction.commit().is_err());
+    assert_eq!(read(&dir.join("one")), "1\n2\n3\n");
+    assert_eq!(read(&dir.join("two")), "x\ny\nz\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn failed_commit_is_rolled_back() {
+    use crate::metadata::create_symlink;
+
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    write(&dir.join("one"), "1\n2\n3\n");
+    write(&dir.join("one_new"), "1\n2\n3\n4\n");
+    create_symlink(Path::new("one"), &dir.join("link")).unwrap();
+    write(&dir.join("blocker"), "not a directory\n");
+    let diff_set = DiffSet::from(vec![
+        Diff::new(&dir.join("one"), &dir.join("one_new"), 1).unwrap(),
+        Diff::new_addition(&dir.join("link"), &dir.join("blocker").join("link")).unwrap(),
+    ]);
+
+    let mut transaction = Transaction::new(unconfined());
+    assert!(transaction.stage_set(&diff_set).iter().all(|r| r.is_ok()));
+    assert!(transaction.commit().is_err());
+    assert_eq!(read(&dir.join("one")), "1\n2\n3\n");
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["blocker", "link", "one", "one_new"]);
+}
+
+#[test]
+fn commit_keeps_backups() {
+    use crate::backup::{BackupControl, Backups};
+
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    let file = dir.join("file");
+    let created = dir.join("created");
+    write(&file, "1\n2\n3\n");
+    write(&dir.join("new_content"), "new\n");
+    write(&dir.join("other"), "1\n2\n3\n4\n");
+    let diff_set = DiffSet::from(vec![
+        Diff::new(&created, &dir.join("new_content"), 1).unwrap(),
+        Diff::new(&file, &dir.join("other"), 1).unwrap(),
+    ]);
+
+    let backups = Backups::new(BackupControl::Numbered, ".orig", None);
+    for _ in 0..2 {
+        let mut transaction = Transaction::new(unconfined());
+        transaction.set_backups(Some(backups.clone()));
+        assert!(transaction.stage_set(&diff_set).iter().all(|r| r.is_ok()));
+        transaction.commit().unwrap();
+        assert_eq!(read(&file), "1\n2\n3\n4\n");
+        assert_eq!(read(&created), "new\n");
+        assert!(backups.restore(&file).unwrap());
+        assert!(backups.restore(&created).unwrap());
+        assert_eq!(read(&file), "1\n2\n3\n");
+        assert!(!created.exists());
+    }
+    assert!(!backups.restore(&file).unwrap());
+}
+
+#[test]
+fn output_to_another_file() {
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    let file = dir.join("file");
+    let output = dir.join("output");
+    write(&file, "1\n2\n3\n");
+    write(&dir.join("other"), "1\n2\n3\n4\n");
+    let diff_set = DiffSet::from(vec![
+        Diff::new(&file, &dir.join("other"), 1).unwrap(),
+        Diff::new_addition(&dir.join("other"), &dir.join("added")).unwrap(),
+    ]);
+
+    let mut transaction = Transaction::new(unconfined());
+    transaction.set_output(Some(&output));
+    transaction.stage(diff_set.diffs().next().unwrap()).unwrap();
+    transaction.commit().unwrap();
+    assert_eq!(read(&output), "1\n2\n3\n4\n");
+    assert_eq!(read(&file), "1\n2\n3\n");
+
+    let mut transaction = Transaction::new(unconfined());
+    transaction.set_output(Some(&dir.join("another")));
+    assert!(transaction.stage_set(&diff_set).iter().all(|r| r.is_ok()));
+    assert!(transaction.commit().is_err());
+    assert!(!dir.join("another").exists() && !dir.join("added").exists());
+}
+
+#[test]
+fn unsafe_paths_are_refused() {
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    write(&dir.join("file"), "1\n2\n3\n");
+    write(&dir.join("other"), "1\n2\n3\n4\n");
+    let diff = Diff::new(&dir.join("file"), &dir.join("other"), 1).unwrap();
+
+    let mut transaction = Transaction::new(ApplyOptions::default());
+    let err = transaction.stage(&diff).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert!(err.to_string().contains("absolute"));
+    assert_eq!(transaction.problems(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn mode_changes_check_the_current_mode() {
+    use crate::metadata::{file_mode, set_file_mode, ModeChange, Modes};
+
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    write(&file, "content\n");
+    set_file_mode(&file, 0o644).unwrap();
+    let diff_set = DiffSet::from(vec![Diff::ModeChange(ModeChange::new(
+        &file,
+        Modes::new(0o644, 0o755),
+    ))]);
+
+    let reversed = ApplyOptions {
+        reverse: true,
+        ..unconfined()
+    };
+    let mut transaction = Transaction::new(reversed);
+    assert!(transaction.stage_set(&diff_set)[0].is_err());
+    assert!(transaction.commit().is_err());
+    assert_eq!(file_mode(&file).unwrap(), Some(0o644));
+
+    let mut transaction = Transaction::new(unconfined());
+    assert!(transaction.stage_set(&diff_set)[0].is_ok());
+    transaction.commit().unwrap();
+    assert_eq!(file_mode(&file).unwrap(), Some(0o755));
+
+    let mut transaction = Transaction::new(unconfined());
+    assert!(transaction.stage_set(&diff_set)[0].is_err());
+    assert_eq!(file_mode(&file).unwrap(), Some(0o755));
+}
@@ -1,16 +1,25 @@
 // Copyright 2024 Peter Williams <pwil3058@gmail.com> <pwil3058@bigpond.net.au>
 
+use std::collections::BTreeSet;
 use std::fs;
-use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-use log;
-use stderrlog;
 use stderrlog::LogLevelNum;
 
+use pw_cub_diff_lib::context_diff::ContextPatch;
+use pw_cub_diff_lib::ed_diff::EdPatch;
+use pw_cub_diff_lib::normal_diff::NormalPatch;
+use pw_cub_diff_lib::text_diff::{GnuPatch, ParseWarning, PatchParseError};
+use pw_cub_diff_lib::unified_diff::UnifiedPatch;
 use pw_diff_lib::{
-    apply_bytes::ApplyClumpsClean, apply_text::ApplyClumpsFuzzy, diff::Diff, sequence::Seq,
+    apply_text::{ApplyReport, LineMatching},
+    backup::{BackupControl, Backups},
+    diff_set::DiffSet,
+    digest::Verification,
+    paths::{confined_path, strip_path},
+    sequence::Seq,
+    transaction::{Action, ApplyOptions, Staged, Transaction},
 };
 
 #[derive(Debug, Parser)]
@@ -19,12 +28,315 @@ struct Cli {
     verbose: u8,
     #[arg(short, long, help = "Apply the patch in reverse")]
     reverse: bool,
+    #[arg(
+        short = 'l',
+        long,
+        help = "Ignore differences in whitespace when matching context lines"
+    )]
+    ignore_whitespace: bool,
+    #[arg(long, help = "Report what would happen without changing any files")]
+    dry_run: bool,
+    #[arg(
+        long,
+        conflicts_with = "dry_run",
+        help = "Silently check that the patch applies without changing any files"
+    )]
+    check: bool,
+    #[arg(
+        short,
+        long,
+        help = "Change the files even if some clumps could not be applied"
+    )]
+    force: bool,
+    #[arg(
+        long,
+        help = "Verify the result against the digests recorded in the patch and refuse to patch the wrong file"
+    )]
+    verify: bool,
+    #[arg(short = 'b', long, help = "Back up the original of each changed file")]
+    backup: bool,
+    #[arg(
+        short = 'V',
+        long,
+        value_name = "CONTROL",
+        help = "Name backups according to CONTROL: simple, numbered or existing [default: simple] (implies --backup)"
+    )]
+    version_control: Option<BackupControl>,
+    #[arg(
+        short = 'z',
+        long,
+        value_name = "SUFFIX",
+        help = "Use SUFFIX for simple backups [default: .orig] (implies --backup)"
+    )]
+    suffix: Option<String>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Keep backups in DIR rather than beside their files (implies --backup)"
+    )]
+    backup_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "check"],
+        help = "Restore the files changed by the patch from their most recent backups"
+    )]
+    restore: bool,
+    #[arg(
+        short = 'p',
+        long,
+        value_name = "N",
+        default_value = "0",
+        help = "Strip N leading components from the paths in the patch"
+    )]
+    strip: usize,
+    #[arg(
+        short = 'd',
+        long,
+        value_name = "DIR",
+        help = "Change to DIR before doing anything else (after reading the patch)"
+    )]
+    directory: Option<PathBuf>,
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "FILE",
+        help = "Write the patched file to FILE (\"-\" for standard output) instead of patching in place"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Allow absolute paths and paths leading outside the current directory"
+    )]
+    unsafe_paths: bool,
+    #[arg(long, help = "Allow paths that pass through symbolic links")]
+    follow_symlinks: bool,
+    #[arg(
+        short = 'c',
+        long,
+        help = "Interpret the patch as a context diff (rather than deciding from its content)"
+    )]
+    context: bool,
+    #[arg(
+        short = 'u',
+        long,
+        conflicts_with = "context",
+        help = "Interpret the patch as a unified diff (rather than deciding from its content)"
+    )]
+    unified: bool,
+    #[arg(
+        long,
+        help = "Tolerate damaged unified diffs (wrong clump line counts, blank context lines without their space, CRLF line endings) with warnings"
+    )]
+    lenient: bool,
+    #[arg(
+        short = 'n',
+        long,
+        conflicts_with_all = ["context", "unified"],
+        help = "Interpret the patch as a normal diff (rather than deciding from its content)"
+    )]
+    normal: bool,
+    #[arg(
+        short = 'e',
+        long,
+        conflicts_with_all = ["context", "unified", "normal", "reverse"],
+        help = "Interpret the patch as an ed script (rather than deciding from its content)"
+    )]
+    ed: bool,
     #[arg(required = true)]
     patch_path: PathBuf,
+    #[arg(help = "The file to patch (for normal diffs and ed scripts that don't name it)")]
+    file_path: Option<PathBuf>,
+}
+
+enum Patch {
+    Json(DiffSet),
+    Unified(UnifiedPatch),
+    Context(ContextPatch),
+    Normal(NormalPatch),
+    Ed(EdPatch),
+}
+
+impl Patch {
+    /// Patches made by pw_diff are JSON, anything else is taken to be a
+    /// (possibly multi-file) unified diff or, failing that, context diff,
+    /// normal diff or ed script.
+    fn from_text(text: String, args: &Cli) -> Result<Self, Box<dyn std::error::Error>> {
+        let forced = args.context || args.unified || args.normal || args.ed;
+        if !forced && text.trim_start().starts_with('{') {
+            return Ok(Self::Json(DiffSet::from_reader(&mut text.as_bytes())?));
+        }
+        let lines = Seq::<String>::from(text);
+        let located = |err| PatchParseError::new(err, &lines, Some(&args.patch_path));
+        if !forced || args.unified {
+            let (unified_patch, warnings) = if args.lenient {
+                UnifiedPatch::from_lines_leniently(&lines).map_err(located)?
+            } else {
+                (UnifiedPatch::from_lines(&lines).map_err(located)?, vec![])
+            };
+            if args.unified || !unified_patch.file_diffs.is_empty() {
+                log_warnings(&args.patch_path, &warnings);
+                return Ok(Self::Unified(unified_patch));
+            }
+        }
+        if !forced || args.context {
+            let context_patch = ContextPatch::from_lines(&lines).map_err(located)?;
+            if args.context || !context_patch.file_diffs.is_empty() {
+                return Ok(Self::Context(context_patch));
+            }
+        }
+        if !forced || args.normal {
+            let normal_patch = NormalPatch::from_lines(&lines).map_err(located)?;
+            if args.normal || !normal_patch.file_diffs.is_empty() {
+                return Ok(Self::Normal(normal_patch));
+            }
+        }
+        Ok(Self::Ed(EdPatch::from_lines(&lines).map_err(located)?))
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Json(_) => false,
+            Self::Unified(unified_patch) => unified_patch.file_diffs.is_empty(),
+            Self::Context(context_patch) => context_patch.file_diffs.is_empty(),
+            Self::Normal(normal_patch) => normal_patch.file_diffs.is_empty(),
+            Self::Ed(ed_patch) => ed_patch.file_diffs.is_empty(),
+        }
+    }
+}
+
+fn log_warnings(patch_path: &Path, warnings: &[ParseWarning]) {
+    for warning in warnings {
+        log::warn!(
+            "{}:{}: {}",
+            patch_path.display(),
+            warning.line_index + 1,
+            warning.message
+        );
+    }
+}
+
+fn show_verification(path: &Path, verification: &Option<Verification>) {
+    match verification {
+        Some(Verification::Exact) => log::info!("{path:?}: {}", Verification::Exact),
+        Some(verification) => log::warn!("{path:?}: {verification}"),
+        None => (),
+    }
+}
+
+fn show_report(path: &Path, report: &ApplyReport) {
+    println!("checking {path:?}");
+    for (i, outcome) in report.outcomes.iter().enumerate() {
+        println!("{} {outcome}.", report.clump_label(i + 1));
+    }
+}
+
+/// Describe what committing `staged` would do.
+fn show_staged(staged: &Staged) {
+    let path = &staged.path;
+    match &staged.action {
+        Action::Create => println!("{path:?} would be created."),
+        Action::Delete => println!("{path:?} would be deleted."),
+        Action::Patch(report, _) => show_report(path, report),
+        Action::Rename(from, report, _) => {
+            show_report(from, report);
+            println!("{from:?} would be renamed to {path:?}.");
+        }
+        Action::Copy(from, report, _) => {
+            show_report(from, report);
+            println!("{from:?} would be copied to {path:?}.");
+        }
+        Action::ChangeMode(mode) => println!("{path:?} mode would be changed to {mode:o}."),
+        Action::Repoint(target) => println!("{path:?} would be pointed at {target:?}."),
+    }
+}
+
+/// Log what committing `staged` will do.
+fn log_staged(staged: &Staged, force: bool) {
+    let path = &staged.path;
+    match &staged.action {
+        Action::Create => log::info!("{path:?}: create"),
+        Action::Delete => log::info!("{path:?}: delete"),
+        Action::Patch(_, verification) => show_verification(path, verification),
+        Action::Rename(from, _, verification) => {
+            log::info!("{from:?}: rename to {path:?}");
+            show_verification(path, verification);
+        }
+        Action::Copy(from, _, verification) => {
+            log::info!("{from:?}: copy to {path:?}");
+            show_verification(path, verification);
+        }
+        Action::ChangeMode(mode) => log::info!("{path:?}: change mode to {mode:o}"),
+        Action::Repoint(target) => log::info!("{path:?}: point at {target:?}"),
+    }
+    if let Some(report) = staged.report() {
+        let stats = report.statistics();
+        if stats.failed > 0 {
+            if force {
+                log::warn!("{path:?}: {} clump(s) could NOT be applied", stats.failed);
+            } else {
+                log::error!("{path:?}: {} clump(s) could NOT be applied", stats.failed);
+            }
+        } else {
+            log::info!("{path:?}: {stats:?}");
+        }
+    }
+}
+
+impl Cli {
+    fn backups(&self) -> Option<Backups> {
+        if self.backup
+            || self.version_control.is_some()
+            || self.suffix.is_some()
+            || self.backup_dir.is_some()
+        {
+            Some(Backups::new(
+                self.version_control.unwrap_or_default(),
+                self.suffix.as_deref().unwrap_or(".orig"),
+                self.backup_dir.as_deref(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Restore the files changed by `diff_set` from their backups.
+fn restore(diff_set: &DiffSet, backups: &Backups, args: &Cli) -> bool {
+    let mut paths = BTreeSet::new();
+    for path in diff_set.diffs().flat_map(|diff| diff.paths()) {
+        let resolved = strip_path(path, args.strip).and_then(|path| {
+            if args.unsafe_paths {
+                Ok(path)
+            } else {
+                confined_path(Path::new("."), &path, args.follow_symlinks)
+            }
+        });
+        match resolved {
+            Ok(path) => {
+                paths.insert(path);
+            }
+            Err(err) => {
+                log::error!("{err}");
+                return false;
+            }
+        }
+    }
+    let mut ok = true;
+    for path in paths.iter() {
+        match backups.restore(path) {
+            Ok(true) => log::info!("{path:?}: restored"),
+            Ok(false) => log::warn!("{path:?}: no backup found"),
+            Err(err) => {
+                log::error!("{path:?}: restore failed: {err}");
+                ok = false;
+            }
+        }
+    }
+    ok
 }
 
 fn main() {
-    let args = Cli::parse();
+    let mut args = Cli::parse();
 
     stderrlog::new()
         .module(module_path!())
@@ -32,214 +344,123 @@ fn main() {
         .init()
         .unwrap();
 
-    let mut patch_file = match File::open(&args.patch_path) {
-        Ok(file) => file,
+    let patch_text = match fs::read_to_string(&args.patch_path) {
+        Ok(text) => text,
         Err(err) => {
-            log::error!("Error opening {:?}: {err}", args.patch_path);
+            log::error!("Error reading {:?}: {err}", args.patch_path);
             std::process::exit(1);
         }
     };
 
-    let diff: Diff = match Diff::from_reader(&mut patch_file) {
-        Ok(diff) => diff,
+    let mut patch = match Patch::from_text(patch_text, &args) {
+        Ok(patch) => patch,
         Err(err) => {
             log::error!("Error reading patch file: {err}");
             std::process::exit(1)
         }
     };
+    if patch.is_empty() {
+        log::error!("Error: no diffs found in {:?}", args.patch_path);
+        std::process::exit(1)
+    }
 
-    match diff {
-        Diff::TextChange(diff) => {
-            let patchable_path = diff.before_path();
-            let patchable_file = match File::open(patchable_path) {
-                Ok(file) => file,
-                Err(err) => {
-                    log::error!("Error opening {patchable_path:?}: {err}");
-                    std::process::exit(1);
-                }
-            };
-            let patchable_lines = match Seq::<String>::read(patchable_file) {
-                Ok(lines) => lines,
-                Err(err) => {
-                    log::error!("Error reading {patchable_path:?}: {err}");
-                    std::process::exit(1);
-                }
-            };
+    if let Some(dir) = &args.directory {
+        if let Err(err) = std::env::set_current_dir(dir) {
+            log::error!("Error changing to {dir:?}: {err}");
+            std::process::exit(1);
+        }
+    }
 
-            match temp_file::TempFile::in_dir(".") {
-                Ok(temp_file) => {
-                    let mut writer = match File::create(temp_file.path()) {
-                        Ok(file) => file,
-                        Err(err) => {
-                            log::error!("Error opening temporary file: {err}");
-                            std::process::exit(1);
-                        }
-                    };
-                    match diff.apply_into(&patchable_lines, &mut writer, args.reverse) {
-                        Ok(stats) => {
-                            match std::fs::rename(temp_file.path(), patchable_path) {
-                                Ok(_) => log::info!("{stats:?}"),
-                                Err(err) => {
-                                    log::error!("Error writing patched file: {err}");
-                                    std::process::exit(1);
-                                }
-                            };
-                        }
-                        Err(err) => {
-                            log::error!("Patch failed to apply: {err}");
-                            std::process::exit(1);
-                        }
-                    }
-                }
-                Err(err) => {
-                    log::error!("Error creating temp file: {err}");
-                    std::process::exit(1)
-                }
+    if args.file_path.is_some() && !matches!(patch, Patch::Normal(_) | Patch::Ed(_)) {
+        log::error!("Error: only normal diffs and ed scripts can be applied to a named file");
+        std::process::exit(1)
+    }
+    if args.reverse && matches!(patch, Patch::Ed(_)) {
+        log::error!("Error: ed scripts can't be applied in reverse");
+        std::process::exit(1)
+    }
+
+    // Whether CRs are stray depends on the files' line endings.
+    if let (true, Patch::Unified(unified_patch)) = (args.lenient, &mut patch) {
+        match unified_patch.drop_stray_crs(args.strip) {
+            Ok(warnings) => log_warnings(&args.patch_path, &warnings),
+            Err(err) => {
+                log::error!("Error: {err}");
+                std::process::exit(1)
             }
         }
-        Diff::TextAdd(path_and_lines) => {
-            let path = path_and_lines.path();
-            if args.reverse {
-                match fs::remove_file(path) {
-                    Ok(_) => log::info!("{path:?} deleted"),
-                    Err(err) => {
-                        log::error!("{path:?} deletion failed: {err}");
-                        std::process::exit(1)
-                    }
-                }
-            } else {
-                match File::create_new(path) {
-                    Ok(mut file) => match path_and_lines.write_into(&mut file) {
-                        Ok(_) => log::info!("{path:?} created"),
-                        Err(err) => {
-                            log::error!("{path:?} creation failed: {err}")
-                        }
-                    },
-                    Err(err) => {
-                        log::error!("{path:?} creation failed: {err}")
-                    }
-                }
-            }
+    }
+
+    // Which files the text diffs apply to depends on which files exist (and
+    // ed scripts depend on the files' contents).
+    let diff_set = match patch {
+        Patch::Json(diff_set) => Ok(diff_set),
+        Patch::Context(context_patch) => context_patch.to_diff_set(args.strip, None),
+        Patch::Unified(unified_patch) => unified_patch.to_diff_set(args.strip, None),
+        Patch::Normal(normal_patch) => {
+            normal_patch.to_diff_set(args.strip, args.file_path.as_deref())
         }
-        Diff::TextRemove(path_and_lines) => {
-            let path = path_and_lines.path();
-            if args.reverse {
-                match File::create_new(path) {
-                    Ok(mut file) => match path_and_lines.write_into(&mut file) {
-                        Ok(_) => log::info!("{path:?} created"),
-                        Err(err) => {
-                            log::error!("{path:?} creation failed: {err}")
-                        }
-                    },
-                    Err(err) => {
-                        log::error!("{path:?} creation failed: {err}")
-                    }
-                }
-            } else {
-                match fs::remove_file(path) {
-                    Ok(_) => log::info!("{path:?} deleted"),
-                    Err(err) => {
-                        log::error!("{path:?} deletion failed: {err}");
-                        std::process::exit(1)
-                    }
-                }
-            }
+        Patch::Ed(ed_patch) => ed_patch.to_diff_set(args.strip, args.file_path.as_deref()),
+    };
+    let diff_set = match diff_set {
+        Ok(diff_set) => diff_set,
+        Err(err) => {
+            log::error!("Error: {err}");
+            std::process::exit(1)
         }
-        Diff::ByteChange(diff) => {
-            let patchable_path = diff.before_path();
-            let patchable_file = match File::open(patchable_path) {
-                Ok(file) => file,
-                Err(err) => {
-                    log::error!("Error opening {patchable_path:?}: {err}");
-                    std::process::exit(1);
-                }
-            };
-            let patchable_bytes = match Seq::<u8>::read(patchable_file) {
-                Ok(lines) => lines,
-                Err(err) => {
-                    log::error!("Error reading {patchable_path:?}: {err}");
-                    std::process::exit(1);
-                }
-            };
+    };
+    // A file named on the command line is used as is.
+    if args.file_path.is_some() {
+        args.strip = 0;
+    }
 
-            match temp_file::TempFile::in_dir(".") {
-                Ok(temp_file) => {
-                    let mut writer = match File::create(temp_file.path()) {
-                        Ok(file) => file,
-                        Err(err) => {
-                            log::error!("Error opening temporary file: {err}");
-                            std::process::exit(1);
-                        }
-                    };
-                    match diff.apply_into(&patchable_bytes, &mut writer, args.reverse) {
-                        Ok(stats) => {
-                            match std::fs::rename(temp_file.path(), patchable_path) {
-                                Ok(_) => log::info!("{stats:?}"),
-                                Err(err) => {
-                                    log::error!("Error writing patched file: {err}");
-                                    std::process::exit(1);
-                                }
-                            };
-                        }
-                        Err(err) => {
-                            log::error!("Patch failed to apply: {err}");
-                            std::process::exit(1);
-                        }
-                    }
-                }
-                Err(err) => {
-                    log::error!("Error creating temp file: {err}");
-                    std::process::exit(1)
-                }
-            }
+    let matching = if args.ignore_whitespace {
+        LineMatching::IgnoreWhitespace
+    } else {
+        LineMatching::Exact
+    };
+
+    if !diff_set.description().is_empty() {
+        log::info!("{}", diff_set.description());
+    }
+
+    if args.restore {
+        if !restore(&diff_set, &args.backups().unwrap_or_default(), &args) {
+            std::process::exit(1)
         }
-        Diff::ByteAdd(path_and_bytes) => {
-            let path = path_and_bytes.path();
-            if args.reverse {
-                match fs::remove_file(path) {
-                    Ok(_) => log::info!("{path:?} deleted"),
-                    Err(err) => {
-                        log::error!("{path:?} deletion failed: {err}");
-                        std::process::exit(1)
-                    }
-                }
-            } else {
-                match File::create_new(path) {
-                    Ok(mut file) => match path_and_bytes.write_into(&mut file) {
-                        Ok(_) => log::info!("{path:?} created"),
-                        Err(err) => {
-                            log::error!("{path:?} creation failed: {err}")
-                        }
-                    },
-                    Err(err) => {
-                        log::error!("{path:?} creation failed: {err}")
-                    }
+        return;
+    }
+
+    let mut transaction = Transaction::new(ApplyOptions {
+        reverse: args.reverse,
+        matching,
+        verify: args.verify,
+        force: args.force,
+        strip: args.strip,
+        unsafe_paths: args.unsafe_paths,
+        follow_symlinks: args.follow_symlinks,
+    });
+    transaction.set_backups(args.backups());
+    transaction.set_output(args.output.as_deref());
+    for result in transaction.stage_set(&diff_set) {
+        match result {
+            Ok(staged) => {
+                if args.dry_run {
+                    show_staged(&staged);
+                } else if !args.check {
+                    log_staged(&staged, args.force);
                 }
             }
+            Err(err) => log::error!("{err}"),
         }
-        Diff::ByteRemove(path_and_bytes) => {
-            let path = path_and_bytes.path();
-            if args.reverse {
-                match File::create_new(path) {
-                    Ok(mut file) => match path_and_bytes.write_into(&mut file) {
-                        Ok(_) => log::info!("{path:?} created"),
-                        Err(err) => {
-                            log::error!("{path:?} creation failed: {err}")
-                        }
-                    },
-                    Err(err) => {
-                        log::error!("{path:?} creation failed: {err}")
-                    }
-                }
-            } else {
-                match fs::remove_file(path) {
-                    Ok(_) => log::info!("{path:?} deleted"),
-                    Err(err) => {
-                        log::error!("{path:?} deletion failed: {err}");
-                        std::process::exit(1)
-                    }
-                }
-            }
+    }
+
+    if args.dry_run || args.check {
+        if transaction.problems() > 0 {
+            std::process::exit(1)
         }
+    } else if let Err(err) = transaction.commit() {
+        log::error!("{err}");
+        std::process::exit(1)
     }
 }